@@ -1,393 +1,13418 @@
 // lib.rs
-use ink_lang as ink;
-use scale::Encode;
-use scale_info::TypeInfo;
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+/// The subset of the PSP22 fungible token standard used for rent payments.
+pub mod psp22 {
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::primitives::AccountId;
+
+    pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+    pub const TRANSFER_SELECTOR: [u8; 4] = [0xdb, 0x20, 0xf9, 0xf5];
+    pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x54, 0xb3, 0xc7, 0x6e];
+    pub const BALANCE_OF_SELECTOR: [u8; 4] = [0x65, 0x68, 0x38, 0x2f];
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    #[ink::trait_definition]
+    pub trait PSP22 {
+        #[ink(message, selector = 0x6568382f)]
+        fn balance_of(&self, owner: AccountId) -> Balance;
+
+        #[ink(message, selector = 0xdb20f9f5)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+
+        #[ink(message, selector = 0x54b3c76e)]
+        fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            data: Vec<u8>,
+        ) -> Result<(), PSP22Error>;
+    }
+}
+
+/// The subset of the PSP34 non-fungible token standard used for lease tokens.
+pub mod psp34 {
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::primitives::AccountId;
+
+    pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Id {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP34Error {
+        Custom(String),
+        SelfApprove,
+        NotApproved,
+        TokenExists,
+        TokenNotExists,
+        SafeTransferCheckFailed(String),
+    }
+
+    #[ink::trait_definition]
+    pub trait PSP34 {
+        #[ink(message)]
+        fn collection_id(&self) -> Id;
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u32;
+
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId>;
+
+        #[ink(message)]
+        fn total_supply(&self) -> Balance;
+
+        #[ink(message)]
+        fn transfer(&mut self, to: AccountId, id: Id, data: Vec<u8>) -> Result<(), PSP34Error>;
+    }
+
+    #[ink::trait_definition]
+    pub trait PSP34Metadata {
+        /// Returns attribute `key` of token `id`, or of the collection when
+        /// `id` is the collection id.
+        #[ink(message)]
+        fn get_attribute(&self, id: Id, key: Vec<u8>) -> Option<Vec<u8>>;
+    }
+}
+
+/// Interface a successor contract implements to take over escrows migrated
+/// from an older deployment.
+pub mod rental_escrow {
+    use ink::prelude::vec::Vec;
+    use ink::primitives::Hash;
+
+    pub const IMPORT_ESCROW_SELECTOR: [u8; 4] = [0xe4, 0xfc, 0xbb, 0x93];
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum ImportError {
+        /// The successor already holds an escrow with this id.
+        IdExists,
+        /// The successor cannot decode this escrow layout.
+        UnsupportedVersion,
+        /// The successor refused the escrow for another reason.
+        Rejected,
+    }
+
+    #[ink::trait_definition]
+    pub trait RentalEscrow {
+        /// Takes over `escrow_id`. `escrow` is the SCALE-encoded escrow state
+        /// of the sending contract, followed by its `Option<Agreement>` and the
+        /// `(AccountId, Balance)` signatures and deposits of co-renters on an
+        /// open listing; native funds held by the escrow are transferred with
+        /// the call.
+        #[ink(message, payable, selector = 0xe4fcbb93)]
+        fn import_escrow(&mut self, escrow_id: Hash, escrow: Vec<u8>) -> Result<(), ImportError>;
+    }
+}
+
+/// Price feed consulted for escrows whose rent is pegged to a fiat amount.
+pub mod price_oracle {
+    use ink::primitives::AccountId;
+
+    pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+    pub const LATEST_PRICE_SELECTOR: [u8; 4] = [0x61, 0x36, 0x7d, 0x38];
+
+    /// `rate` is the price of one fiat unit in the payment currency, scaled
+    /// by `RATE_SCALE`.
+    pub const RATE_SCALE: Balance = 1_000_000_000;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Price {
+        pub rate: Balance,
+        pub updated_at: u64,
+    }
+
+    #[ink::trait_definition]
+    pub trait PriceOracle {
+        /// Current price of `token`, or of the native currency for `None`.
+        #[ink(message, selector = 0x61367d38)]
+        fn latest_price(&self, token: Option<AccountId>) -> Price;
+    }
+}
+
+/// Yield source that idle escrow deposits in the native currency can be
+/// lent to. Shares are tracked by the escrow contract; the strategy only
+/// holds one balance for it.
+pub mod yield_strategy {
+    use ink::primitives::AccountId;
+
+    pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+
+    pub const DEPOSIT_SELECTOR: [u8; 4] = [0x61, 0x4f, 0xbb, 0xfa];
+    pub const WITHDRAW_SELECTOR: [u8; 4] = [0x87, 0x41, 0x8e, 0x1a];
+    pub const BALANCE_OF_SELECTOR: [u8; 4] = [0x9d, 0x58, 0x16, 0x0b];
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum StrategyError {
+        /// The strategy cannot pay out the requested amount right now.
+        InsufficientLiquidity,
+        /// The strategy refused the call for another reason.
+        Rejected,
+    }
+
+    #[ink::trait_definition]
+    pub trait YieldStrategy {
+        /// Credits the transferred value to the caller.
+        #[ink(message, payable, selector = 0x614fbbfa)]
+        fn deposit(&mut self) -> Result<(), StrategyError>;
+
+        /// Sends `amount` of the caller's balance back to it.
+        #[ink(message, selector = 0x87418e1a)]
+        fn withdraw(&mut self, amount: Balance) -> Result<(), StrategyError>;
+
+        /// Current value of `owner`'s balance, including accrued yield.
+        #[ink(message, selector = 0x9d58160b)]
+        fn balance_of(&self, owner: AccountId) -> Balance;
+    }
+}
 
 #[ink::contract]
 mod my_psp34 {
-    use ink_storage::collections::HashMap;
-    use scale::Decode;
+    use crate::price_oracle::{self, Price};
+    use crate::psp22::{self, PSP22Error};
+    use crate::psp34::{Id, PSP34Error, PSP34Metadata, PSP34};
+    use crate::rental_escrow::{self, ImportError};
+    use crate::yield_strategy::{self, StrategyError};
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::Blake2x256;
+    use ink::prelude::{string::ToString, vec::Vec};
+    use ink::storage::{Lazy, Mapping};
+    use scale::{Decode, Encode};
+
+    /// Reasons a message can be rejected.
+    #[derive(Debug, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum EscrowError {
+        /// No escrow is stored under the given id.
+        EscrowNotFound,
+        /// The escrow has already been leased.
+        AlreadyLeased,
+        /// The escrow has not been leased yet.
+        NotLeased,
+        /// The caller is not the renter of the escrow.
+        NotRenter,
+        /// The caller is not the landlord of the escrow.
+        NotLandlord,
+        /// The transferred value is below the rent amount.
+        InsufficientRent,
+        /// The lease duration has not passed yet.
+        LeaseNotExpired,
+        /// Transferring the escrow balance failed.
+        TransferFailed,
+        /// The transferred value is below the security deposit.
+        InsufficientDeposit,
+        /// The security deposit has already been paid.
+        DepositAlreadyPaid,
+        /// The security deposit has not been paid.
+        DepositNotPaid,
+        /// The deduction is larger than the held deposit.
+        DeductionExceedsDeposit,
+        /// The security deposit must be released before the lease is closed.
+        DepositNotSettled,
+        /// The payment interval is zero or longer than the lease.
+        InvalidPaymentInterval,
+        /// Every rent period of the lease has already been paid.
+        LeaseFullyPaid,
+        /// No paid rent period has matured yet.
+        NothingToClaim,
+        /// The late fee is above 100% of the rent amount.
+        InvalidLateFee,
+        /// The escrow has no arbiter to hear a dispute.
+        NoArbiter,
+        /// The caller is not the arbiter of the escrow.
+        NotArbiter,
+        /// The caller is neither the renter nor the landlord.
+        NotParty,
+        /// The escrow is frozen by an open dispute.
+        Disputed,
+        /// The escrow has no open dispute.
+        NotDisputed,
+        /// The shares do not add up to the funds held by the escrow.
+        InvalidShares,
+        /// The listing has already been taken by a renter.
+        NotOpen,
+        /// The amount is larger than the held deposit.
+        AmountExceedsDeposit,
+        /// The escrow cannot move from its current status to the requested one.
+        InvalidTransition { from: LeaseStatus, to: LeaseStatus },
+        /// Native value was sent to an escrow paid in a PSP22 token.
+        UnexpectedNativeValue,
+        /// A PSP22 transfer into or out of the contract failed.
+        TokenTransferFailed,
+        /// The successor contract did not accept the migrated escrow.
+        MigrationFailed,
+        /// The caller is not the contract owner.
+        NotOwner,
+        /// The arbiter's response deadline has not passed yet.
+        ArbiterStillActive,
+        /// No registered arbiter is eligible to take over the dispute.
+        NoArbiterAvailable,
+        /// The amount must be greater than zero.
+        ZeroAmount,
+        /// A reimbursement claim is already waiting for the landlord.
+        ClaimPending,
+        /// There is no pending reimbursement claim.
+        NoPendingClaim,
+        /// The settlement is waiting for the renter's confirmation or the
+        /// confirmation timeout.
+        SettlementPending,
+        /// There is no queued settlement for the escrow.
+        NoPendingSettlement,
+        /// The escrow does not collect common-area-maintenance charges.
+        NotCommercial,
+        /// A year has not passed since the last CAM reconciliation.
+        ReconciliationNotDue,
+        /// There is no CAM statement waiting for acknowledgement.
+        NoPendingStatement,
+        /// The acknowledged statement hash differs from the submitted one.
+        StatementMismatch,
+        /// The lease does not end within the renewal window yet.
+        RenewalNotDue,
+        /// There is no unexpired renewal proposal for the escrow.
+        NoPendingRenewal,
+        /// The percentage rent rate is above 100%.
+        InvalidPercentageRent,
+        /// The lease has no percentage rent component.
+        NoPercentageRent,
+        /// A sales report is still inside its challenge window.
+        ReportPending,
+        /// There is no sales report waiting for the challenge window.
+        NoPendingReport,
+        /// The sales report can no longer be challenged.
+        ChallengeWindowClosed,
+        /// The contract is paused by its owner.
+        Paused,
+        /// No failed payout is stored under the given id.
+        PayoutNotFound,
+        /// The platform fee is above 100%.
+        InvalidFee,
+        /// The caller is not the fee collector.
+        NotFeeCollector,
+        /// The co-renter has already accepted the listing.
+        AlreadySigned,
+        /// The amount is part of the tokens tracked by escrow accounting.
+        AmountTracked,
+        /// No matching token recovery has been scheduled.
+        RecoveryNotScheduled,
+        /// The recovery timelock has not passed yet.
+        TimelockActive,
+        /// The runtime rejected the new code hash.
+        UpgradeFailed,
+        /// Storage already has the layout this code expects.
+        NothingToMigrate,
+        /// A rent peg needs a non-zero fiat amount.
+        InvalidRentPeg,
+        /// The price oracle could not be queried or returned no price.
+        OracleUnavailable,
+        /// The oracle price is older than the allowed maximum age.
+        StalePrice,
+        /// A balance or timestamp calculation would overflow.
+        Overflow,
+        /// The current rent period has not opened yet.
+        PaymentNotDue,
+        /// The vault holds less than the amount requested.
+        InsufficientVaultBalance,
+        /// The metadata URI is longer than `MAX_METADATA_URI_LEN`.
+        MetadataTooLong,
+        /// An escrow with this id already exists.
+        IdExists,
+        /// The renter has paused automatic payments from their vault.
+        VaultPaused,
+        /// The draw would exceed a spending cap set by the renter.
+        SpendingLimitExceeded,
+        /// The deposit plan leaves nothing to finance, has no installments or
+        /// interval, or runs past the end of the lease.
+        InvalidDepositPlan,
+        /// The escrow has no outstanding deposit installment.
+        NoInstallmentDue,
+        /// No assistance program is stored under the given id.
+        ProgramNotFound,
+        /// The program has been terminated by its sponsor.
+        ProgramInactive,
+        /// The caller is not the program's sponsor.
+        NotSponsor,
+        /// The coverage is zero or above 100%, the rent band is empty or no
+        /// enrollments are allowed.
+        InvalidProgram,
+        /// The escrow does not meet the program's criteria.
+        NotEligible,
+        /// The program has reached its enrollment cap.
+        EnrollmentFull,
+        /// The escrow is already enrolled in an active program.
+        AlreadyEnrolled,
+        /// The escrow is not enrolled in a program.
+        NotEnrolled,
+        /// The escrow does not accept partial rent payments.
+        PartialPaymentsNotAllowed,
+        /// Rent for a period that has fallen due is still outstanding.
+        RentInArrears,
+        /// The escrow does not require insurance.
+        NoInsuranceRequirement,
+        /// The caller is not the escrow's insurer.
+        NotInsurer,
+        /// The policy expiry is not in the future.
+        InvalidPolicyExpiry,
+        /// The policy is valid or its lapse has already been reported.
+        InsuranceNotLapsed,
+        /// The lease has no valid insurance policy.
+        InsuranceLapsed,
+        /// The escrow streams its rent; use `withdraw_earned`.
+        RentIsStreamed,
+        /// The escrow does not stream its rent.
+        RentNotStreamed,
+        /// The lease has not been terminated.
+        NotTerminated,
+        /// Weights can only be looked up for blocks that have completed.
+        FutureBlock,
+        /// There is no sublease request for the escrow.
+        NoSubleaseRequest,
+        /// The sublease would run past the end of the parent lease.
+        SubleaseOutlastsLease,
+        /// The caller has already committed to the inspector draw.
+        AlreadyCommitted,
+        /// Both parties must commit before secrets are revealed.
+        InspectionNotAgreed,
+        /// The secret does not match the caller's commitment.
+        InvalidReveal,
+        /// No registered inspector is eligible for the escrow.
+        NoInspectorAvailable,
+        /// The escrow has no property manager.
+        NoManager,
+        /// The batch has more than `MAX_BATCH_SIZE` items.
+        BatchTooLarge,
+        /// The payment would take the escrow above the balance cap.
+        BalanceCapExceeded,
+        /// A party to the escrow has not passed identity verification.
+        NotVerified,
+        /// The payment would exceed the canary value limits.
+        CanaryLimitExceeded,
+        /// No change to the canary limits is scheduled.
+        NoPendingCanaryChange,
+        /// A protection plan needs a fee, may not cover more than the fees
+        /// the lease pays in, and rules out a security deposit.
+        InvalidProtectionPlan,
+        /// The escrow has no protection plan.
+        NotProtected,
+        /// The claim is above the plan's cover or the pool's balance.
+        ClaimExceedsCover,
+        /// The landlord has already claimed against the pool for this lease.
+        ProtectionAlreadyClaimed,
+        /// There is no pending withdrawal with the given id.
+        WithdrawalNotFound,
+        /// The caller is not the recipient of the withdrawal.
+        NotRecipient,
+        /// The withdrawal has been vetoed.
+        WithdrawalVetoed,
+        /// Only a vetoed withdrawal can be requeued.
+        WithdrawalNotVetoed,
+        /// No yield strategy is configured.
+        NoYieldStrategy,
+        /// The escrow does not take part in the yield strategy.
+        YieldNotEnabled,
+        /// Yield needs a native-currency lease and a renter share of at most
+        /// 100%.
+        InvalidYieldSplit,
+        /// The escrow's deposit is already invested.
+        AlreadyInvested,
+        /// A call to the yield strategy failed.
+        StrategyCallFailed,
+        /// The strategy cannot be changed while escrows are invested in it.
+        StrategyInUse,
+        /// The escrow has no open counter-offer.
+        NoCounterOffer,
+        /// Another renter's counter-offer on the listing is still open.
+        CounterOfferPending,
+        /// Joint ownership needs distinct co-owners other than the landlord
+        /// and a threshold between one and the number of owners.
+        InvalidJointOwnership,
+        /// The escrow's property has a single owner.
+        NotJointlyOwned,
+        /// The action lacks the approvals its joint owners require.
+        ApprovalsMissing,
+        /// The insurance premium is zero or above 100%, or the policy covers
+        /// no periods or never treats a renter as in default.
+        InvalidInsurancePolicy,
+        /// The owner has not set up rent-default insurance.
+        NoInsurancePolicy,
+        /// The escrow is not insured against rent default.
+        NotInsured,
+        /// The renter is not far enough behind on rent for a claim.
+        NotInDefault,
+        /// An insurance claim for the escrow is waiting for adjudication.
+        InsuranceClaimPending,
+        /// There is no insurance claim waiting for adjudication.
+        NoInsuranceClaim,
+        /// The insurance fund has already paid out for this lease.
+        InsuranceAlreadyClaimed,
+        /// The contract is being decommissioned and takes no new leases.
+        Decommissioned,
+        /// No decommission has been scheduled.
+        DecommissionNotScheduled,
+        /// An escrow still runs a lease or holds funds.
+        LeasesRemaining,
+        /// The caller has no credit to withdraw in the currency.
+        NoCredit,
+        /// The escrow carries state `import_escrow` cannot hand over.
+        MigrationUnsupported,
+        /// A sublease must run for a non-zero duration.
+        InvalidSubleaseDuration,
+        /// There is no protection claim waiting to be paid or decided.
+        NoProtectionClaim,
+        /// The renter disputed the protection claim, so it needs a decision.
+        ProtectionClaimDisputed,
+        /// The time to reveal an inspection secret has passed.
+        RevealWindowClosed,
+    }
+
+    pub type Result<T> = core::result::Result<T, EscrowError>;
+
+    /// Balance, counter and timestamp arithmetic that reports `Overflow`
+    /// instead of trapping or wrapping.
+    trait CheckedMath: Sized {
+        fn try_add(self, other: Self) -> Result<Self>;
+        fn try_sub(self, other: Self) -> Result<Self>;
+        fn try_mul(self, other: Self) -> Result<Self>;
+    }
+
+    macro_rules! impl_checked_math {
+        ($($t:ty),*) => {$(
+            impl CheckedMath for $t {
+                fn try_add(self, other: Self) -> Result<Self> {
+                    self.checked_add(other).ok_or(EscrowError::Overflow)
+                }
+
+                fn try_sub(self, other: Self) -> Result<Self> {
+                    self.checked_sub(other).ok_or(EscrowError::Overflow)
+                }
+
+                fn try_mul(self, other: Self) -> Result<Self> {
+                    self.checked_mul(other).ok_or(EscrowError::Overflow)
+                }
+            }
+        )*};
+    }
+
+    impl_checked_math!(u32, u64, u128);
+
+    /// Basis points making up 100%.
+    const MAX_BPS: u16 = 10_000;
+
+    /// Layout version carried by every event. Bump it whenever the fields
+    /// of any event change so indexers can tell the layouts apart. Version 2
+    /// put the escrow header on every escrow event; version 3 added
+    /// `pull_ledger_total` to `HealthReport`.
+    pub const EVENT_SCHEMA_VERSION: u8 = 3;
+
+    /// Storage layout this code expects. Bump it together with a new step in
+    /// `migrate` whenever a stored type such as `Escrow` changes. Fields
+    /// added to `MyPSP34` after version 1 each live in their own `Lazy` or
+    /// `Mapping` cell, so the root cell an older deployment wrote still
+    /// decodes after `upgrade`; an unset `Lazy` reads as its default.
+    /// Version 2 moved the arbiter registry and the escrow index out of the
+    /// root cell.
+    pub const STORAGE_VERSION: u32 = 2;
+
+    /// Version byte leading every `encoded_escrow` output. Bump it whenever
+    /// the fields of `LeaseTerms` or the canonical tuple change.
+    pub const ESCROW_ENCODING_VERSION: u8 = 1;
+
+    /// Time an arbiter has to resolve a dispute before it can be reassigned.
+    const DEFAULT_ARBITER_RESPONSE_WINDOW: u64 = 14 * 24 * 60 * 60 * 1000;
+
+    /// Time the renter has to confirm a large settlement before the landlord
+    /// may execute it alone.
+    const DEFAULT_SETTLEMENT_TIMEOUT: u64 = 3 * 24 * 60 * 60 * 1000;
+
+    /// Minimum time between CAM reconciliations while the lease is running.
+    const CAM_RECONCILIATION_PERIOD: u64 = 365 * 24 * 60 * 60 * 1000;
+
+    /// Delay between scheduling and executing a token recovery.
+    const TOKEN_RECOVERY_TIMELOCK: u64 = 2 * 24 * 60 * 60 * 1000;
+
+    /// Time a large settlement waits before its recipient can withdraw it.
+    const DEFAULT_WITHDRAWAL_DELAY: u64 = 2 * 24 * 60 * 60 * 1000;
+
+    /// Delay before looser canary limits take effect.
+    const CANARY_TIMELOCK: u64 = 2 * 24 * 60 * 60 * 1000;
+
+    /// Delay between scheduling a decommission and converting or closing
+    /// out the remaining escrows.
+    const DECOMMISSION_TIMELOCK: u64 = 7 * 24 * 60 * 60 * 1000;
+
+    /// How close to expiry a renewal may be proposed, and how long the
+    /// proposal stays open.
+    const DEFAULT_RENEWAL_WINDOW: u64 = 30 * 24 * 60 * 60 * 1000;
+
+    /// How long a counter-offer stays open, and how long an accepted one
+    /// holds the listing for its renter.
+    const COUNTER_OFFER_WINDOW: u64 = 7 * 24 * 60 * 60 * 1000;
+
+    /// Longest metadata URI accepted for a lease agreement.
+    const MAX_METADATA_URI_LEN: usize = 256;
+
+    /// Oldest oracle price a pegged rent payment accepts.
+    const DEFAULT_ORACLE_MAX_AGE: u64 = 60 * 60 * 1000;
+
+    /// Time the landlord has to challenge a renter's sales report.
+    const SALES_CHALLENGE_WINDOW: u64 = 7 * 24 * 60 * 60 * 1000;
+
+    /// Time the renter has to dispute a protection claim before it is paid.
+    const PROTECTION_CHALLENGE_WINDOW: u64 = 7 * 24 * 60 * 60 * 1000;
+
+    /// Time both parties have to reveal their inspection secrets once both
+    /// have committed.
+    const INSPECTION_REVEAL_WINDOW: u64 = 7 * 24 * 60 * 60 * 1000;
+
+    /// Most items a batch message processes, to keep it within block weight.
+    const MAX_BATCH_SIZE: usize = 20;
+
+    /// Most items a paged query returns, whatever `limit` the caller asks for.
+    const MAX_PAGE_SIZE: u32 = 100;
+
+    /// Where the money for a rent payment comes from.
+    #[derive(Clone, Copy)]
+    enum RentSource {
+        /// The value transferred with the call, or a token allowance.
+        Transferred,
+        /// The renter's vault.
+        Vault,
+        /// What is left of the native value sent with a batch.
+        Batch(Balance),
+    }
+
+    /// Window over a list for messages that return it in pages. The limit is
+    /// capped by `MAX_PAGE_SIZE` so no call reads an unbounded number of
+    /// items however large the list grows.
+    #[derive(Clone, Copy)]
+    struct Paginator {
+        cursor: u32,
+        limit: u32,
+    }
+
+    impl Paginator {
+        fn new(cursor: u32, limit: u32) -> Self {
+            Self {
+                cursor,
+                limit: limit.min(MAX_PAGE_SIZE),
+            }
+        }
+
+        /// Cuts the page out of `items`, with the cursor of the next page if
+        /// any items are left after it.
+        fn page<T: Clone>(self, items: &[T]) -> Page<T> {
+            let start = (self.cursor as usize).min(items.len());
+            let end = start.saturating_add(self.limit as usize).min(items.len());
+            Page {
+                items: items[start..end].to_vec(),
+                next: (end < items.len()).then_some(end as u32),
+            }
+        }
+
+        /// Like `page`, for a list of `len` items stored by index.
+        fn page_indexed<T>(self, len: u32, item: impl Fn(u32) -> Option<T>) -> Page<T> {
+            let start = self.cursor.min(len);
+            let end = start.saturating_add(self.limit).min(len);
+            Page {
+                items: (start..end).filter_map(item).collect(),
+                next: (end < len).then_some(end),
+            }
+        }
+    }
+
+    /// Fields every escrow event carries ahead of its own.
+    struct EventHeader {
+        landlord: AccountId,
+        renter: Option<AccountId>,
+        sequence: u32,
+    }
+
+    /// Where the dust of a basis-point calculation goes. Every fee, charge and
+    /// share is computed by `allocate` under the contract-wide policy.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum RoundingPolicy {
+        /// Round the allocated amount up, so the dust goes to the side
+        /// collecting it (the platform, the landlord, the co-renter share).
+        FloorToProtocol,
+        /// Round the allocated amount down, so the dust stays with the payer.
+        #[default]
+        FloorToUser,
+        /// Round to the nearest unit, halves to the even one.
+        Bankers,
+    }
+
+    /// Lifecycle of an escrow.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum LeaseStatus {
+        /// Created or listed, waiting for the lease to start.
+        #[default]
+        Open,
+        /// The lease is running and rent is being paid.
+        Active,
+        /// The lease duration has passed but the escrow is not settled yet.
+        /// Never stored; derived from `Active` by `status`.
+        Expired,
+        /// Frozen by a dispute until the arbiter resolves it.
+        Disputed,
+        /// Cancelled before the lease started or terminated early.
+        Terminated,
+        /// All funds have been paid out.
+        Settled,
+        /// Moved with its funds to a successor contract.
+        Migrated,
+    }
+
+    impl LeaseStatus {
+        fn can_transition_to(self, to: LeaseStatus) -> bool {
+            use LeaseStatus::*;
+            matches!(
+                (self, to),
+                (Open, Active)
+                    | (Open, Terminated)
+                    | (Active, Disputed)
+                    | (Active, Terminated)
+                    | (Active, Settled)
+                    | (Disputed, Settled)
+                    | (Open, Migrated)
+                    | (Active, Migrated)
+            )
+        }
+    }
+
+    /// Terms agreed for a new escrow.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct LeaseTerms {
+        pub rent_amount: Balance,
+        pub lease_duration: u64,
+        pub security_deposit: Balance,
+        pub payment_interval: u64,
+        pub grace_period: u64,
+        pub late_fee_bps: u16,
+        pub arbiter: Option<AccountId>,
+        pub payment_token: Option<AccountId>,
+        /// Periods of prepaid rent the renter forfeits to the landlord when
+        /// the renter ends the lease early.
+        pub early_termination_penalty_periods: u32,
+        /// Estimated common-area-maintenance charge collected with each rent
+        /// payment. Non-zero only for commercial leases.
+        pub cam_estimate: Balance,
+        /// Share of reported sales above `sales_breakpoint` owed as extra
+        /// rent, in basis points. Non-zero only for retail leases.
+        pub percentage_rent_bps: u16,
+        pub sales_breakpoint: Balance,
+        /// Tenants sharing a listed property and their share of the payments
+        /// in basis points, summing to 100%. The first one holds the lease
+        /// token. Empty for a single renter; `create_escrow` requires it empty.
+        pub co_renters: Vec<(AccountId, u16)>,
+        /// Pegs the rent to a fiat amount. `rent_amount` is used while no
+        /// peg is set.
+        pub rent_peg: Option<RentPeg>,
+        /// Hash of the signed off-chain lease document.
+        pub agreement_hash: Hash,
+        /// Where the document can be fetched, e.g. an IPFS URI.
+        pub metadata_uri: Vec<u8>,
+        /// Lets the deposit be paid in installments instead of in full.
+        pub deposit_plan: Option<DepositPlan>,
+        /// Code of the jurisdiction the property is in, checked by assistance
+        /// programs. 0 if unspecified.
+        pub jurisdiction: u32,
+        /// Accepts rent in parts through `pay_partial_rent`. A period counts
+        /// as paid once its full amount has arrived.
+        pub allow_partial: bool,
+        /// Requires the renter to hold insurance attested by an insurer.
+        pub insurance: Option<InsuranceRequirement>,
+        /// Lets the landlord withdraw rent as it accrues, second by second,
+        /// through `withdraw_earned` instead of by matured period.
+        pub stream_rent: bool,
+        /// Building the property belongs to, for tenancy-weighted votes of
+        /// its tenant association. Zero if none.
+        pub building: Hash,
+        /// Replaces the security deposit with a per-period protection fee.
+        pub protection: Option<ProtectionPlan>,
+        /// Lets the deposit earn yield in the yield strategy, with this share
+        /// of the yield, in basis points, going to the renter and the rest
+        /// to the landlord. Native currency only.
+        pub yield_renter_bps: Option<u16>,
+        /// Puts landlord-gated actions under the joint control of the
+        /// landlord and these co-owners.
+        pub joint_ownership: Option<JointOwnership>,
+    }
+
+    /// Limits a renter puts on what `auto_pay` may draw from their vaults.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct VaultPolicy {
+        /// Stops all `auto_pay` draws while set.
+        pub paused: bool,
+        /// Length of the spending window `period_cap` applies to.
+        pub period_length: u64,
+        /// Most all escrows together may draw within one window.
+        pub period_cap: Option<Balance>,
+        pub period_start: u64,
+        pub spent_in_period: Balance,
+    }
+
+    /// The off-chain lease document an escrow is anchored to.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Agreement {
+        pub agreement_hash: Hash,
+        pub metadata_uri: Vec<u8>,
+    }
+
+    /// A new agreement hash proposed by one party, waiting for the other.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct AgreementAmendment {
+        pub new_hash: Hash,
+        pub proposed_by: AccountId,
+    }
+
+    /// Rent fixed in fiat and converted at the oracle's rate on each payment.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct RentPeg {
+        pub price_oracle: AccountId,
+        pub rent_fiat_amount: Balance,
+    }
+
+    /// A security deposit the landlord lets the renter fund in installments,
+    /// in effect an interest-free loan of the unpaid part.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct DepositPlan {
+        /// Part of the deposit collected when the lease is accepted.
+        pub upfront: Balance,
+        /// Equal installments the rest is split into; the last one also
+        /// carries any remainder.
+        pub installments: u32,
+        /// Time between installments. The first falls due one interval after
+        /// the lease starts.
+        pub interval: u64,
+    }
+
+    /// Progress of an escrow's deposit plan.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct DepositSchedule {
+        pub plan: DepositPlan,
+        pub installments_paid: u32,
+    }
+
+    /// Protection for a zero-deposit lease: a non-refundable fee paid with
+    /// every period into the protection pool of the lease's currency, which
+    /// covers the landlord's claims at the end instead of a deposit.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ProtectionPlan {
+        pub fee: Balance,
+        /// Most the landlord may claim from the pool for this lease.
+        pub cover: Balance,
+    }
+
+    /// A landlord's claim against the protection pool, paid once the
+    /// renter's challenge window has passed or, if the renter disputed it,
+    /// once the owner or a registered arbiter approves it.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ProtectionClaim {
+        pub amount: Balance,
+        pub reason: Hash,
+        pub filed_at: u64,
+        pub disputed: bool,
+    }
+
+    /// Co-owners of a jointly owned property. Together with the landlord,
+    /// who still receives the payouts, they approve landlord-gated actions.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct JointOwnership {
+        pub co_owners: Vec<AccountId>,
+        /// Approvals an action needs, the landlord's included.
+        pub threshold: u8,
+    }
+
+    /// A landlord-gated call on a jointly owned escrow. The owners approve
+    /// its `landlord_action_id` before one of them makes the call.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum LandlordAction {
+        ClaimRent,
+        ReleaseDeposit {
+            deduction_amount: Balance,
+            reason: Hash,
+        },
+        ReleaseDepositPortion {
+            amount: Balance,
+        },
+        TerminateEarly,
+        CancelLease,
+        SetManager {
+            manager: AccountId,
+        },
+        RevokeManager,
+        ApproveSublease,
+        ApproveAssignment {
+            assignee: AccountId,
+        },
+        AcceptCounter,
+        ClaimProtection {
+            amount: Balance,
+            reason: Hash,
+        },
+        ClaimInsurance,
+        OptIntoInsurance,
+        RejectCounter,
+        Migrate {
+            successor: AccountId,
+        },
+        AmendAgreement {
+            new_hash: Hash,
+        },
+    }
+
+    /// Terms of the contract's rent-default insurance. Insured landlords
+    /// pay `premium_bps` of every landlord payout into the insurance fund
+    /// of the lease's currency, and may claim once the renter is
+    /// `default_periods` periods behind, for at most `cover_periods`
+    /// periods of rent and never more than `max_payout`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct InsurancePolicy {
+        pub premium_bps: u16,
+        pub default_periods: u32,
+        pub cover_periods: u32,
+        pub max_payout: Balance,
+    }
+
+    /// A landlord's claim against the insurance fund. `paid_out` is set
+    /// once the owner or a registered arbiter approves it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct InsuranceClaim {
+        /// Rent in arrears when the claim was filed, capped at the cover and
+        /// the policy's `max_payout`.
+        pub amount: Balance,
+        pub periods_in_arrears: u32,
+        pub filed_at: u64,
+        pub paid_out: Option<Balance>,
+    }
+
+    /// An escrow's stake in the yield strategy. `principal` is the part of
+    /// the deposit forwarded; the value of `shares` above it is yield.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct YieldPosition {
+        pub shares: Balance,
+        pub principal: Balance,
+    }
+
+    /// Renter's insurance a lease must carry.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct InsuranceRequirement {
+        /// The only account allowed to attest policies for the lease.
+        pub insurer: AccountId,
+        /// Refuses renewals while no valid policy is attested.
+        pub block_renewal: bool,
+    }
+
+    /// The latest policy attested for an escrow that requires insurance.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct InsuranceStatus {
+        pub requirement: InsuranceRequirement,
+        /// Hash of the policy document.
+        pub policy_hash: Hash,
+        /// 0 until the first attestation.
+        pub expires_at: u64,
+        /// Set once a lapse has been reported; a new attestation clears it.
+        pub lapse_reported: bool,
+    }
+
+    /// What a rent assistance program pays and which escrows qualify.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ProgramTerms {
+        pub payment_token: Option<AccountId>,
+        /// Share of each period's rent the program pays, in basis points.
+        /// 5,000 matches the renter's own payment one to one.
+        pub coverage_bps: u16,
+        /// Jurisdiction enrolled properties must be in, or 0 for any.
+        pub jurisdiction: u32,
+        /// Band the rent of an enrolled escrow must fall in.
+        pub min_rent: Balance,
+        pub max_rent: Balance,
+        pub max_enrollments: u32,
+    }
+
+    /// A sponsor-funded pool, e.g. run by a government or a DAO, that pays
+    /// part of the rent of the escrows enrolled in it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct AssistanceProgram {
+        pub sponsor: AccountId,
+        pub terms: ProgramTerms,
+        pub enrollments: u32,
+        /// Funds not yet paid out.
+        pub balance: Balance,
+        pub total_disbursed: Balance,
+        /// Cleared when the sponsor terminates the program.
+        pub active: bool,
+    }
+
+    /// The binding terms of an escrow as shown to a renter before accepting.
+    /// `terms_digest` hashes the SCALE encoding of this struct.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct TermsSummary {
+        pub escrow_id: Hash,
+        pub landlord: AccountId,
+        pub terms: LeaseTerms,
+    }
+
+    /// A successor contract proposed for a single escrow and which parties
+    /// have agreed to it.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct MigrationProposal {
+        pub successor: AccountId,
+        pub renter_approved: bool,
+        pub landlord_approved: bool,
+    }
+
+    /// A renter's request to be reimbursed for a repair paid out of pocket.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ReimbursementClaim {
+        pub amount: Balance,
+        pub invoice_hash: Hash,
+        pub claimed_at: u64,
+    }
+
+    /// The landlord's actual CAM costs for a reconciliation year, waiting for
+    /// the renter to acknowledge the statement.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct CamStatement {
+        pub actual_amount: Balance,
+        pub statement_hash: Hash,
+    }
+
+    /// Sales attested by the renter for a period, applied to the next rent
+    /// payment once the challenge window has passed.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SalesReport {
+        pub sales_amount: Balance,
+        pub attestation_hash: Hash,
+        pub submitted_at: u64,
+    }
+
+    /// A PSP22 payout the token refused, kept until it is retried.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct FailedPayout {
+        pub id: u32,
+        pub token: AccountId,
+        pub to: AccountId,
+        pub amount: Balance,
+    }
+
+    /// A settlement held back for `withdrawal_delay`, which its recipient
+    /// withdraws with `execute_withdrawal` unless it is vetoed first.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PendingWithdrawal {
+        pub escrow_id: Hash,
+        pub token: Option<AccountId>,
+        pub to: AccountId,
+        pub amount: Balance,
+        pub unlock_at: u64,
+        /// Frozen by the owner or the escrow's arbiter until the owner
+        /// requeues it.
+        pub vetoed: bool,
+    }
+
+    /// An owner request to return stray PSP22 tokens, executable once the
+    /// timelock has passed.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct TokenRecovery {
+        pub to: AccountId,
+        pub amount: Balance,
+        pub executable_at: u64,
+    }
+
+    /// Value limits for an early deployment. `max_total_locked` caps the
+    /// native currency held by the whole contract.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct CanaryLimits {
+        pub max_total_locked: Balance,
+        pub max_escrow_balance: Balance,
+    }
+
+    /// Looser canary limits waiting for their timelock. `None` ends canary
+    /// mode.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct CanaryChange {
+        pub limits: Option<CanaryLimits>,
+        pub executable_at: u64,
+    }
+
+    /// A prospective renter's proposed rent and duration for an open
+    /// listing, open until `expires_at`. Once the landlord accepts it the
+    /// listing carries the new terms and only `renter` may take it until
+    /// `expires_at`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct CounterOffer {
+        pub renter: AccountId,
+        pub rent_amount: Balance,
+        pub lease_duration: u64,
+        pub expires_at: u64,
+        pub accepted: bool,
+    }
+
+    /// A landlord's offer to extend a lease, open until `expires_at`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct RenewalProposal {
+        pub new_duration: u64,
+        pub new_rent_amount: Balance,
+        pub expires_at: u64,
+    }
+
+    /// A renter's request to sublet, waiting for the landlord's approval.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SubleaseRequest {
+        pub sub_renter: AccountId,
+        pub sub_rent: Balance,
+        pub sub_duration: u64,
+    }
+
+    /// Both parties' commit-reveal contributions to the draw of a neutral
+    /// inspector. Commitments are the Blake2x256 hash of the secret.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct InspectionDraw {
+        pub renter_commitment: Option<Hash>,
+        pub landlord_commitment: Option<Hash>,
+        pub renter_secret: Option<Hash>,
+        pub landlord_secret: Option<Hash>,
+        /// Set once both secrets are revealed and never drawn again.
+        pub inspector: Option<AccountId>,
+    }
+
+    /// One kind of record shown on an escrow's activity feed.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Activity {
+        /// A full or partial rent payment.
+        Payment {
+            payer: AccountId,
+            amount: Balance,
+        },
+        RenewalProposed {
+            new_duration: u64,
+            new_rent_amount: Balance,
+        },
+        InspectorAssigned {
+            inspector: AccountId,
+        },
+        DisputeRaised {
+            raised_by: AccountId,
+            reason_hash: Hash,
+        },
+        DisputeResolved {
+            renter_share: Balance,
+            landlord_share: Balance,
+        },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ActivityEntry {
+        pub timestamp: u64,
+        pub activity: Activity,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum PaymentKind {
+        /// A full or partial rent payment.
+        Rent,
+        /// The deposit, one of its installments or a co-renter's share.
+        Deposit,
+    }
+
+    /// Receipt of one payment into an escrow, kept so tenants can prove
+    /// their payment history.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PaymentRecord {
+        pub payer: AccountId,
+        pub amount: Balance,
+        pub timestamp: u64,
+        /// Rent period open when the payment was made, counted from 0.
+        pub period: u32,
+        pub kind: PaymentKind,
+    }
+
+    /// A final settlement above the release threshold, queued by the landlord
+    /// until the renter confirms it or the timeout passes.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PendingSettlement {
+        pub amount: Balance,
+        pub queued_at: u64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Escrow {
+        renter: Option<AccountId>,
+        landlord: AccountId,
+        rent_amount: Balance,
+        lease_duration: u64,
+        lease_start_time: u64,
+        escrow_balance: Balance,
+        status: LeaseStatus,
+        security_deposit: Balance,
+        deposit_balance: Balance,
+        payment_interval: u64,
+        periods_paid: u32,
+        periods_claimed: u32,
+        next_due_timestamp: u64,
+        grace_period: u64,
+        late_fee_bps: u16,
+        late_fees: Balance,
+        arbiter: Option<AccountId>,
+        dispute_reason: Option<Hash>,
+        dispute_deadline: u64,
+        payment_token: Option<AccountId>,
+        rent_credit: Balance,
+        early_termination_penalty_periods: u32,
+        cam_estimate: Balance,
+        /// Estimated CAM collected since the last reconciliation.
+        cam_collected: Balance,
+        /// Reconciliation shortfall added to the next payment.
+        cam_charge: Balance,
+        cam_reconciled_at: u64,
+        percentage_rent_bps: u16,
+        sales_breakpoint: Balance,
+        /// Percentage rent from accepted sales reports, due with the next payment.
+        percentage_rent_due: Balance,
+        /// Paid above the amount due; owed back to the renter.
+        overpayment: Balance,
+        co_renters: Vec<(AccountId, u16)>,
+    }
+
+    impl Default for Escrow {
+        fn default() -> Self {
+            MyPSP34::new_escrow(None, AccountId::from([0; 32]), LeaseTerms::default())
+        }
+    }
+
+    /// Track record of an account across all of its leases, as renter or
+    /// landlord.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Reputation {
+        pub leases_completed: u32,
+        pub on_time_payments: u32,
+        pub late_payments: u32,
+        /// Disputes in which the account was awarded the smaller share.
+        pub disputes_lost: u32,
+    }
+
+    /// Reputation returned by `reputation_of`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ReputationView {
+        pub account: AccountId,
+        pub leases_completed: u32,
+        pub on_time_payments: u32,
+        pub late_payments: u32,
+        pub disputes_lost: u32,
+        /// Share of rent payments made on time, in basis points; `None`
+        /// before the first payment.
+        pub on_time_bps: Option<u16>,
+    }
+
+    /// One page of a list returned by a paged query.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Page<T> {
+        pub items: Vec<T>,
+        /// Cursor to pass for the next page; `None` on the last one.
+        pub next: Option<u32>,
+    }
+
+    /// Read-only copy of an escrow returned by `get_escrow`.
+    #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct EscrowView {
+        pub escrow_id: Hash,
+        pub renter: Option<AccountId>,
+        pub landlord: AccountId,
+        pub status: LeaseStatus,
+        pub rent_amount: Balance,
+        pub lease_duration: u64,
+        pub lease_start_time: u64,
+        pub escrow_balance: Balance,
+        pub security_deposit: Balance,
+        pub deposit_balance: Balance,
+        pub payment_interval: u64,
+        pub periods_paid: u32,
+        pub periods_claimed: u32,
+        pub next_due_timestamp: u64,
+        pub grace_period: u64,
+        pub late_fee_bps: u16,
+        pub late_fees: Balance,
+        pub arbiter: Option<AccountId>,
+        /// Reason given for the open dispute, if any.
+        pub dispute_reason: Option<Hash>,
+        pub dispute_deadline: u64,
+        pub payment_token: Option<AccountId>,
+        /// Credit applied to the next rent payments.
+        pub rent_credit: Balance,
+        pub early_termination_penalty_periods: u32,
+        pub cam_estimate: Balance,
+        pub cam_collected: Balance,
+        pub cam_charge: Balance,
+        pub percentage_rent_bps: u16,
+        pub sales_breakpoint: Balance,
+        pub percentage_rent_due: Balance,
+        pub overpayment: Balance,
+        pub co_renters: Vec<(AccountId, u16)>,
+    }
+
+    #[ink(storage)]
+    pub struct MyPSP34 {
+        escrows: Mapping<Hash, Escrow>,
+        total_locked: Balance,
+        active_disputes: u32,
+        lease_token_owners: Mapping<Hash, AccountId>,
+        lease_token_balances: Mapping<AccountId, u32>,
+        lease_token_supply: Balance,
+        assignment_approvals: Mapping<Hash, AccountId>,
+        migrations: Mapping<Hash, MigrationProposal>,
+        owner: AccountId,
+        /// Blocks every state-changing message except the owner's while set.
+        paused: bool,
+        /// Layout of the stored data; behind `STORAGE_VERSION` until
+        /// `migrate` has run after an upgrade.
+        storage_version: u32,
+        /// Version 1 arbiter registry; `migrate` moves it to
+        /// `arbiter_registry`.
+        legacy_arbiter_registry: Vec<AccountId>,
+        next_arbiter: u32,
+        arbiter_response_window: u64,
+        escrows_by_landlord: Mapping<AccountId, Vec<Hash>>,
+        escrows_by_renter: Mapping<AccountId, Vec<Hash>>,
+        reimbursement_claims: Mapping<Hash, ReimbursementClaim>,
+        cam_statements: Mapping<Hash, CamStatement>,
+        renewal_proposals: Mapping<Hash, RenewalProposal>,
+        renewal_window: u64,
+        sales_reports: Mapping<Hash, SalesReport>,
+        /// Every escrow id in creation order, for paging through `escrows`.
+        /// Version 1 escrow index; `migrate` moves it to `escrow_index`.
+        legacy_escrow_index: Vec<Hash>,
+        /// Counter mixed into generated escrow ids; never decreases.
+        escrow_nonce: Lazy<u64>,
+        /// Escrow ids by payment token, `None` being the native currency.
+        escrows_by_token: Mapping<Option<AccountId>, Vec<Hash>>,
+        failed_payouts: Mapping<u32, FailedPayout>,
+        failed_payouts_by_account: Mapping<AccountId, Vec<u32>>,
+        /// Sum of the queued failed payouts per token.
+        queued_payout_totals: Mapping<AccountId, Balance>,
+        next_payout_id: u32,
+        /// Platform fee taken from the landlord's settlement payouts.
+        fee_bps: u16,
+        fee_collector: AccountId,
+        /// Negotiated fees replacing `fee_bps` for individual landlords.
+        fee_overrides: Mapping<AccountId, u16>,
+        /// Fees accrued per payment token, `None` being the native currency.
+        collected_fees: Mapping<Option<AccountId>, Balance>,
+        rounding_policy: RoundingPolicy,
+        /// Co-renters who accepted a listing so far, with the deposit each paid.
+        lease_signatures: Mapping<Hash, Vec<(AccountId, Balance)>>,
+        /// Co-renters who agreed to terminate a lease early.
+        termination_approvals: Mapping<Hash, Vec<AccountId>>,
+        /// Everything each tenant has paid into an escrow.
+        contributions: Mapping<(Hash, AccountId), Balance>,
+        token_recoveries: Mapping<AccountId, TokenRecovery>,
+        /// Settlements of at least this amount need the renter's confirmation.
+        release_threshold: Option<Balance>,
+        settlement_timeout: u64,
+        pending_settlements: Mapping<Hash, PendingSettlement>,
+        /// Kept beside `escrows` so existing escrow entries decode unchanged.
+        rent_pegs: Mapping<Hash, RentPeg>,
+        oracle_max_age: Lazy<u64>,
+        /// Escrows whose deposit is paid in installments.
+        deposit_schedules: Mapping<Hash, DepositSchedule>,
+        /// Renter-funded balances per currency that `auto_pay` draws from.
+        vaults: Mapping<(AccountId, Option<AccountId>), Balance>,
+        /// Sum of the vault balances per token.
+        vault_token_totals: Mapping<AccountId, Balance>,
+        vault_policies: Mapping<AccountId, VaultPolicy>,
+        /// Most a single `auto_pay` may draw for a renter's escrow.
+        auto_pay_caps: Mapping<(AccountId, Hash), Balance>,
+        agreements: Mapping<Hash, Agreement>,
+        agreement_amendments: Mapping<Hash, AgreementAmendment>,
+        jurisdictions: Mapping<Hash, u32>,
+        assistance_programs: Mapping<u32, AssistanceProgram>,
+        next_program_id: Lazy<u32>,
+        /// The program each enrolled escrow receives assistance from.
+        program_enrollments: Mapping<Hash, u32>,
+        /// Sum of the unspent program balances per token.
+        program_token_totals: Mapping<AccountId, Balance>,
+        /// Escrows that accept partial rent, with what has been paid towards
+        /// the open period. The funds are held in the escrow's `overpayment`
+        /// until the period is complete.
+        partial_payments: Mapping<Hash, Balance>,
+        /// Escrows that require renter's insurance.
+        insurance: Mapping<Hash, InsuranceStatus>,
+        /// Escrows that stream their rent, with the rent the landlord has
+        /// withdrawn so far.
+        rent_streams: Mapping<Hash, Balance>,
+        buildings: Mapping<Hash, Hash>,
+        /// Active leases a renter holds in a building, as `(block, weight)`
+        /// checkpoints in block order.
+        tenancy_checkpoints: Mapping<(Hash, AccountId), Vec<(u32, u32)>>,
+        /// Active leases per building, checkpointed the same way.
+        building_checkpoints: Mapping<Hash, Vec<(u32, u32)>>,
+        sublease_requests: Mapping<Hash, SubleaseRequest>,
+        /// Child escrows of each sublet lease.
+        subleases: Mapping<Hash, Vec<Hash>>,
+        parent_leases: Mapping<Hash, Hash>,
+        inspector_registry: Lazy<Vec<AccountId>>,
+        inspection_draws: Mapping<Hash, InspectionDraw>,
+        managers: Mapping<Hash, AccountId>,
+        /// Each escrow's activity in the order it happened.
+        activity_log: Mapping<Hash, Vec<ActivityEntry>>,
+        /// Most one escrow may hold, if capped.
+        max_escrow_balance: Lazy<Option<Balance>>,
+        reputations: Mapping<AccountId, Reputation>,
+        verified: Mapping<AccountId, ()>,
+        /// Whether both parties must be verified to create or start a lease.
+        require_kyc: Lazy<bool>,
+        canary: Lazy<Option<CanaryLimits>>,
+        pending_canary: Lazy<Option<CanaryChange>>,
+        protection_plans: Mapping<Hash, ProtectionPlan>,
+        /// Protection fees held per currency for landlords' claims.
+        protection_pools: Mapping<Option<AccountId>, Balance>,
+        protection_claims: Mapping<Hash, Balance>,
+        /// Settlements at or above this amount go through a pending withdrawal.
+        withdrawal_threshold: Lazy<Option<Balance>>,
+        withdrawal_delay: Lazy<u64>,
+        pending_withdrawals: Mapping<u32, PendingWithdrawal>,
+        next_withdrawal_id: Lazy<u32>,
+        withdrawal_token_totals: Mapping<AccountId, Balance>,
+        yield_strategy: Lazy<Option<AccountId>>,
+        yield_splits: Mapping<Hash, u16>,
+        yield_positions: Mapping<Hash, YieldPosition>,
+        total_yield_shares: Lazy<Balance>,
+        /// Events emitted so far for each escrow.
+        event_counts: Mapping<Hash, u32>,
+        /// Paid from collected fees to whoever settles an expired lease.
+        keeper_reward: Lazy<Balance>,
+        /// Payment receipts by escrow and position, oldest first.
+        payment_records: Mapping<(Hash, u32), PaymentRecord>,
+        payment_counts: Mapping<Hash, u32>,
+        counter_offers: Mapping<Hash, CounterOffer>,
+        joint_ownerships: Mapping<Hash, JointOwnership>,
+        /// Owners who approved each pending landlord action, by escrow and
+        /// action id.
+        action_approvals: Mapping<(Hash, Hash), Vec<AccountId>>,
+        insurance_policy: Lazy<Option<InsurancePolicy>>,
+        /// Escrows insured against rent default, with the policy they took.
+        insured_escrows: Mapping<Hash, InsurancePolicy>,
+        /// Insurance premiums held per currency for landlords' claims.
+        insurance_fund: Mapping<Option<AccountId>, Balance>,
+        insurance_claims: Mapping<Hash, InsuranceClaim>,
+        /// When a scheduled decommission may go ahead.
+        decommission_at: Lazy<Option<u64>>,
+        decommissioned: Lazy<bool>,
+        /// Funds of force-refunded escrows, by account and currency, until
+        /// withdrawn with `withdraw_credit`.
+        credits: Mapping<(AccountId, Option<AccountId>), Balance>,
+        credit_totals: Mapping<Option<AccountId>, Balance>,
+        /// Renters who have agreed to the pending migration proposal.
+        migration_approvals: Mapping<Hash, Vec<AccountId>>,
+        /// Native funds held in pending withdrawals.
+        withdrawal_native_total: Lazy<Balance>,
+        /// Protection claims waiting for their challenge window or a decision.
+        pending_protection_claims: Mapping<Hash, ProtectionClaim>,
+        /// Arrears the insurance fund paid to the landlord and recovers from
+        /// the renter's later rent.
+        insurance_subrogations: Mapping<Hash, Balance>,
+        arbiter_registry: Lazy<Vec<AccountId>>,
+        /// Every escrow id by creation order, `escrow_count` of them.
+        escrow_index: Mapping<u32, Hash>,
+        escrow_count: Lazy<u32>,
+        /// Sum of `held_funds` over the escrows paid in each token.
+        held_escrow_totals: Mapping<Option<AccountId>, Balance>,
+        /// Escrows that run a lease or hold funds; `decommission` waits for
+        /// none to remain.
+        live_escrows: Lazy<u32>,
+        /// When the inspection reveal window closes, set once both parties
+        /// have committed.
+        inspection_reveal_deadlines: Mapping<Hash, u64>,
+    }
+
+    #[ink(event)]
+    pub struct EscrowCreated {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        rent_amount: Balance,
+        lease_duration: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct PropertyListed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        rent_amount: Balance,
+        lease_duration: u64,
+        security_deposit: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct LeaseStarted {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        lease_start_time: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct RentPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        payer: AccountId,
+        amount: Balance,
+        late_fee: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsuranceAttested {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        insurer: AccountId,
+        policy_hash: Hash,
+        expires_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsuranceLapsed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        /// 0 if no policy was ever attested.
+        expired_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct PartialRentPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        payer: AccountId,
+        amount: Balance,
+        /// What is left to pay for the open period.
+        outstanding: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AgreementAmendmentProposed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        new_hash: Hash,
+        proposed_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AgreementAmended {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        previous_hash: Hash,
+        new_hash: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct VaultFunded {
+        #[ink(topic)]
+        renter: AccountId,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct VaultWithdrawn {
+        #[ink(topic)]
+        renter: AccountId,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AssistanceProgramCreated {
+        #[ink(topic)]
+        program_id: u32,
+        #[ink(topic)]
+        sponsor: AccountId,
+        terms: ProgramTerms,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AssistanceProgramFunded {
+        #[ink(topic)]
+        program_id: u32,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AssistanceProgramTerminated {
+        #[ink(topic)]
+        program_id: u32,
+        refund: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct EnrollmentChanged {
+        program_id: u32,
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        enrolled: bool,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AssistancePaid {
+        program_id: u32,
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DepositPortionReleased {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        remaining: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct RentClaimed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        periods: u32,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct EarnedRentWithdrawn {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct UnearnedRentWithdrawn {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct LeaseEnded {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CodeUpgraded {
+        #[ink(topic)]
+        code_hash: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct StorageMigrated {
+        from_version: u32,
+        to_version: u32,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct PayoutFailed {
+        #[ink(topic)]
+        id: u32,
+        #[ink(topic)]
+        to: AccountId,
+        token: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalQueued {
+        id: u32,
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        to: AccountId,
+        amount: Balance,
+        unlock_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalExecuted {
+        id: u32,
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalVetoed {
+        id: u32,
+        #[ink(topic)]
+        vetoed_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct PayoutRetried {
+        #[ink(topic)]
+        id: u32,
+        #[ink(topic)]
+        to: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct FeeAccrued {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct FeesWithdrawn {
+        #[ink(topic)]
+        collector: AccountId,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct KeeperRewarded {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        keeper: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct TokenRecoveryScheduled {
+        #[ink(topic)]
+        token: AccountId,
+        to: AccountId,
+        amount: Balance,
+        executable_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CanaryLimitsScheduled {
+        limits: Option<CanaryLimits>,
+        executable_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CanaryLimitsChanged {
+        limits: Option<CanaryLimits>,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct TokenRecovered {
+        #[ink(topic)]
+        token: AccountId,
+        to: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DecommissionScheduled {
+        executable_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DecommissionCancelled {
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ContractDecommissioned {
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CreditWithdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SettlementQueued {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        executable_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SettlementConfirmed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        confirmed_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct LeaseCancelled {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        refund: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct OverpaymentRefunded {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        to: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CoRenterSigned {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        co_renter: AccountId,
+        deposit: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct TerminationApproved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        approved_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct LeaseTerminatedEarly {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        terminated_by: AccountId,
+        landlord_amount: Balance,
+        renter_refund: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DepositPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        payer: AccountId,
+        amount: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DepositInstallmentPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        payer: AccountId,
+        amount: Balance,
+        late_fee: Balance,
+        installments_paid: u32,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ProtectionClaimFiled {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        reason: Hash,
+        challenge_deadline: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ProtectionClaimDisputed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        disputed_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ProtectionClaimRejected {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        rejected_by: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ProtectionClaimPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        reason: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DepositInvested {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        shares: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct YieldDistributed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        principal: Balance,
+        renter_share: Balance,
+        landlord_share: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DepositSettled {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        deduction: Balance,
+        refund: Balance,
+        reason: Hash,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DisputeRaised {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        raised_by: AccountId,
+        reason_hash: Hash,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct DisputeResolved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        arbiter: AccountId,
+        renter_share: Balance,
+        landlord_share: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct HealthReport {
+        #[ink(topic)]
+        reporter: AccountId,
+        total_value_locked: Balance,
+        unattributed_balance: Balance,
+        active_disputes: u32,
+        /// Native funds waiting to be pulled as credits or timelocked
+        /// withdrawals. They are still part of `total_value_locked`.
+        pull_ledger_total: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        id: Id,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SubleaseRequested {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        sub_renter: AccountId,
+        sub_rent: Balance,
+        sub_duration: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SubleaseApproved {
+        parent_id: Hash,
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InspectorAssigned {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        inspector: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ManagerChanged {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        manager: Option<AccountId>,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct AssignmentApproved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        assignee: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct MigrationApproved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        approved_by: AccountId,
+        successor: AccountId,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct EscrowMigrated {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        successor: AccountId,
+        amount: Balance,
+        timestamp: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ArbiterReassigned {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        previous: Option<AccountId>,
+        arbiter: AccountId,
+        deadline: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CamStatementSubmitted {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        actual_amount: Balance,
+        collected: Balance,
+        statement_hash: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CamReconciled {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        statement_hash: Hash,
+        credit: Balance,
+        charge: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SalesReported {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        sales_amount: Balance,
+        attestation_hash: Hash,
+        challenge_deadline: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct SalesReportChallenged {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        attestation_hash: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ActionApproved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        action_id: Hash,
+        approved_by: AccountId,
+        approvals: u32,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsurancePremiumPaid {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        token: Option<AccountId>,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsuranceClaimFiled {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        periods_in_arrears: u32,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsuranceRecovered {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        amount: Balance,
+        remaining: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct InsuranceClaimDecided {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        decided_by: AccountId,
+        approved: bool,
+        /// Paid to the landlord; the claim capped at the fund's balance.
+        payout: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct EscrowForceRefunded {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        landlord_credit: Balance,
+        renter_credit: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CounterOffered {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        offered_by: AccountId,
+        rent_amount: Balance,
+        lease_duration: u64,
+        expires_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct CounterOfferDecided {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        offered_by: AccountId,
+        accepted: bool,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct RenewalProposed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        new_duration: u64,
+        new_rent_amount: Balance,
+        expires_at: u64,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct LeaseRenewed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        lease_duration: u64,
+        rent_amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ReimbursementClaimed {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        claimed_by: AccountId,
+        amount: Balance,
+        invoice_hash: Hash,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ReimbursementApproved {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        decided_by: AccountId,
+        amount: Balance,
+        schema_version: u8,
+    }
+
+    #[ink(event)]
+    pub struct ReimbursementRejected {
+        #[ink(topic)]
+        escrow_id: Hash,
+        #[ink(topic)]
+        landlord: AccountId,
+        #[ink(topic)]
+        renter: Option<AccountId>,
+        sequence: u32,
+        decided_by: AccountId,
+        amount: Balance,
+        disputed: bool,
+        schema_version: u8,
+    }
+
+    impl PSP34 for MyPSP34 {
+        #[ink(message)]
+        fn collection_id(&self) -> Id {
+            Id::Bytes(self.env().account_id().as_ref().to_vec())
+        }
+
+        #[ink(message)]
+        fn balance_of(&self, owner: AccountId) -> u32 {
+            self.lease_token_balances.get(&owner).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        fn owner_of(&self, id: Id) -> Option<AccountId> {
+            let escrow_id = Self::escrow_id_of(&id)?;
+            self.lease_token_owners.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        fn total_supply(&self) -> Balance {
+            self.lease_token_supply
+        }
+
+        /// Assigns the lease to `to`, who becomes the renter of the escrow.
+        /// The landlord must have approved `to` with `approve_assignment`.
+        #[ink(message)]
+        fn transfer(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            _data: Vec<u8>,
+        ) -> core::result::Result<(), PSP34Error> {
+            if self.paused {
+                return Err(PSP34Error::Custom("contract is paused".into()));
+            }
+            let caller = self.env().caller();
+            let escrow_id = Self::escrow_id_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+            let owner = self
+                .lease_token_owners
+                .get(&escrow_id)
+                .ok_or(PSP34Error::TokenNotExists)?;
+            if owner != caller {
+                return Err(PSP34Error::NotApproved);
+            }
+            if self.assignment_approvals.get(&escrow_id) != Some(to) {
+                return Err(PSP34Error::NotApproved);
+            }
+            let mut escrow = self
+                .get_escrow_or_revert(escrow_id)
+                .map_err(|_| PSP34Error::TokenNotExists)?;
+            if escrow.status != LeaseStatus::Active {
+                return Err(PSP34Error::Custom("lease is not active".into()));
+            }
+            if self.ensure_verified(&[to]).is_err() {
+                return Err(PSP34Error::Custom("assignee is not verified".into()));
+            }
+
+            escrow.renter = Some(to);
+            for (renter, _) in escrow.co_renters.iter_mut() {
+                if *renter == caller {
+                    *renter = to;
+                }
+            }
+            self.store_escrow(escrow_id, &escrow);
+            Self::remove_from_index(&mut self.escrows_by_renter, caller, escrow_id);
+            Self::add_to_index(&mut self.escrows_by_renter, to, escrow_id);
+            self.assignment_approvals.take(&escrow_id);
+            self.lease_token_owners.insert(escrow_id, &to);
+            self.adjust_token_balance(caller, false);
+            self.adjust_token_balance(to, true);
+            self.record_tenancy(escrow_id, caller, false, false);
+            self.record_tenancy(escrow_id, to, true, false);
+            self.env().emit_event(Transfer {
+                from: Some(caller),
+                to: Some(to),
+                id,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+    }
+
+    /// Lease tokens describe their lease through `rent_band`, `start`, `end`
+    /// and `property_cid`, with numbers as decimal strings.
+    impl PSP34Metadata for MyPSP34 {
+        #[ink(message)]
+        fn get_attribute(&self, id: Id, key: Vec<u8>) -> Option<Vec<u8>> {
+            if id == self.collection_id() {
+                return match key.as_slice() {
+                    b"name" => Some(b"Rental lease".to_vec()),
+                    b"symbol" => Some(b"LEASE".to_vec()),
+                    _ => None,
+                };
+            }
+            let escrow_id = Self::escrow_id_of(&id)?;
+            if !self.lease_token_owners.contains(&escrow_id) {
+                return None;
+            }
+            let escrow = self.escrows.get(&escrow_id)?;
+            let end = escrow
+                .lease_start_time
+                .saturating_add(escrow.lease_duration);
+            match key.as_slice() {
+                b"rent_band" => Some(Self::rent_band(escrow.rent_amount)),
+                b"start" => Some(escrow.lease_start_time.to_string().into_bytes()),
+                b"end" => Some(end.to_string().into_bytes()),
+                b"property_cid" => self
+                    .agreements
+                    .get(&escrow_id)
+                    .map(|agreement| agreement.metadata_uri)
+                    .filter(|uri| !uri.is_empty()),
+                _ => None,
+            }
+        }
+    }
+
+    impl MyPSP34 {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                escrows: Mapping::default(),
+                total_locked: 0,
+                active_disputes: 0,
+                lease_token_owners: Mapping::default(),
+                lease_token_balances: Mapping::default(),
+                lease_token_supply: 0,
+                assignment_approvals: Mapping::default(),
+                migrations: Mapping::default(),
+                owner: Self::env().caller(),
+                paused: false,
+                storage_version: STORAGE_VERSION,
+                legacy_arbiter_registry: Vec::new(),
+                next_arbiter: 0,
+                arbiter_response_window: DEFAULT_ARBITER_RESPONSE_WINDOW,
+                escrows_by_landlord: Mapping::default(),
+                escrows_by_renter: Mapping::default(),
+                reimbursement_claims: Mapping::default(),
+                cam_statements: Mapping::default(),
+                renewal_proposals: Mapping::default(),
+                renewal_window: DEFAULT_RENEWAL_WINDOW,
+                sales_reports: Mapping::default(),
+                legacy_escrow_index: Vec::new(),
+                escrow_nonce: Lazy::new(),
+                escrows_by_token: Mapping::default(),
+                failed_payouts: Mapping::default(),
+                failed_payouts_by_account: Mapping::default(),
+                queued_payout_totals: Mapping::default(),
+                next_payout_id: 0,
+                fee_bps: 0,
+                fee_collector: Self::env().caller(),
+                fee_overrides: Mapping::default(),
+                rent_pegs: Mapping::default(),
+                oracle_max_age: Lazy::new(),
+                deposit_schedules: Mapping::default(),
+                vaults: Mapping::default(),
+                vault_token_totals: Mapping::default(),
+                vault_policies: Mapping::default(),
+                auto_pay_caps: Mapping::default(),
+                agreements: Mapping::default(),
+                agreement_amendments: Mapping::default(),
+                jurisdictions: Mapping::default(),
+                assistance_programs: Mapping::default(),
+                next_program_id: Lazy::new(),
+                program_enrollments: Mapping::default(),
+                program_token_totals: Mapping::default(),
+                partial_payments: Mapping::default(),
+                insurance: Mapping::default(),
+                rent_streams: Mapping::default(),
+                buildings: Mapping::default(),
+                tenancy_checkpoints: Mapping::default(),
+                building_checkpoints: Mapping::default(),
+                sublease_requests: Mapping::default(),
+                subleases: Mapping::default(),
+                parent_leases: Mapping::default(),
+                inspector_registry: Lazy::new(),
+                inspection_draws: Mapping::default(),
+                managers: Mapping::default(),
+                activity_log: Mapping::default(),
+                max_escrow_balance: Lazy::new(),
+                reputations: Mapping::default(),
+                verified: Mapping::default(),
+                require_kyc: Lazy::new(),
+                canary: Lazy::new(),
+                pending_canary: Lazy::new(),
+                protection_plans: Mapping::default(),
+                protection_pools: Mapping::default(),
+                protection_claims: Mapping::default(),
+                withdrawal_threshold: Lazy::new(),
+                withdrawal_delay: Lazy::new(),
+                pending_withdrawals: Mapping::default(),
+                next_withdrawal_id: Lazy::new(),
+                withdrawal_token_totals: Mapping::default(),
+                yield_strategy: Lazy::new(),
+                yield_splits: Mapping::default(),
+                yield_positions: Mapping::default(),
+                total_yield_shares: Lazy::new(),
+                event_counts: Mapping::default(),
+                keeper_reward: Lazy::new(),
+                payment_records: Mapping::default(),
+                payment_counts: Mapping::default(),
+                counter_offers: Mapping::default(),
+                joint_ownerships: Mapping::default(),
+                action_approvals: Mapping::default(),
+                insurance_policy: Lazy::new(),
+                insured_escrows: Mapping::default(),
+                insurance_fund: Mapping::default(),
+                insurance_claims: Mapping::default(),
+                decommission_at: Lazy::new(),
+                decommissioned: Lazy::new(),
+                credits: Mapping::default(),
+                credit_totals: Mapping::default(),
+                migration_approvals: Mapping::default(),
+                withdrawal_native_total: Lazy::new(),
+                pending_protection_claims: Mapping::default(),
+                insurance_subrogations: Mapping::default(),
+                arbiter_registry: Lazy::new(),
+                escrow_index: Mapping::default(),
+                escrow_count: Lazy::new(),
+                held_escrow_totals: Mapping::default(),
+                live_escrows: Lazy::new(),
+                inspection_reveal_deadlines: Mapping::default(),
+                collected_fees: Mapping::default(),
+                rounding_policy: RoundingPolicy::default(),
+                lease_signatures: Mapping::default(),
+                termination_approvals: Mapping::default(),
+                contributions: Mapping::default(),
+                token_recoveries: Mapping::default(),
+                release_threshold: None,
+                settlement_timeout: DEFAULT_SETTLEMENT_TIMEOUT,
+                pending_settlements: Mapping::default(),
+            }
+        }
+
+        /// Creates an escrow between the caller as renter and `landlord` and
+        /// returns its id, which the contract generates.
+        #[ink(message)]
+        pub fn create_escrow(&mut self, landlord: AccountId, terms: LeaseTerms) -> Result<Hash> {
+            self.ensure_not_paused()?;
+            self.ensure_accepting_leases()?;
+            let caller = self.env().caller();
+            self.ensure_valid_terms(&terms)?;
+            Self::ensure_valid_ownership(&landlord, &terms)?;
+            if !terms.co_renters.is_empty() {
+                return Err(EscrowError::InvalidShares);
+            }
+            self.ensure_verified(&[caller, landlord])?;
+            let escrow_id = self.next_escrow_id(caller, landlord)?;
+            let rent_amount = terms.rent_amount;
+            let lease_duration = terms.lease_duration;
+
+            self.insert_new_escrow(escrow_id, Some(caller), landlord, terms)?;
+            Self::add_to_index(&mut self.escrows_by_landlord, landlord, escrow_id);
+            Self::add_to_index(&mut self.escrows_by_renter, caller, escrow_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(EscrowCreated {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                rent_amount,
+                lease_duration,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn list_property(&mut self, escrow_id: Hash, terms: LeaseTerms) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_accepting_leases()?;
+            let caller = self.env().caller();
+            self.ensure_valid_terms(&terms)?;
+            Self::ensure_valid_ownership(&caller, &terms)?;
+            if self.escrows.contains(&escrow_id) {
+                return Err(EscrowError::IdExists);
+            }
+            let rent_amount = terms.rent_amount;
+            let lease_duration = terms.lease_duration;
+            let security_deposit = terms.security_deposit;
+
+            self.insert_new_escrow(escrow_id, None, caller, terms)?;
+            Self::add_to_index(&mut self.escrows_by_landlord, caller, escrow_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(PropertyListed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                rent_amount,
+                lease_duration,
+                security_deposit,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn accept_lease(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_accepting_leases()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.renter.is_some() {
+                return Err(EscrowError::NotOpen);
+            }
+            self.ensure_verified(&[caller, escrow.landlord])?;
+            if !escrow.co_renters.is_empty() {
+                return self.sign_as_co_renter(escrow_id, escrow, caller);
+            }
+            if let Some(offer) = self.pending_counter_offer(escrow_id) {
+                if offer.accepted && offer.renter != caller {
+                    return Err(EscrowError::CounterOfferPending);
+                }
+            }
+            let required = self.upfront_deposit(escrow_id, &escrow);
+            let value = self.incoming_amount(escrow.payment_token, required)?;
+            if value < required {
+                return Err(EscrowError::InsufficientDeposit);
+            }
+            self.ensure_within_balance_cap(&escrow, value)?;
+            self.collect_payment(escrow.payment_token, caller, value)?;
+            self.counter_offers.take(&escrow_id);
+
+            let lease_start_time = self.now();
+            escrow.renter = Some(caller);
+            Self::add_to_index(&mut self.escrows_by_renter, caller, escrow_id);
+            escrow.lease_start_time = lease_start_time;
+            escrow.next_due_timestamp = lease_start_time;
+            self.transition(&mut escrow, LeaseStatus::Active)?;
+            escrow.deposit_balance = value;
+            self.store_escrow(escrow_id, &escrow);
+            self.mint_lease_token(escrow_id, caller);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseStarted {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                lease_start_time,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            if value > 0 {
+                self.record_payment(escrow_id, &escrow, caller, value, PaymentKind::Deposit);
+                let header = self.event_header(escrow_id);
+                self.env().emit_event(DepositPaid {
+                    escrow_id,
+                    landlord: header.landlord,
+                    renter: header.renter,
+                    sequence: header.sequence,
+                    payer: caller,
+                    amount: value,
+                    timestamp: lease_start_time,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn rent(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_accepting_leases()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_not_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            self.ensure_verified(&[caller, escrow.landlord])?;
+            if let Some(parent_id) = self.parent_leases.get(&escrow_id) {
+                self.ensure_fits_parent_lease(parent_id, escrow.lease_duration)?;
+            }
+
+            let lease_start_time = self.now();
+            escrow.lease_start_time = lease_start_time;
+            escrow.next_due_timestamp = lease_start_time;
+            self.transition(&mut escrow, LeaseStatus::Active)?;
+            self.store_escrow(escrow_id, &escrow);
+            self.mint_lease_token(escrow_id, caller);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseStarted {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                lease_start_time,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn pay_rent(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            self.take_rent(escrow_id, caller, RentSource::Transferred)
+                .map(|_| ())
+        }
+
+        /// Pays part of the open period of an escrow that accepts partial
+        /// rent. A payment covering the rest completes the period like
+        /// `pay_rent`. For the native currency `amount` is ignored and the
+        /// transferred value is used.
+        #[ink(message, payable)]
+        pub fn pay_partial_rent(&mut self, escrow_id: Hash, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            if !Self::is_renter(&escrow, &caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            let prepaid = self
+                .partial_payments
+                .get(&escrow_id)
+                .ok_or(EscrowError::PartialPaymentsNotAllowed)?;
+            if escrow.periods_paid >= self.total_periods(&escrow) {
+                return Err(EscrowError::LeaseFullyPaid);
+            }
+            let value = self.incoming_amount(escrow.payment_token, amount)?;
+            if value == 0 {
+                return Err(EscrowError::ZeroAmount);
+            }
+            let remaining = self.period_balance(escrow_id, &mut escrow.clone())?;
+            if value >= remaining {
+                return self
+                    .take_rent(escrow_id, caller, RentSource::Transferred)
+                    .map(|_| ());
+            }
+            self.ensure_within_balance_cap(&escrow, value)?;
+            self.collect_payment(escrow.payment_token, caller, value)?;
+
+            escrow.overpayment = escrow.overpayment.try_add(value)?;
+            self.partial_payments
+                .insert(escrow_id, &prepaid.try_add(value)?);
+            let contributed = self.contributions.get(&(escrow_id, caller)).unwrap_or(0);
+            self.contributions
+                .insert((escrow_id, caller), &contributed.try_add(value)?);
+            self.store_escrow(escrow_id, &escrow);
+            self.log_activity(
+                escrow_id,
+                Activity::Payment {
+                    payer: caller,
+                    amount: value,
+                },
+            );
+            self.record_payment(escrow_id, &escrow, caller, value, PaymentKind::Rent);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(PartialRentPaid {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                payer: caller,
+                amount: value,
+                outstanding: remaining.saturating_sub(value),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Pays the open period of an escrow from its renter's vault. Meant to
+        /// be called by a keeper once `next_due_timestamp` has passed.
+        #[ink(message)]
+        pub fn auto_pay(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            if self.now() < escrow.next_due_timestamp {
+                return Err(EscrowError::PaymentNotDue);
+            }
+            self.take_rent(escrow_id, renter, RentSource::Vault)
+                .map(|_| ())
+        }
+
+        /// Creates one escrow per `(landlord, terms)` pair, as `create_escrow`
+        /// does. Every item is tried and reports its own result.
+        #[ink(message)]
+        pub fn create_escrows(
+            &mut self,
+            items: Vec<(AccountId, LeaseTerms)>,
+        ) -> Result<Vec<Result<Hash>>> {
+            Self::ensure_batch_size(items.len())?;
+            Ok(items
+                .into_iter()
+                .map(|(landlord, terms)| self.create_escrow(landlord, terms))
+                .collect())
+        }
+
+        /// Pays the open period of each escrow, as `pay_rent` does. In the
+        /// native currency the transferred value is spent item by item at the
+        /// amount each one owes, and whatever is left is refunded.
+        #[ink(message, payable)]
+        pub fn pay_rent_batch(&mut self, escrow_ids: Vec<Hash>) -> Result<Vec<Result<()>>> {
+            self.ensure_not_paused()?;
+            Self::ensure_batch_size(escrow_ids.len())?;
+            let caller = self.env().caller();
+            let mut remaining = self.env().transferred_value();
+            let mut results = Vec::new();
+            for escrow_id in escrow_ids {
+                let result = self.take_rent(escrow_id, caller, RentSource::Batch(remaining));
+                if let (Ok(value), Some(escrow)) = (&result, self.escrows.get(&escrow_id)) {
+                    if escrow.payment_token.is_none() {
+                        remaining = remaining.saturating_sub(*value);
+                    }
+                }
+                results.push(result.map(|_| ()));
+            }
+            // The unspent value was never counted in `total_locked`.
+            if remaining > 0 {
+                self.env()
+                    .transfer(caller, remaining)
+                    .map_err(|_| EscrowError::TransferFailed)?;
+            }
+            Ok(results)
+        }
+
+        /// Claims matured rent on each escrow, as `claim_rent` does.
+        #[ink(message)]
+        pub fn claim_rent_batch(&mut self, escrow_ids: Vec<Hash>) -> Result<Vec<Result<()>>> {
+            Self::ensure_batch_size(escrow_ids.len())?;
+            Ok(escrow_ids
+                .into_iter()
+                .map(|escrow_id| self.claim_rent(escrow_id))
+                .collect())
+        }
+
+        /// Adds to the caller's vault in `token`, or in the native currency
+        /// for `None`. For the native currency `amount` is ignored and the
+        /// transferred value is credited.
+        #[ink(message, payable)]
+        pub fn fund_vault(&mut self, token: Option<AccountId>, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let value = self.incoming_amount(token, amount)?;
+            if value == 0 {
+                return Err(EscrowError::ZeroAmount);
+            }
+            self.ensure_within_tvl_cap(token, value)?;
+            self.collect_payment(token, caller, value)?;
+
+            let balance = self.vault_balance(caller, token).try_add(value)?;
+            self.vaults.insert((caller, token), &balance);
+            if let Some(token) = token {
+                let total = self.vault_token_totals.get(&token).unwrap_or(0);
+                self.vault_token_totals
+                    .insert(token, &total.try_add(value)?);
+            }
+            self.env().emit_event(VaultFunded {
+                renter: caller,
+                token,
+                amount: value,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn withdraw_from_vault(
+            &mut self,
+            token: Option<AccountId>,
+            amount: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            if amount == 0 {
+                return Err(EscrowError::ZeroAmount);
+            }
+            self.draw_from_vault(caller, token, amount)?;
+            self.pay_out(token, caller, amount)?;
+            self.env().emit_event(VaultWithdrawn {
+                renter: caller,
+                token,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn vault_balance(&self, renter: AccountId, token: Option<AccountId>) -> Balance {
+            self.vaults.get(&(renter, token)).unwrap_or(0)
+        }
+
+        /// Caps what `auto_pay` may draw from the caller's vaults in total
+        /// within each `period_length`, or lifts the cap for `None`. Starts a
+        /// new window.
+        #[ink(message)]
+        pub fn set_vault_period_cap(
+            &mut self,
+            period_length: u64,
+            period_cap: Option<Balance>,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            let mut policy = self.vault_policy(caller);
+            policy.period_length = period_length;
+            policy.period_cap = period_cap;
+            policy.period_start = self.now();
+            policy.spent_in_period = 0;
+            self.vault_policies.insert(caller, &policy);
+            Ok(())
+        }
+
+        /// Caps a single `auto_pay` draw for one of the caller's escrows, or
+        /// lifts the cap for `None`.
+        #[ink(message)]
+        pub fn set_auto_pay_cap(&mut self, escrow_id: Hash, cap: Option<Balance>) -> Result<()> {
+            let caller = self.env().caller();
+            match cap {
+                Some(cap) => {
+                    self.auto_pay_caps.insert((caller, escrow_id), &cap);
+                }
+                None => self.auto_pay_caps.remove((caller, escrow_id)),
+            }
+            Ok(())
+        }
+
+        /// Stops or resumes `auto_pay` draws from the caller's vaults.
+        /// Withdrawals and manual payments are unaffected.
+        #[ink(message)]
+        pub fn set_vault_paused(&mut self, paused: bool) -> Result<()> {
+            let caller = self.env().caller();
+            let mut policy = self.vault_policy(caller);
+            policy.paused = paused;
+            self.vault_policies.insert(caller, &policy);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn vault_policy(&self, renter: AccountId) -> VaultPolicy {
+            self.vault_policies.get(&renter).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn auto_pay_cap(&self, renter: AccountId, escrow_id: Hash) -> Option<Balance> {
+            self.auto_pay_caps.get(&(renter, escrow_id))
+        }
+
+        /// Opens a rent assistance program sponsored by the caller. It pays
+        /// nothing until funded with `fund_program`.
+        #[ink(message)]
+        pub fn create_assistance_program(&mut self, terms: ProgramTerms) -> Result<u32> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            if terms.coverage_bps == 0
+                || terms.coverage_bps > MAX_BPS
+                || terms.min_rent > terms.max_rent
+                || terms.max_enrollments == 0
+            {
+                return Err(EscrowError::InvalidProgram);
+            }
+            let program_id = self.next_program_id.get_or_default();
+            self.next_program_id.set(&program_id.try_add(1)?);
+            self.assistance_programs.insert(
+                program_id,
+                &AssistanceProgram {
+                    sponsor: caller,
+                    terms,
+                    enrollments: 0,
+                    balance: 0,
+                    total_disbursed: 0,
+                    active: true,
+                },
+            );
+            self.env().emit_event(AssistanceProgramCreated {
+                program_id,
+                sponsor: caller,
+                terms,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(program_id)
+        }
+
+        /// Adds the sponsor's funds to a program. For the native currency
+        /// `amount` is ignored and the transferred value is credited.
+        #[ink(message, payable)]
+        pub fn fund_program(&mut self, program_id: u32, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut program = self.get_program_or_revert(program_id)?;
+            self.ensure_caller_is_sponsor(&program, &caller)?;
+            if !program.active {
+                return Err(EscrowError::ProgramInactive);
+            }
+            let token = program.terms.payment_token;
+            let value = self.incoming_amount(token, amount)?;
+            if value == 0 {
+                return Err(EscrowError::ZeroAmount);
+            }
+            self.ensure_within_tvl_cap(token, value)?;
+            self.collect_payment(token, caller, value)?;
+
+            program.balance = program.balance.try_add(value)?;
+            self.assistance_programs.insert(program_id, &program);
+            if let Some(token) = token {
+                let total = self.program_token_totals.get(&token).unwrap_or(0);
+                self.program_token_totals
+                    .insert(token, &total.try_add(value)?);
+            }
+            self.env().emit_event(AssistanceProgramFunded {
+                program_id,
+                amount: value,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Ends a program and refunds its unspent funds to the sponsor.
+        /// Enrolled escrows stop receiving assistance from their next payment.
+        #[ink(message)]
+        pub fn terminate_program(&mut self, program_id: u32) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut program = self.get_program_or_revert(program_id)?;
+            self.ensure_caller_is_sponsor(&program, &caller)?;
+            if !program.active {
+                return Err(EscrowError::ProgramInactive);
+            }
+            let refund = program.balance;
+            program.active = false;
+            program.balance = 0;
+            self.assistance_programs.insert(program_id, &program);
+            self.release_program_funds(program.terms.payment_token, refund);
+            if refund > 0 {
+                self.pay_out(program.terms.payment_token, caller, refund)?;
+            }
+            self.env().emit_event(AssistanceProgramTerminated {
+                program_id,
+                refund,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Enrolls one of the caller's escrows in a program whose criteria it
+        /// meets. The program then pays its share of every rent payment.
+        #[ink(message)]
+        pub fn enroll_in_program(&mut self, program_id: u32, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            if !Self::is_renter(&escrow, &caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            if self.active_program_of(escrow_id).is_some() {
+                return Err(EscrowError::AlreadyEnrolled);
+            }
+            let mut program = self.get_program_or_revert(program_id)?;
+            if !program.active {
+                return Err(EscrowError::ProgramInactive);
+            }
+            let terms = program.terms;
+            let jurisdiction = self.jurisdictions.get(&escrow_id).unwrap_or(0);
+            if terms.payment_token != escrow.payment_token
+                || (terms.jurisdiction != 0 && terms.jurisdiction != jurisdiction)
+                || escrow.rent_amount < terms.min_rent
+                || escrow.rent_amount > terms.max_rent
+            {
+                return Err(EscrowError::NotEligible);
+            }
+            if program.enrollments >= terms.max_enrollments {
+                return Err(EscrowError::EnrollmentFull);
+            }
+
+            program.enrollments = program.enrollments.try_add(1)?;
+            self.assistance_programs.insert(program_id, &program);
+            self.program_enrollments.insert(escrow_id, &program_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(EnrollmentChanged {
+                program_id,
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                enrolled: true,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Takes an escrow out of its program, freeing its enrollment slot.
+        /// Either the renter or the sponsor may do this.
+        #[ink(message)]
+        pub fn leave_program(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let program_id = self
+                .program_enrollments
+                .get(&escrow_id)
+                .ok_or(EscrowError::NotEnrolled)?;
+            let mut program = self.get_program_or_revert(program_id)?;
+            if !Self::is_renter(&escrow, &caller) && program.sponsor != caller {
+                return Err(EscrowError::NotParty);
+            }
+
+            program.enrollments = program.enrollments.saturating_sub(1);
+            self.assistance_programs.insert(program_id, &program);
+            self.program_enrollments.remove(escrow_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(EnrollmentChanged {
+                program_id,
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                enrolled: false,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn assistance_program(&self, program_id: u32) -> Option<AssistanceProgram> {
+            self.assistance_programs.get(&program_id)
+        }
+
+        #[ink(message)]
+        pub fn enrolled_program(&self, escrow_id: Hash) -> Option<u32> {
+            self.program_enrollments.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn compute_late_fee(&self, escrow_id: Hash) -> Result<Balance> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            Ok(self.late_fee(&escrow))
+        }
+
+        #[ink(message)]
+        pub fn rent_due(&self, escrow_id: Hash) -> Result<(u64, Balance)> {
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.apply_rent_peg(escrow_id, &mut escrow)?;
+            self.apply_sales_report(escrow_id, &mut escrow)?;
+            if escrow.periods_paid >= self.total_periods(&escrow) {
+                return Ok((escrow.next_due_timestamp, 0));
+            }
+            let assisted = self
+                .assistance_for(escrow_id, &escrow)
+                .map_or(0, |(_, amount)| amount);
+            let scheduled = self
+                .scheduled_amount(&escrow)?
+                .try_add(self.protection_fee(escrow_id))?;
+            Ok((
+                escrow.next_due_timestamp,
+                scheduled.saturating_sub(assisted),
+            ))
+        }
+
+        /// Rent still owed for the open period once it has fallen due, after
+        /// partial payments and assistance. Zero before the due date.
+        #[ink(message)]
+        pub fn outstanding_balance(&self, escrow_id: Hash) -> Result<Balance> {
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.status != LeaseStatus::Active
+                || self.now() < escrow.next_due_timestamp
+                || escrow.periods_paid >= self.total_periods(&escrow)
+            {
+                return Ok(0);
+            }
+            self.period_balance(escrow_id, &mut escrow)
+        }
+
+        #[ink(message)]
+        pub fn claim_rent(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            if self.managers.get(&escrow_id) != Some(caller) {
+                self.ensure_landlord_action(
+                    escrow_id,
+                    &escrow,
+                    &caller,
+                    LandlordAction::ClaimRent,
+                )?;
+            }
+            if self.rent_streams.contains(&escrow_id) {
+                return Err(EscrowError::RentIsStreamed);
+            }
+
+            let matured = self.matured_periods(&escrow);
+            if matured <= escrow.periods_claimed && escrow.late_fees == 0 {
+                return Err(EscrowError::NothingToClaim);
+            }
+            let periods = matured.saturating_sub(escrow.periods_claimed);
+            let rent = core::cmp::min(
+                escrow.rent_amount.saturating_mul(Balance::from(periods)),
+                escrow.escrow_balance,
+            );
+            let amount = rent.try_add(escrow.late_fees)?;
+            self.pay_landlord(escrow_id, &escrow, amount)?;
+
+            escrow.escrow_balance = escrow.escrow_balance.try_sub(rent)?;
+            escrow.late_fees = 0;
+            escrow.periods_claimed = core::cmp::max(matured, escrow.periods_claimed);
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(RentClaimed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                periods,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Pays the landlord of a streaming escrow the rent accrued since the
+        /// last withdrawal, together with any late fees.
+        #[ink(message)]
+        pub fn withdraw_earned(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            let withdrawn = self
+                .rent_streams
+                .get(&escrow_id)
+                .ok_or(EscrowError::RentNotStreamed)?;
+            let earned = self.unwithdrawn_rent(&escrow, withdrawn);
+            let amount = earned.try_add(escrow.late_fees)?;
+            if amount == 0 {
+                return Err(EscrowError::NothingToClaim);
+            }
+            self.pay_landlord(escrow_id, &escrow, amount)?;
+
+            escrow.escrow_balance = escrow.escrow_balance.try_sub(earned)?;
+            escrow.late_fees = 0;
+            self.rent_streams
+                .insert(escrow_id, &withdrawn.try_add(earned)?);
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(EarnedRentWithdrawn {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Returns the prepaid rent a streaming escrow had not yet earned when
+        /// it was terminated early.
+        #[ink(message)]
+        pub fn withdraw_unearned(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if !Self::is_renter(&escrow, &caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            if !self.rent_streams.contains(&escrow_id) {
+                return Err(EscrowError::RentNotStreamed);
+            }
+            if escrow.status != LeaseStatus::Terminated {
+                return Err(EscrowError::NotTerminated);
+            }
+            let amount = escrow.escrow_balance;
+            if amount == 0 {
+                return Err(EscrowError::NothingToClaim);
+            }
+            let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            self.refund_renters(&escrow, renter, amount)?;
+
+            escrow.escrow_balance = 0;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(UnearnedRentWithdrawn {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Rent a streaming escrow has accrued and the landlord has not yet
+        /// withdrawn, late fees excluded.
+        #[ink(message)]
+        pub fn earned_rent(&self, escrow_id: Hash) -> Result<Balance> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let withdrawn = self
+                .rent_streams
+                .get(&escrow_id)
+                .ok_or(EscrowError::RentNotStreamed)?;
+            if escrow.status != LeaseStatus::Active {
+                return Ok(0);
+            }
+            Ok(self.unwithdrawn_rent(&escrow, withdrawn))
+        }
+
+        #[ink(message)]
+        pub fn withdrawn_by_landlord(&self, escrow_id: Hash) -> Balance {
+            self.rent_streams.get(&escrow_id).unwrap_or(0)
+        }
+
+        #[ink(message, payable)]
+        pub fn pay_deposit(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if escrow.deposit_balance > 0 {
+                return Err(EscrowError::DepositAlreadyPaid);
+            }
+            let required = self.upfront_deposit(escrow_id, &escrow);
+            let value = self.incoming_amount(escrow.payment_token, required)?;
+            if value < required {
+                return Err(EscrowError::InsufficientDeposit);
+            }
+            self.ensure_within_balance_cap(&escrow, value)?;
+            self.collect_payment(escrow.payment_token, caller, value)?;
+
+            escrow.deposit_balance = value;
+            self.store_escrow(escrow_id, &escrow);
+            self.record_payment(escrow_id, &escrow, caller, value, PaymentKind::Deposit);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DepositPaid {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                payer: caller,
+                amount: value,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Pays the next installment of a deposit plan. An installment paid
+        /// after its grace period carries the same late fee as rent.
+        #[ink(message, payable)]
+        pub fn pay_deposit_installment(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            if !Self::is_renter(&escrow, &caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            let mut schedule = self
+                .deposit_schedules
+                .get(&escrow_id)
+                .filter(|schedule| schedule.installments_paid < schedule.plan.installments)
+                .ok_or(EscrowError::NoInstallmentDue)?;
+            if schedule.plan.upfront > 0 && escrow.deposit_balance == 0 {
+                return Err(EscrowError::DepositNotPaid);
+            }
+            let installment = Self::deposit_installment(&escrow, &schedule);
+            let late_fee = self.installment_late_fee(&escrow, &schedule);
+            let due = installment.try_add(late_fee)?;
+            let value = self.incoming_amount(escrow.payment_token, due)?;
+            if value < due {
+                return Err(EscrowError::InsufficientDeposit);
+            }
+            self.ensure_within_balance_cap(&escrow, value)?;
+            self.collect_payment(escrow.payment_token, caller, value)?;
+
+            escrow.deposit_balance = escrow.deposit_balance.try_add(installment)?;
+            escrow.late_fees = escrow.late_fees.try_add(late_fee)?;
+            escrow.overpayment = escrow.overpayment.try_add(value.try_sub(due)?)?;
+            let contributed = self.contributions.get(&(escrow_id, caller)).unwrap_or(0);
+            self.contributions
+                .insert((escrow_id, caller), &contributed.try_add(value)?);
+            schedule.installments_paid = schedule.installments_paid.try_add(1)?;
+            self.deposit_schedules.insert(escrow_id, &schedule);
+            self.store_escrow(escrow_id, &escrow);
+            self.record_payment(escrow_id, &escrow, caller, value, PaymentKind::Deposit);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DepositInstallmentPaid {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                payer: caller,
+                amount: value,
+                late_fee,
+                installments_paid: schedule.installments_paid,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// When the next deposit installment falls due and what paying it now
+        /// costs, late fee included. `(0, 0)` once the deposit is funded.
+        #[ink(message)]
+        pub fn deposit_installment_due(&self, escrow_id: Hash) -> Result<(u64, Balance)> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            match self.deposit_schedules.get(&escrow_id) {
+                Some(schedule) if schedule.installments_paid < schedule.plan.installments => {
+                    let amount = Self::deposit_installment(&escrow, &schedule)
+                        .try_add(self.installment_late_fee(&escrow, &schedule))?;
+                    Ok((Self::installment_due_at(&escrow, &schedule), amount))
+                }
+                _ => Ok((0, 0)),
+            }
+        }
+
+        #[ink(message)]
+        pub fn deposit_schedule(&self, escrow_id: Hash) -> Option<DepositSchedule> {
+            self.deposit_schedules.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn release_deposit(
+            &mut self,
+            escrow_id: Hash,
+            deduction_amount: Balance,
+            reason: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ReleaseDeposit {
+                    deduction_amount,
+                    reason,
+                },
+            )?;
+            self.ensure_lease_duration_passed(&escrow)?;
+            self.divest(escrow_id, &mut escrow)?;
+            if escrow.deposit_balance == 0 {
+                return Err(EscrowError::DepositNotPaid);
+            }
+            if deduction_amount > escrow.deposit_balance {
+                return Err(EscrowError::DeductionExceedsDeposit);
+            }
+
+            let refund = escrow.deposit_balance.try_sub(deduction_amount)?;
+            if deduction_amount > 0 {
+                self.pay_out(escrow.payment_token, escrow.landlord, deduction_amount)?;
+            }
+            let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            if refund > 0 {
+                self.pay_out(escrow.payment_token, renter, refund)?;
+            }
+
+            escrow.deposit_balance = 0;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DepositSettled {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                deduction: deduction_amount,
+                refund,
+                reason,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn release_deposit_portion(&mut self, escrow_id: Hash, amount: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ReleaseDepositPortion { amount },
+            )?;
+            self.divest(escrow_id, &mut escrow)?;
+            if escrow.deposit_balance == 0 {
+                return Err(EscrowError::DepositNotPaid);
+            }
+            if amount > escrow.deposit_balance {
+                return Err(EscrowError::AmountExceedsDeposit);
+            }
+
+            let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            self.pay_out(escrow.payment_token, renter, amount)?;
+
+            escrow.deposit_balance = escrow.deposit_balance.try_sub(amount)?;
+            let remaining = escrow.deposit_balance;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DepositPortionReleased {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                remaining,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Files a claim for damages on a zero-deposit lease against the
+        /// protection pool, once per lease after its duration has passed. A
+        /// claim is capped by the plan's cover and by the protection fees the
+        /// lease has actually paid in. Takes the place of deposit deductions,
+        /// so it has to be filed before `lease_ended` settles the lease. The
+        /// renter may dispute it with `dispute_protection_claim` during the
+        /// challenge window; otherwise anyone can pay it out afterwards with
+        /// `execute_protection_claim`.
+        #[ink(message)]
+        pub fn claim_protection(
+            &mut self,
+            escrow_id: Hash,
+            amount: Balance,
+            reason: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ClaimProtection { amount, reason },
+            )?;
+            self.ensure_lease_duration_passed(&escrow)?;
+            let plan = self
+                .protection_plans
+                .get(&escrow_id)
+                .ok_or(EscrowError::NotProtected)?;
+            if self.protection_claims.contains(&escrow_id)
+                || self.pending_protection_claims.contains(&escrow_id)
+            {
+                return Err(EscrowError::ProtectionAlreadyClaimed);
+            }
+            let paid_in = plan.fee.try_mul(Balance::from(escrow.periods_paid))?;
+            let pool = self
+                .protection_pools
+                .get(&escrow.payment_token)
+                .unwrap_or(0);
+            if amount > plan.cover || amount > paid_in || amount > pool {
+                return Err(EscrowError::ClaimExceedsCover);
+            }
+
+            let filed_at = self.now();
+            self.pending_protection_claims.insert(
+                escrow_id,
+                &ProtectionClaim {
+                    amount,
+                    reason,
+                    filed_at,
+                    disputed: false,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ProtectionClaimFiled {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                reason,
+                challenge_deadline: filed_at.saturating_add(PROTECTION_CHALLENGE_WINDOW),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Disputes a pending protection claim within its challenge window,
+        /// leaving the decision to the owner or a registered arbiter.
+        #[ink(message)]
+        pub fn dispute_protection_claim(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            if !Self::is_renter(&escrow, &caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            let mut claim = self
+                .pending_protection_claims
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoProtectionClaim)?;
+            if self.now() >= claim.filed_at.saturating_add(PROTECTION_CHALLENGE_WINDOW) {
+                return Err(EscrowError::ChallengeWindowClosed);
+            }
+
+            claim.disputed = true;
+            self.pending_protection_claims.insert(escrow_id, &claim);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ProtectionClaimDisputed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                disputed_by: caller,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Pays out an undisputed protection claim once its challenge window
+        /// has passed. Anyone may call it.
+        #[ink(message)]
+        pub fn execute_protection_claim(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let claim = self
+                .pending_protection_claims
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoProtectionClaim)?;
+            if claim.disputed {
+                return Err(EscrowError::ProtectionClaimDisputed);
+            }
+            if self.now() < claim.filed_at.saturating_add(PROTECTION_CHALLENGE_WINDOW) {
+                return Err(EscrowError::TimelockActive);
+            }
+            self.pay_protection_claim(escrow_id, claim)
+        }
+
+        /// Approves or rejects a pending protection claim. Only the owner or
+        /// an arbiter from the registry may decide, never one the parties
+        /// picked themselves. A rejected claim cannot be filed again.
+        #[ink(message)]
+        pub fn decide_protection_claim(&mut self, escrow_id: Hash, approve: bool) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            self.ensure_owner_or_registered_arbiter(&caller)?;
+            let claim = self
+                .pending_protection_claims
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoProtectionClaim)?;
+            if approve {
+                return self.pay_protection_claim(escrow_id, claim);
+            }
+
+            self.pending_protection_claims.take(&escrow_id);
+            self.protection_claims.insert(escrow_id, &0);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ProtectionClaimRejected {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                rejected_by: caller,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn protection_pool(&self, token: Option<AccountId>) -> Balance {
+            self.protection_pools.get(&token).unwrap_or(0)
+        }
+
+        /// What the pool paid for the lease's claim; zero if it was rejected.
+        #[ink(message)]
+        pub fn protection_claim(&self, escrow_id: Hash) -> Option<Balance> {
+            self.protection_claims.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn pending_protection_claim(&self, escrow_id: Hash) -> Option<ProtectionClaim> {
+            self.pending_protection_claims.get(&escrow_id)
+        }
+
+        /// Sets the terms of the rent-default insurance new opt-ins take, or
+        /// stops taking opt-ins with `None`. Escrows already insured keep
+        /// the policy they opted into.
+        #[ink(message)]
+        pub fn set_insurance_policy(&mut self, policy: Option<InsurancePolicy>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            if let Some(policy) = policy {
+                if policy.premium_bps == 0
+                    || policy.premium_bps > MAX_BPS
+                    || policy.default_periods == 0
+                    || policy.cover_periods == 0
+                    || policy.max_payout == 0
+                {
+                    return Err(EscrowError::InvalidInsurancePolicy);
+                }
+            }
+            self.insurance_policy.set(&policy);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn insurance_policy(&self) -> Option<InsurancePolicy> {
+            self.insurance_policy.get_or_default()
+        }
+
+        /// Insures the listing against rent default under the current
+        /// policy. Only open listings can be insured, so a landlord cannot
+        /// opt in once a renter has started falling behind.
+        #[ink(message)]
+        pub fn opt_into_insurance(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::OptIntoInsurance,
+            )?;
+            if escrow.status != LeaseStatus::Open {
+                return Err(EscrowError::NotOpen);
+            }
+            let policy = self
+                .insurance_policy
+                .get_or_default()
+                .ok_or(EscrowError::NoInsurancePolicy)?;
+            self.insured_escrows.insert(escrow_id, &policy);
+            Ok(())
+        }
+
+        /// Files a claim against the insurance fund for rent the renter owes,
+        /// once they are the policy's `default_periods` behind. The claim
+        /// covers the periods in arrears up to `cover_periods`, at most the
+        /// policy's `max_payout`, and is paid once the owner or a registered
+        /// arbiter approves it.
+        #[ink(message)]
+        pub fn claim_insurance(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ClaimInsurance,
+            )?;
+            let policy = self
+                .insured_escrows
+                .get(&escrow_id)
+                .ok_or(EscrowError::NotInsured)?;
+            match self.insurance_claims.get(&escrow_id) {
+                Some(claim) if claim.paid_out.is_some() => {
+                    return Err(EscrowError::InsuranceAlreadyClaimed)
+                }
+                Some(_) => return Err(EscrowError::InsuranceClaimPending),
+                None => {}
+            }
+            let periods_in_arrears = self.periods_in_arrears(&escrow);
+            if periods_in_arrears < policy.default_periods {
+                return Err(EscrowError::NotInDefault);
+            }
+
+            let covered = core::cmp::min(periods_in_arrears, policy.cover_periods);
+            let amount = core::cmp::min(
+                escrow.rent_amount.try_mul(Balance::from(covered))?,
+                policy.max_payout,
+            );
+            self.insurance_claims.insert(
+                escrow_id,
+                &InsuranceClaim {
+                    amount,
+                    periods_in_arrears,
+                    filed_at: self.now(),
+                    paid_out: None,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InsuranceClaimFiled {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                periods_in_arrears,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Approves or rejects the escrow's pending insurance claim. Only the
+        /// owner or a registered arbiter may decide; the escrow's own arbiter
+        /// is picked by the parties and so cannot. An approved claim pays out
+        /// what the fund holds up to the claimed amount, and the fund takes
+        /// over that much of the renter's arrears: the next rent the renter
+        /// pays goes to the fund until it is repaid. A rejected claim is
+        /// dropped and the landlord may file again.
+        #[ink(message)]
+        pub fn decide_insurance_claim(&mut self, escrow_id: Hash, approve: bool) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_owner_or_registered_arbiter(&caller)?;
+            let mut claim = self
+                .insurance_claims
+                .get(&escrow_id)
+                .filter(|claim| claim.paid_out.is_none())
+                .ok_or(EscrowError::NoInsuranceClaim)?;
+
+            let mut payout = 0;
+            if approve {
+                let token = escrow.payment_token;
+                let fund = self.insurance_fund.get(&token).unwrap_or(0);
+                payout = core::cmp::min(claim.amount, fund);
+                claim.paid_out = Some(payout);
+                self.insurance_claims.insert(escrow_id, &claim);
+                self.insurance_fund
+                    .insert(token, &fund.saturating_sub(payout));
+                if payout > 0 {
+                    let owed = self.insurance_subrogations.get(&escrow_id).unwrap_or(0);
+                    self.insurance_subrogations
+                        .insert(escrow_id, &owed.try_add(payout)?);
+                    self.pay_out(token, escrow.landlord, payout)?;
+                }
+            } else {
+                self.insurance_claims.take(&escrow_id);
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InsuranceClaimDecided {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                decided_by: caller,
+                approved: approve,
+                payout,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn insurance_fund(&self, token: Option<AccountId>) -> Balance {
+            self.insurance_fund.get(&token).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn insured_policy(&self, escrow_id: Hash) -> Option<InsurancePolicy> {
+            self.insured_escrows.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn insurance_claim(&self, escrow_id: Hash) -> Option<InsuranceClaim> {
+            self.insurance_claims.get(&escrow_id)
+        }
+
+        /// Rent arrears the insurance fund paid and still recovers from the
+        /// renter's payments.
+        #[ink(message)]
+        pub fn insurance_subrogation(&self, escrow_id: Hash) -> Balance {
+            self.insurance_subrogations.get(&escrow_id).unwrap_or(0)
+        }
+
+        /// Forwards the escrow's deposit to the yield strategy. Anyone may
+        /// call it, typically a keeper once the deposit is paid. The deposit
+        /// comes back with its yield whenever it is released, the lease is
+        /// terminated, or a dispute is resolved.
+        #[ink(message)]
+        pub fn invest_deposit(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let strategy = self
+                .yield_strategy
+                .get_or_default()
+                .ok_or(EscrowError::NoYieldStrategy)?;
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            if !self.yield_splits.contains(&escrow_id) {
+                return Err(EscrowError::YieldNotEnabled);
+            }
+            if self.yield_positions.contains(&escrow_id) {
+                return Err(EscrowError::AlreadyInvested);
+            }
+            let amount = escrow.deposit_balance;
+            if amount == 0 {
+                return Err(EscrowError::DepositNotPaid);
+            }
+
+            let value = self.strategy_balance(strategy)?;
+            let total_shares = self.total_yield_shares.get_or_default();
+            let shares = if total_shares == 0 || value == 0 {
+                amount
+            } else {
+                amount.try_mul(total_shares)? / value
+            };
+            build_call::<Environment>()
+                .call(strategy)
+                .transferred_value(amount)
+                .exec_input(ExecutionInput::new(Selector::new(
+                    yield_strategy::DEPOSIT_SELECTOR,
+                )))
+                .returns::<core::result::Result<(), StrategyError>>()
+                .try_invoke()
+                .map_err(|_| EscrowError::StrategyCallFailed)?
+                .map_err(|_| EscrowError::StrategyCallFailed)?
+                .map_err(|_| EscrowError::StrategyCallFailed)?;
+
+            let total_shares = self.total_yield_shares.get_or_default();
+            self.total_yield_shares.set(&total_shares.try_add(shares)?);
+            self.yield_positions.insert(
+                escrow_id,
+                &YieldPosition {
+                    shares,
+                    principal: amount,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DepositInvested {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                shares,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn yield_position(&self, escrow_id: Hash) -> Option<YieldPosition> {
+            self.yield_positions.get(&escrow_id)
+        }
+
+        /// Yield the escrow's invested deposit has earned so far.
+        #[ink(message)]
+        pub fn accrued_yield(&self, escrow_id: Hash) -> Result<Balance> {
+            let position = match self.yield_positions.get(&escrow_id) {
+                Some(position) => position,
+                None => return Ok(0),
+            };
+            let strategy = self
+                .yield_strategy
+                .get_or_default()
+                .ok_or(EscrowError::NoYieldStrategy)?;
+            let value = self.position_value(strategy, &position)?;
+            Ok(value.saturating_sub(position.principal))
+        }
+
+        #[ink(message)]
+        pub fn lease_ended(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            self.end_lease(escrow_id, escrow)?;
+            Ok(())
+        }
+
+        /// Settles a lease whose term has passed when the landlord has not
+        /// done so. Anyone may call it; the caller is paid the keeper reward
+        /// out of collected fees once the settlement goes through.
+        #[ink(message)]
+        pub fn settle_expired(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            let token = escrow.payment_token;
+            if !self.end_lease(escrow_id, escrow)? {
+                return Ok(());
+            }
+
+            let collected = self.collected_fees(token);
+            let reward = self.keeper_reward.get_or_default().min(collected);
+            if reward == 0 {
+                return Ok(());
+            }
+            self.collected_fees
+                .insert(token, &collected.saturating_sub(reward));
+            self.pay_out(token, caller, reward)?;
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(KeeperRewarded {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                keeper: caller,
+                amount: reward,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Sets the reward paid to callers of `settle_expired`, in units of
+        /// the settled escrow's payment currency.
+        #[ink(message)]
+        pub fn set_keeper_reward(&mut self, reward: Balance) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.keeper_reward.set(&reward);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn keeper_reward(&self) -> Balance {
+            self.keeper_reward.get_or_default()
+        }
+
+        /// Burns the lease tokens of escrows that have ended but whose token
+        /// was left behind, e.g. by state from before tokens were burned on
+        /// settlement. Anyone may call it; ids of live leases are skipped.
+        /// Returns how many tokens were burned.
+        #[ink(message)]
+        pub fn burn_stale_tokens(&mut self, escrow_ids: Vec<Hash>) -> Result<u32> {
+            self.ensure_not_paused()?;
+            Self::ensure_batch_size(escrow_ids.len())?;
+            let mut burned = 0;
+            for escrow_id in escrow_ids {
+                if !self.lease_token_owners.contains(&escrow_id) {
+                    continue;
+                }
+                let ended = self.escrows.get(&escrow_id).map_or(true, |escrow| {
+                    matches!(
+                        escrow.status,
+                        LeaseStatus::Terminated | LeaseStatus::Settled | LeaseStatus::Migrated
+                    )
+                });
+                if ended {
+                    self.burn_lease_token(escrow_id);
+                    burned = burned.saturating_add(1);
+                }
+            }
+            Ok(burned)
+        }
+
+        /// Lets the renter co-confirm a queued settlement, which then executes
+        /// without waiting for the timeout.
+        #[ink(message)]
+        pub fn confirm_settlement(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if !self.pending_settlements.contains(&escrow_id) {
+                return Err(EscrowError::NoPendingSettlement);
+            }
+
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(SettlementConfirmed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                confirmed_by: caller,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            self.settle_lease(escrow_id, escrow)
+        }
+
+        #[ink(message)]
+        pub fn pending_settlement(&self, escrow_id: Hash) -> Option<PendingSettlement> {
+            self.pending_settlements.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn cancel_lease(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_not_leased(&escrow)?;
+            self.ensure_landlord_action(escrow_id, &escrow, &caller, LandlordAction::CancelLease)?;
+            self.transition(&mut escrow, LeaseStatus::Terminated)?;
+
+            // Anything held before the lease started was paid by the renter.
+            let mut refund = escrow.escrow_balance.try_add(escrow.overpayment)?;
+            if let Some(renter) = escrow.renter {
+                if refund > 0 {
+                    self.release(escrow_id, escrow.payment_token, renter, refund)?;
+                }
+            }
+            for (co_renter, deposit) in self.lease_signatures.take(&escrow_id).unwrap_or_default() {
+                self.release(escrow_id, escrow.payment_token, co_renter, deposit)?;
+                refund = refund.try_add(deposit)?;
+            }
+            escrow.deposit_balance = 0;
+
+            let renter = escrow.renter;
+            escrow.escrow_balance = 0;
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            self.burn_lease_token(escrow_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseCancelled {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                refund,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Returns whatever the renter paid above the amounts owed.
+        #[ink(message)]
+        pub fn refund_overpayment(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            let amount = escrow.overpayment;
+            if amount == 0 {
+                return Err(EscrowError::NothingToClaim);
+            }
+            self.pay_out(escrow.payment_token, caller, amount)?;
+
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(OverpaymentRefunded {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                to: caller,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Ends an active lease before its duration has passed. The landlord
+        /// keeps rent for matured periods and late fees; the renter gets back
+        /// the deposit and the unearned prepaid rent. If the renter terminates,
+        /// up to `early_termination_penalty_periods` of that unearned rent go
+        /// to the landlord instead.
+        #[ink(message)]
+        pub fn terminate_early(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            let by_renter = Self::is_renter(&escrow, &caller);
+            if !by_renter {
+                if caller != escrow.landlord && !self.is_co_owner(escrow_id, &caller) {
+                    return Err(EscrowError::NotParty);
+                }
+                let action = LandlordAction::TerminateEarly;
+                self.ensure_landlord_action(escrow_id, &escrow, &caller, action)?;
+            }
+            if by_renter && !escrow.co_renters.is_empty() {
+                let mut approvals = self
+                    .termination_approvals
+                    .get(&escrow_id)
+                    .unwrap_or_default();
+                if !approvals.contains(&caller) {
+                    approvals.push(caller);
+                    let header = self.event_header(escrow_id);
+                    self.env().emit_event(TerminationApproved {
+                        escrow_id,
+                        landlord: header.landlord,
+                        renter: header.renter,
+                        sequence: header.sequence,
+                        approved_by: caller,
+                        schema_version: EVENT_SCHEMA_VERSION,
+                    });
+                }
+                if approvals.len() < escrow.co_renters.len() {
+                    self.termination_approvals.insert(escrow_id, &approvals);
+                    return Ok(());
+                }
+                self.termination_approvals.take(&escrow_id);
+            }
+            self.close_early(escrow_id, escrow, caller, by_renter)
+        }
+
+        /// Id under which the owners of a jointly owned escrow approve
+        /// `action`.
+        #[ink(message)]
+        pub fn landlord_action_id(&self, escrow_id: Hash, action: LandlordAction) -> Hash {
+            Hash::from(
+                self.env()
+                    .hash_encoded::<Blake2x256, _>(&(escrow_id, action)),
+            )
+        }
+
+        /// Records the caller's approval of a landlord action on a jointly
+        /// owned escrow. The owner who finally makes the call uses up the
+        /// approvals.
+        #[ink(message)]
+        pub fn approve_action(&mut self, escrow_id: Hash, action_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            if !self.joint_ownerships.contains(&escrow_id) {
+                return Err(EscrowError::NotJointlyOwned);
+            }
+            if caller != escrow.landlord && !self.is_co_owner(escrow_id, &caller) {
+                return Err(EscrowError::NotLandlord);
+            }
+
+            let mut approvals = self.action_approvals(escrow_id, action_id);
+            if approvals.contains(&caller) {
+                return Ok(());
+            }
+            approvals.push(caller);
+            self.action_approvals
+                .insert((escrow_id, action_id), &approvals);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ActionApproved {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                action_id,
+                approved_by: caller,
+                approvals: approvals.len() as u32,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn action_approvals(&self, escrow_id: Hash, action_id: Hash) -> Vec<AccountId> {
+            self.action_approvals
+                .get(&(escrow_id, action_id))
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn joint_ownership(&self, escrow_id: Hash) -> Option<JointOwnership> {
+            self.joint_ownerships.get(&escrow_id)
+        }
+
+        /// Lets `manager` make the landlord's operational calls on the escrow,
+        /// such as claiming rent or proposing a renewal. Changes to where
+        /// funds go and terminating the lease stay with the landlord. A new
+        /// manager replaces the previous one. On a jointly owned escrow the
+        /// appointment needs the owners' approvals, like the calls it opens.
+        #[ink(message)]
+        pub fn set_manager(&mut self, escrow_id: Hash, manager: AccountId) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::SetManager { manager },
+            )?;
+            self.managers.insert(escrow_id, &manager);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ManagerChanged {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                manager: Some(manager),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn revoke_manager(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::RevokeManager,
+            )?;
+            self.managers
+                .take(&escrow_id)
+                .ok_or(EscrowError::NoManager)?;
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ManagerChanged {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                manager: None,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn manager(&self, escrow_id: Hash) -> Option<AccountId> {
+            self.managers.get(&escrow_id)
+        }
+
+        /// Asks the landlord to let `sub_renter` sublet the property from the
+        /// caller, starting when the sub-renter accepts. A new request
+        /// replaces the previous one.
+        #[ink(message)]
+        pub fn request_sublease(
+            &mut self,
+            escrow_id: Hash,
+            sub_renter: AccountId,
+            sub_rent: Balance,
+            sub_duration: u64,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if sub_duration == 0 {
+                return Err(EscrowError::InvalidSubleaseDuration);
+            }
+            self.ensure_fits_parent_lease(escrow_id, sub_duration)?;
+
+            self.sublease_requests.insert(
+                escrow_id,
+                &SubleaseRequest {
+                    sub_renter,
+                    sub_rent,
+                    sub_duration,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(SubleaseRequested {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                sub_renter,
+                sub_rent,
+                sub_duration,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Approves the pending sublease and creates its child escrow, with
+        /// the renter as landlord and the sub-renter as renter, in the parent
+        /// lease's currency. The sub-renter starts it with `rent`. Returns the
+        /// child's id.
+        #[ink(message)]
+        pub fn approve_sublease(&mut self, escrow_id: Hash) -> Result<Hash> {
+            self.ensure_not_paused()?;
+            self.ensure_accepting_leases()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ApproveSublease,
+            )?;
+            let request = self
+                .sublease_requests
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoSubleaseRequest)?;
+            self.ensure_fits_parent_lease(escrow_id, request.sub_duration)?;
+            let sublessor = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            let terms = LeaseTerms {
+                rent_amount: request.sub_rent,
+                lease_duration: request.sub_duration,
+                payment_interval: core::cmp::min(escrow.payment_interval, request.sub_duration),
+                payment_token: escrow.payment_token,
+                ..Default::default()
+            };
+            self.ensure_valid_terms(&terms)?;
+            let child_id = self.next_escrow_id(request.sub_renter, sublessor)?;
+
+            self.sublease_requests.take(&escrow_id);
+            self.insert_new_escrow(child_id, Some(request.sub_renter), sublessor, terms)?;
+            Self::add_to_index(&mut self.escrows_by_landlord, sublessor, child_id);
+            Self::add_to_index(&mut self.escrows_by_renter, request.sub_renter, child_id);
+            self.parent_leases.insert(child_id, &escrow_id);
+            let mut children = self.subleases.get(&escrow_id).unwrap_or_default();
+            children.push(child_id);
+            self.subleases.insert(escrow_id, &children);
+            let header = self.event_header(child_id);
+            self.env().emit_event(EscrowCreated {
+                escrow_id: child_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                rent_amount: request.sub_rent,
+                lease_duration: request.sub_duration,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            let header = self.event_header(child_id);
+            self.env().emit_event(SubleaseApproved {
+                parent_id: escrow_id,
+                escrow_id: child_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(child_id)
+        }
+
+        #[ink(message)]
+        pub fn sublease_request(&self, escrow_id: Hash) -> Option<SubleaseRequest> {
+            self.sublease_requests.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn subleases(&self, escrow_id: Hash) -> Vec<Hash> {
+            self.subleases.get(&escrow_id).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn parent_lease(&self, escrow_id: Hash) -> Option<Hash> {
+            self.parent_leases.get(&escrow_id)
+        }
+
+        /// Opts the caller into a neutral inspection by committing to the hash
+        /// of a secret. Each party commits once; the draw cannot be restarted.
+        /// The second commitment opens the reveal window.
+        #[ink(message)]
+        pub fn opt_into_inspection(&mut self, escrow_id: Hash, commitment: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let mut draw = self.inspection_draws.get(&escrow_id).unwrap_or_default();
+            let slot = if escrow.renter == Some(caller) {
+                &mut draw.renter_commitment
+            } else if escrow.landlord == caller {
+                &mut draw.landlord_commitment
+            } else {
+                return Err(EscrowError::NotParty);
+            };
+            if slot.is_some() {
+                return Err(EscrowError::AlreadyCommitted);
+            }
+            *slot = Some(commitment);
+            if draw.renter_commitment.is_some() && draw.landlord_commitment.is_some() {
+                self.inspection_reveal_deadlines.insert(
+                    escrow_id,
+                    &self.now().saturating_add(INSPECTION_REVEAL_WINDOW),
+                );
+            }
+            self.inspection_draws.insert(escrow_id, &draw);
+            Ok(())
+        }
+
+        /// Reveals the caller's secret once both parties have committed. The
+        /// second reveal draws the inspector from the registered pool, seeded
+        /// by both secrets so neither party can steer the result.
+        #[ink(message)]
+        pub fn reveal_inspection_secret(&mut self, escrow_id: Hash, secret: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let mut draw = self
+                .inspection_draws
+                .get(&escrow_id)
+                .ok_or(EscrowError::InspectionNotAgreed)?;
+            let (Some(renter_commitment), Some(landlord_commitment)) =
+                (draw.renter_commitment, draw.landlord_commitment)
+            else {
+                return Err(EscrowError::InspectionNotAgreed);
+            };
+            let (commitment, slot) = if escrow.renter == Some(caller) {
+                (renter_commitment, &mut draw.renter_secret)
+            } else if escrow.landlord == caller {
+                (landlord_commitment, &mut draw.landlord_secret)
+            } else {
+                return Err(EscrowError::NotParty);
+            };
+            if slot.is_some() {
+                return Err(EscrowError::AlreadyCommitted);
+            }
+            if self.now() > self.inspection_reveal_deadline(escrow_id).unwrap_or(0) {
+                return Err(EscrowError::RevealWindowClosed);
+            }
+            if Hash::from(self.env().hash_encoded::<Blake2x256, _>(&secret)) != commitment {
+                return Err(EscrowError::InvalidReveal);
+            }
+            *slot = Some(secret);
+
+            if let (Some(renter_secret), Some(landlord_secret)) =
+                (draw.renter_secret, draw.landlord_secret)
+            {
+                let inspector =
+                    self.draw_inspector(&escrow, (escrow_id, renter_secret, landlord_secret))?;
+                self.assign_inspector(escrow_id, &mut draw, inspector);
+            }
+            self.inspection_draws.insert(escrow_id, &draw);
+            Ok(())
+        }
+
+        /// Lets the owner appoint a registered inspector when the reveal
+        /// window closed without both secrets, so a party cannot stall the
+        /// inspection by withholding theirs.
+        #[ink(message)]
+        pub fn appoint_inspector(&mut self, escrow_id: Hash, inspector: AccountId) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_caller_is_owner()?;
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let mut draw = self
+                .inspection_draws
+                .get(&escrow_id)
+                .ok_or(EscrowError::InspectionNotAgreed)?;
+            let deadline = self
+                .inspection_reveal_deadline(escrow_id)
+                .ok_or(EscrowError::InspectionNotAgreed)?;
+            if draw.inspector.is_some() {
+                return Err(EscrowError::AlreadyCommitted);
+            }
+            if self.now() <= deadline {
+                return Err(EscrowError::TimelockActive);
+            }
+            if !self.eligible_inspectors(&escrow).contains(&inspector) {
+                return Err(EscrowError::NoInspectorAvailable);
+            }
+            self.assign_inspector(escrow_id, &mut draw, inspector);
+            self.inspection_draws.insert(escrow_id, &draw);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn inspection_reveal_deadline(&self, escrow_id: Hash) -> Option<u64> {
+            self.inspection_reveal_deadlines.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn inspection_draw(&self, escrow_id: Hash) -> Option<InspectionDraw> {
+            self.inspection_draws.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn inspector_of(&self, escrow_id: Hash) -> Option<AccountId> {
+            self.inspection_draws
+                .get(&escrow_id)
+                .and_then(|draw| draw.inspector)
+        }
+
+        #[ink(message)]
+        pub fn approve_assignment(&mut self, escrow_id: Hash, assignee: AccountId) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::ApproveAssignment { assignee },
+            )?;
+            self.ensure_verified(&[assignee])?;
+
+            self.assignment_approvals.insert(escrow_id, &assignee);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(AssignmentApproved {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                assignee,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Records the caller's consent to move the escrow to `successor`.
+        /// Once the landlord and every renter with a stake in the escrow have
+        /// agreed on the same successor, the escrow state and its funds are
+        /// handed over through `RentalEscrow::import_escrow`. On an open
+        /// listing those renters are the co-renters who have already signed.
+        /// The lease agreement and any co-renter signatures travel with the
+        /// escrow; escrows with deposit schedules, partial or streamed rent,
+        /// a rent peg or subleases cannot be migrated yet.
+        #[ink(message)]
+        pub fn approve_migration(&mut self, escrow_id: Hash, successor: AccountId) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            let consenters = self.migration_consenters(escrow_id, &escrow);
+            let is_renter = consenters.contains(&caller);
+            if !is_renter && caller != escrow.landlord && !self.is_co_owner(escrow_id, &caller) {
+                return Err(EscrowError::NotParty);
+            }
+            if self.has_unexported_state(escrow_id) {
+                return Err(EscrowError::MigrationUnsupported);
+            }
+            if !is_renter {
+                let action = LandlordAction::Migrate { successor };
+                self.ensure_landlord_action(escrow_id, &escrow, &caller, action)?;
+            }
+
+            let (mut proposal, mut approvals) = match self.migrations.get(&escrow_id) {
+                Some(proposal) if proposal.successor == successor => {
+                    let approvals = self.migration_approvals.get(&escrow_id).unwrap_or_default();
+                    (proposal, approvals)
+                }
+                _ => (
+                    MigrationProposal {
+                        successor,
+                        renter_approved: false,
+                        landlord_approved: false,
+                    },
+                    Vec::new(),
+                ),
+            };
+            if is_renter {
+                if !approvals.contains(&caller) {
+                    approvals.push(caller);
+                }
+            } else {
+                proposal.landlord_approved = true;
+            }
+            // An open listing nobody has signed yet leaves the landlord alone
+            // to decide.
+            proposal.renter_approved = consenters.iter().all(|renter| approvals.contains(renter));
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(MigrationApproved {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                approved_by: caller,
+                successor,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            if !(proposal.renter_approved && proposal.landlord_approved) {
+                self.migrations.insert(escrow_id, &proposal);
+                self.migration_approvals.insert(escrow_id, &approvals);
+                return Ok(());
+            }
+
+            self.transition(&mut escrow, LeaseStatus::Migrated)?;
+            self.divest(escrow_id, &mut escrow)?;
+            let amount = Self::held_funds(&escrow);
+            let native_value = match escrow.payment_token {
+                None => amount,
+                Some(_) => 0,
+            };
+            let mut exported = escrow.clone();
+            exported.status = LeaseStatus::Active;
+            if exported.renter.is_none() {
+                exported.status = LeaseStatus::Open;
+            }
+            let agreement = self.agreements.get(&escrow_id);
+            let signatures = self.lease_signatures.take(&escrow_id).unwrap_or_default();
+            build_call::<Environment>()
+                .call(successor)
+                .transferred_value(native_value)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(rental_escrow::IMPORT_ESCROW_SELECTOR))
+                        .push_arg(escrow_id)
+                        .push_arg((exported, agreement, signatures).encode()),
+                )
+                .returns::<core::result::Result<(), ImportError>>()
+                .try_invoke()
+                .map_err(|_| EscrowError::MigrationFailed)?
+                .map_err(|_| EscrowError::MigrationFailed)?
+                .map_err(|_| EscrowError::MigrationFailed)?;
+            self.total_locked = self.total_locked.try_sub(native_value)?;
+            // Token funds follow once the successor has accepted the escrow.
+            if let Some(token) = escrow.payment_token {
+                self.transfer_token(token, successor, amount)?;
+            }
+
+            escrow.escrow_balance = 0;
+            escrow.deposit_balance = 0;
+            escrow.late_fees = 0;
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            self.migrations.take(&escrow_id);
+            self.migration_approvals.take(&escrow_id);
+            self.burn_lease_token(escrow_id);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(EscrowMigrated {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                successor,
+                amount,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn migration_proposal(&self, escrow_id: Hash) -> Option<MigrationProposal> {
+            self.migrations.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn claim_reimbursement(
+            &mut self,
+            escrow_id: Hash,
+            amount: Balance,
+            invoice_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if amount == 0 {
+                return Err(EscrowError::ZeroAmount);
+            }
+            if self.reimbursement_claims.contains(&escrow_id) {
+                return Err(EscrowError::ClaimPending);
+            }
+
+            self.reimbursement_claims.insert(
+                escrow_id,
+                &ReimbursementClaim {
+                    amount,
+                    invoice_hash,
+                    claimed_at: self.now(),
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ReimbursementClaimed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                claimed_by: caller,
+                amount,
+                invoice_hash,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Accepts the pending claim; its amount is credited against the
+        /// renter's next rent payments.
+        #[ink(message)]
+        pub fn approve_reimbursement(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            let claim = self
+                .reimbursement_claims
+                .take(&escrow_id)
+                .ok_or(EscrowError::NoPendingClaim)?;
+
+            escrow.rent_credit = escrow.rent_credit.try_add(claim.amount)?;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ReimbursementApproved {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                decided_by: caller,
+                amount: claim.amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Refuses the pending claim. If the escrow has an arbiter the refusal
+        /// opens a dispute with the invoice hash as its reason.
+        #[ink(message)]
+        pub fn reject_reimbursement(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            let claim = self
+                .reimbursement_claims
+                .take(&escrow_id)
+                .ok_or(EscrowError::NoPendingClaim)?;
+
+            let disputed = escrow.arbiter.is_some() && escrow.status == LeaseStatus::Active;
+            if disputed {
+                self.open_dispute(escrow_id, &mut escrow, caller, claim.invoice_hash)?;
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ReimbursementRejected {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                decided_by: caller,
+                amount: claim.amount,
+                disputed,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn reimbursement_claim(&self, escrow_id: Hash) -> Option<ReimbursementClaim> {
+            self.reimbursement_claims.get(&escrow_id)
+        }
+
+        /// Submits the actual CAM costs of the past year. Allowed once a year
+        /// while the lease runs, or any time after it has expired.
+        #[ink(message)]
+        pub fn submit_cam_statement(
+            &mut self,
+            escrow_id: Hash,
+            actual_amount: Balance,
+            statement_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            if escrow.cam_estimate == 0 {
+                return Err(EscrowError::NotCommercial);
+            }
+            let since = core::cmp::max(escrow.cam_reconciled_at, escrow.lease_start_time);
+            if self.now() < since.saturating_add(CAM_RECONCILIATION_PERIOD)
+                && self.ensure_lease_duration_passed(&escrow).is_err()
+            {
+                return Err(EscrowError::ReconciliationNotDue);
+            }
+
+            self.cam_statements.insert(
+                escrow_id,
+                &CamStatement {
+                    actual_amount,
+                    statement_hash,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(CamStatementSubmitted {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                actual_amount,
+                collected: escrow.cam_collected,
+                statement_hash,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Acknowledges the submitted CAM statement and applies the true-up:
+        /// an overpayment becomes rent credit, a shortfall is added to the
+        /// next payment.
+        #[ink(message)]
+        pub fn acknowledge_cam_statement(
+            &mut self,
+            escrow_id: Hash,
+            statement_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            let statement = self
+                .cam_statements
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoPendingStatement)?;
+            if statement.statement_hash != statement_hash {
+                return Err(EscrowError::StatementMismatch);
+            }
+            self.cam_statements.take(&escrow_id);
+
+            let (credit, charge) = if statement.actual_amount >= escrow.cam_collected {
+                (
+                    0,
+                    statement.actual_amount.saturating_sub(escrow.cam_collected),
+                )
+            } else {
+                (
+                    escrow.cam_collected.saturating_sub(statement.actual_amount),
+                    0,
+                )
+            };
+            escrow.rent_credit = escrow.rent_credit.try_add(credit)?;
+            escrow.cam_charge = escrow.cam_charge.try_add(charge)?;
+            escrow.cam_collected = 0;
+            escrow.cam_reconciled_at = self.now();
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(CamReconciled {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                statement_hash,
+                credit,
+                charge,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn cam_statement(&self, escrow_id: Hash) -> Option<CamStatement> {
+            self.cam_statements.get(&escrow_id)
+        }
+
+        /// Attests the renter's sales for a period. Unless the landlord
+        /// challenges it within the challenge window, the percentage rent on
+        /// sales above the breakpoint is added to the next payment.
+        #[ink(message)]
+        pub fn submit_sales_report(
+            &mut self,
+            escrow_id: Hash,
+            sales_amount: Balance,
+            attestation_hash: Hash,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if escrow.percentage_rent_bps == 0 {
+                return Err(EscrowError::NoPercentageRent);
+            }
+            if self.apply_sales_report(escrow_id, &mut escrow)? {
+                self.store_escrow(escrow_id, &escrow);
+            } else if self.sales_reports.contains(&escrow_id) {
+                return Err(EscrowError::ReportPending);
+            }
+
+            let submitted_at = self.now();
+            self.sales_reports.insert(
+                escrow_id,
+                &SalesReport {
+                    sales_amount,
+                    attestation_hash,
+                    submitted_at,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(SalesReported {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                sales_amount,
+                attestation_hash,
+                challenge_deadline: submitted_at.saturating_add(SALES_CHALLENGE_WINDOW),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Challenges a sales report inside its window. The report is dropped
+        /// and, if the escrow has an arbiter, a dispute is opened with the
+        /// attestation hash as its reason.
+        #[ink(message)]
+        pub fn challenge_sales_report(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            let report = self
+                .sales_reports
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoPendingReport)?;
+            if self.now() >= report.submitted_at.saturating_add(SALES_CHALLENGE_WINDOW) {
+                return Err(EscrowError::ChallengeWindowClosed);
+            }
+            self.sales_reports.take(&escrow_id);
+
+            if escrow.arbiter.is_some() && escrow.status == LeaseStatus::Active {
+                self.open_dispute(escrow_id, &mut escrow, caller, report.attestation_hash)?;
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(SalesReportChallenged {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                attestation_hash: report.attestation_hash,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn sales_report(&self, escrow_id: Hash) -> Option<SalesReport> {
+            self.sales_reports.get(&escrow_id)
+        }
+
+        /// Proposes a different rent and duration for an open single-renter
+        /// listing. The caller may replace their own offer, but not another
+        /// renter's while it is open.
+        #[ink(message)]
+        pub fn counter_offer(
+            &mut self,
+            escrow_id: Hash,
+            proposed_rent: Balance,
+            proposed_duration: u64,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.status != LeaseStatus::Open
+                || escrow.renter.is_some()
+                || !escrow.co_renters.is_empty()
+            {
+                return Err(EscrowError::NotOpen);
+            }
+            if proposed_duration < escrow.payment_interval {
+                return Err(EscrowError::InvalidPaymentInterval);
+            }
+            if matches!(self.pending_counter_offer(escrow_id), Some(offer) if offer.renter != caller)
+            {
+                return Err(EscrowError::CounterOfferPending);
+            }
+
+            let expires_at = self.now().saturating_add(COUNTER_OFFER_WINDOW);
+            self.counter_offers.insert(
+                escrow_id,
+                &CounterOffer {
+                    renter: caller,
+                    rent_amount: proposed_rent,
+                    lease_duration: proposed_duration,
+                    expires_at,
+                    accepted: false,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(CounterOffered {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                offered_by: caller,
+                rent_amount: proposed_rent,
+                lease_duration: proposed_duration,
+                expires_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Takes the open counter-offer into the listing's terms and holds
+        /// the listing for its renter for another `COUNTER_OFFER_WINDOW`.
+        #[ink(message)]
+        pub fn accept_counter(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::AcceptCounter,
+            )?;
+            if escrow.status != LeaseStatus::Open {
+                return Err(EscrowError::NotOpen);
+            }
+            let mut offer = self
+                .pending_counter_offer(escrow_id)
+                .filter(|offer| !offer.accepted)
+                .ok_or(EscrowError::NoCounterOffer)?;
+
+            escrow.rent_amount = offer.rent_amount;
+            escrow.lease_duration = offer.lease_duration;
+            self.store_escrow(escrow_id, &escrow);
+            offer.accepted = true;
+            offer.expires_at = self.now().saturating_add(COUNTER_OFFER_WINDOW);
+            self.counter_offers.insert(escrow_id, &offer);
+            self.emit_counter_decision(escrow_id, offer.renter, true);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn reject_counter(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_landlord_action(
+                escrow_id,
+                &escrow,
+                &caller,
+                LandlordAction::RejectCounter,
+            )?;
+            let offer = self
+                .pending_counter_offer(escrow_id)
+                .filter(|offer| !offer.accepted)
+                .ok_or(EscrowError::NoCounterOffer)?;
+
+            self.counter_offers.take(&escrow_id);
+            self.emit_counter_decision(escrow_id, offer.renter, false);
+            Ok(())
+        }
+
+        /// The listing's counter-offer, pending or accepted, until it expires.
+        #[ink(message)]
+        pub fn pending_counter_offer(&self, escrow_id: Hash) -> Option<CounterOffer> {
+            self.counter_offers
+                .get(&escrow_id)
+                .filter(|offer| self.now() <= offer.expires_at)
+        }
+
+        /// Offers the renter an extension by `new_duration` at a new rent.
+        /// Only allowed once the lease ends within the renewal window; a
+        /// new proposal replaces the previous one.
+        #[ink(message)]
+        pub fn propose_renewal(
+            &mut self,
+            escrow_id: Hash,
+            new_duration: u64,
+            new_rent_amount: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_can_manage(escrow_id, &escrow, &caller)?;
+            if new_duration == 0 || new_duration % escrow.payment_interval != 0 {
+                return Err(EscrowError::InvalidPaymentInterval);
+            }
+            let now = self.now();
+            if now.saturating_add(self.renewal_window)
+                < escrow
+                    .lease_start_time
+                    .saturating_add(escrow.lease_duration)
+            {
+                return Err(EscrowError::RenewalNotDue);
+            }
+
+            let expires_at = now.saturating_add(self.renewal_window);
+            self.renewal_proposals.insert(
+                escrow_id,
+                &RenewalProposal {
+                    new_duration,
+                    new_rent_amount,
+                    expires_at,
+                },
+            );
+            self.log_activity(
+                escrow_id,
+                Activity::RenewalProposed {
+                    new_duration,
+                    new_rent_amount,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(RenewalProposed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                new_duration,
+                new_rent_amount,
+                expires_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Accepts the pending renewal, extending the lease and switching to
+        /// the new rent in one step.
+        #[ink(message)]
+        pub fn accept_renewal(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_not_disputed(&escrow)?;
+            self.ensure_escrow_leased(&escrow)?;
+            self.ensure_caller_is_renter(&escrow, &caller)?;
+            if self.partial_payments.contains(&escrow_id)
+                && self.outstanding_balance(escrow_id)? > 0
+            {
+                return Err(EscrowError::RentInArrears);
+            }
+            if let Some(status) = self.insurance.get(&escrow_id) {
+                if status.requirement.block_renewal && !self.policy_valid(&status) {
+                    return Err(EscrowError::InsuranceLapsed);
+                }
+            }
+            let proposal = self
+                .renewal_proposal(escrow_id)
+                .ok_or(EscrowError::NoPendingRenewal)?;
+            self.renewal_proposals.take(&escrow_id);
+
+            escrow.lease_duration = escrow.lease_duration.try_add(proposal.new_duration)?;
+            escrow.rent_amount = proposal.new_rent_amount;
+            let lease_duration = escrow.lease_duration;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseRenewed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                lease_duration,
+                rent_amount: proposal.new_rent_amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Returns the pending renewal proposal, or `None` once it expired.
+        #[ink(message)]
+        pub fn renewal_proposal(&self, escrow_id: Hash) -> Option<RenewalProposal> {
+            self.renewal_proposals
+                .get(&escrow_id)
+                .filter(|proposal| self.now() <= proposal.expires_at)
+        }
+
+        /// Records the renter's current policy. Only the insurer named in the
+        /// terms may attest; a new attestation replaces the previous one.
+        #[ink(message)]
+        pub fn attest_insurance(
+            &mut self,
+            escrow_id: Hash,
+            policy_hash: Hash,
+            expires_at: u64,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut status = self
+                .insurance
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoInsuranceRequirement)?;
+            if status.requirement.insurer != caller {
+                return Err(EscrowError::NotInsurer);
+            }
+            if expires_at <= self.now() {
+                return Err(EscrowError::InvalidPolicyExpiry);
+            }
+
+            status.policy_hash = policy_hash;
+            status.expires_at = expires_at;
+            status.lapse_reported = false;
+            self.insurance.insert(escrow_id, &status);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InsuranceAttested {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                insurer: caller,
+                policy_hash,
+                expires_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Flags an active lease whose policy has expired, or was never
+        /// attested, as non-compliant. Anyone may report a lapse, once per
+        /// attestation.
+        #[ink(message)]
+        pub fn report_insurance_lapse(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            let mut status = self
+                .insurance
+                .get(&escrow_id)
+                .ok_or(EscrowError::NoInsuranceRequirement)?;
+            if status.lapse_reported || self.policy_valid(&status) {
+                return Err(EscrowError::InsuranceNotLapsed);
+            }
+
+            status.lapse_reported = true;
+            self.insurance.insert(escrow_id, &status);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InsuranceLapsed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                expired_at: status.expires_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Whether the escrow meets its insurance requirement, if it has one.
+        #[ink(message)]
+        pub fn insurance_compliant(&self, escrow_id: Hash) -> bool {
+            self.insurance
+                .get(&escrow_id)
+                .map_or(true, |status| self.policy_valid(&status))
+        }
+
+        #[ink(message)]
+        pub fn insurance_status(&self, escrow_id: Hash) -> Option<InsuranceStatus> {
+            self.insurance.get(&escrow_id)
+        }
+
+        /// Active leases `account` held in `building` at the end of `block`,
+        /// for tenant association votes. Only completed blocks can be looked
+        /// up, so a weight cannot be raised for a vote already under way.
+        #[ink(message)]
+        pub fn tenancy_weight_at(
+            &self,
+            building: Hash,
+            account: AccountId,
+            block: u32,
+        ) -> Result<u32> {
+            self.weight_at(self.tenancy_checkpoints.get(&(building, account)), block)
+        }
+
+        /// All active leases in `building` at the end of `block`.
+        #[ink(message)]
+        pub fn total_tenancy_at(&self, building: Hash, block: u32) -> Result<u32> {
+            self.weight_at(self.building_checkpoints.get(&building), block)
+        }
+
+        /// Replaces the agreement hash once the landlord and the renter have
+        /// both called this with the same `new_hash`. The first call records
+        /// the proposal; a listing without a renter is amended directly.
+        #[ink(message)]
+        pub fn amend_agreement(&mut self, escrow_id: Hash, new_hash: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let counterparty = match escrow.renter {
+                Some(renter) if caller == escrow.landlord => Some(renter),
+                Some(renter) if caller == renter => Some(escrow.landlord),
+                None if caller == escrow.landlord => None,
+                _ => return Err(EscrowError::NotParty),
+            };
+            if caller == escrow.landlord {
+                let action = LandlordAction::AmendAgreement { new_hash };
+                self.ensure_landlord_action(escrow_id, &escrow, &caller, action)?;
+            }
+            let confirmed = match (counterparty, self.agreement_amendments.get(&escrow_id)) {
+                (None, _) => true,
+                (Some(counterparty), Some(pending)) => {
+                    pending.new_hash == new_hash && pending.proposed_by == counterparty
+                }
+                (Some(_), None) => false,
+            };
+            if !confirmed {
+                self.agreement_amendments.insert(
+                    escrow_id,
+                    &AgreementAmendment {
+                        new_hash,
+                        proposed_by: caller,
+                    },
+                );
+                let header = self.event_header(escrow_id);
+                self.env().emit_event(AgreementAmendmentProposed {
+                    escrow_id,
+                    landlord: header.landlord,
+                    renter: header.renter,
+                    sequence: header.sequence,
+                    new_hash,
+                    proposed_by: caller,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+                return Ok(());
+            }
+
+            let mut agreement = self.agreement(escrow_id).unwrap_or(Agreement {
+                agreement_hash: Hash::default(),
+                metadata_uri: Vec::new(),
+            });
+            let previous_hash = agreement.agreement_hash;
+            agreement.agreement_hash = new_hash;
+            self.store_agreement(escrow_id, agreement);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(AgreementAmended {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                previous_hash,
+                new_hash,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn agreement(&self, escrow_id: Hash) -> Option<Agreement> {
+            self.agreements.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn pending_amendment(&self, escrow_id: Hash) -> Option<AgreementAmendment> {
+            self.agreement_amendments.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn raise_dispute(&mut self, escrow_id: Hash, reason_hash: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.renter != Some(caller) && caller != escrow.landlord {
+                return Err(EscrowError::NotParty);
+            }
+            if escrow.arbiter.is_none() {
+                return Err(EscrowError::NoArbiter);
+            }
+            self.ensure_not_disputed(&escrow)?;
+            self.open_dispute(escrow_id, &mut escrow, caller, reason_hash)
+        }
+
+        /// Hands a dispute over to the next registered arbiter after the
+        /// current one let its response deadline pass. The dispute reason
+        /// stays on the escrow.
+        #[ink(message)]
+        pub fn request_arbiter_reassignment(&mut self, escrow_id: Hash) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.renter != Some(caller) && caller != escrow.landlord {
+                return Err(EscrowError::NotParty);
+            }
+            if escrow.status != LeaseStatus::Disputed {
+                return Err(EscrowError::NotDisputed);
+            }
+            let now = self.now();
+            if now <= escrow.dispute_deadline {
+                return Err(EscrowError::ArbiterStillActive);
+            }
+
+            let arbiter = self.pick_arbiter(&escrow)?;
+            let previous = escrow.arbiter;
+            escrow.arbiter = Some(arbiter);
+            escrow.dispute_deadline = now.saturating_add(self.arbiter_response_window);
+            let deadline = escrow.dispute_deadline;
+            self.store_escrow(escrow_id, &escrow);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ArbiterReassigned {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                previous,
+                arbiter,
+                deadline,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let previous_owner = self.owner;
+            self.owner = new_owner;
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Halts all fund movements until `unpause`. Reads keep working.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                by: self.env().caller(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                by: self.env().caller(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Replaces the contract code while keeping its storage and funds.
+        /// Call `migrate` afterwards if the new code bumps `STORAGE_VERSION`.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| EscrowError::UpgradeFailed)?;
+            self.env().emit_event(CodeUpgraded {
+                code_hash,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Brings stored data up to `STORAGE_VERSION` after an upgrade.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Err(EscrowError::NothingToMigrate);
+            }
+            // A layout change adds a step here that rewrites the entries
+            // stored by older versions.
+            if from_version < 2 {
+                let legacy_arbiters = core::mem::take(&mut self.legacy_arbiter_registry);
+                self.arbiter_registry.set(&legacy_arbiters);
+                let mut count = self.escrow_count.get_or_default();
+                for escrow_id in core::mem::take(&mut self.legacy_escrow_index) {
+                    self.escrow_index.insert(count, &escrow_id);
+                    count = count.try_add(1)?;
+                    if let Some(escrow) = self.escrows.get(&escrow_id) {
+                        self.track_escrow(&escrow, true);
+                    }
+                }
+                self.escrow_count.set(&count);
+            }
+            self.storage_version = STORAGE_VERSION;
+            self.env().emit_event(StorageMigrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        /// Starts winding the deployment down. From now on no escrow is
+        /// created and no lease started; once `DECOMMISSION_TIMELOCK` has
+        /// passed the owner may `force_refund` what is left and
+        /// `decommission` the contract.
+        #[ink(message)]
+        pub fn schedule_decommission(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            if self.decommissioned.get_or_default() {
+                return Err(EscrowError::Decommissioned);
+            }
+            let executable_at = self.now().saturating_add(DECOMMISSION_TIMELOCK);
+            self.decommission_at.set(&Some(executable_at));
+            self.env().emit_event(DecommissionScheduled {
+                executable_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Calls off a scheduled decommission and takes new leases again.
+        #[ink(message)]
+        pub fn cancel_decommission(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            if self.decommissioned.get_or_default() {
+                return Err(EscrowError::Decommissioned);
+            }
+            if self.decommission_at.get_or_default().is_none() {
+                return Err(EscrowError::DecommissionNotScheduled);
+            }
+            self.decommission_at.set(&None);
+            self.env().emit_event(DecommissionCancelled {
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Turns escrows that still run a lease or hold funds into credits
+        /// their parties withdraw with `withdraw_credit`, once the
+        /// decommission timelock has passed. The landlord is credited the
+        /// rent of matured, unclaimed periods and the late fees, less the
+        /// platform fee as on settlement. Co-renters who signed an open
+        /// listing get their deposits back and the renters everything else.
+        /// Disputed escrows stay with their arbiter and are skipped. Returns
+        /// how many escrows were converted.
+        #[ink(message)]
+        pub fn force_refund(&mut self, escrow_ids: Vec<Hash>) -> Result<u32> {
+            self.ensure_caller_is_owner()?;
+            Self::ensure_batch_size(escrow_ids.len())?;
+            self.ensure_decommission_unlocked()?;
+            let mut converted = 0;
+            for escrow_id in escrow_ids {
+                let mut escrow = match self.escrows.get(&escrow_id) {
+                    Some(escrow) => escrow,
+                    None => continue,
+                };
+                let convertible = match escrow.status {
+                    LeaseStatus::Active => true,
+                    LeaseStatus::Open | LeaseStatus::Terminated => Self::held_funds(&escrow) > 0,
+                    _ => false,
+                };
+                if !convertible {
+                    continue;
+                }
+                self.divest(escrow_id, &mut escrow)?;
+
+                let unclaimed = self
+                    .matured_periods(&escrow)
+                    .saturating_sub(escrow.periods_claimed);
+                let earned = core::cmp::min(
+                    escrow.escrow_balance,
+                    escrow.rent_amount.saturating_mul(Balance::from(unclaimed)),
+                );
+                let landlord_amount = earned.try_add(escrow.late_fees)?;
+                let renter_credit = Self::held_funds(&escrow).try_sub(landlord_amount)?;
+                let token = escrow.payment_token;
+                let landlord_credit = self.accrue_fee(escrow_id, &escrow, landlord_amount)?;
+                self.credit(token, escrow.landlord, landlord_credit)?;
+                let mut remaining = renter_credit;
+                for (co_renter, deposit) in
+                    self.lease_signatures.take(&escrow_id).unwrap_or_default()
+                {
+                    self.credit(token, co_renter, deposit)?;
+                    remaining = remaining.saturating_sub(deposit);
+                }
+                if escrow.status == LeaseStatus::Active {
+                    let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+                    for (to, part) in self.renter_shares(&escrow, renter, remaining) {
+                        self.credit(token, to, part)?;
+                    }
+                } else if let Some(renter) = escrow.renter {
+                    // Anything else held before the lease started was paid by
+                    // the renter.
+                    self.credit(token, renter, remaining)?;
+                }
+
+                if escrow.status != LeaseStatus::Terminated {
+                    self.transition(&mut escrow, LeaseStatus::Terminated)?;
+                }
+                escrow.escrow_balance = 0;
+                escrow.deposit_balance = 0;
+                escrow.late_fees = 0;
+                escrow.overpayment = 0;
+                self.store_escrow(escrow_id, &escrow);
+                self.pending_settlements.take(&escrow_id);
+                self.burn_lease_token(escrow_id);
+                let header = self.event_header(escrow_id);
+                self.env().emit_event(EscrowForceRefunded {
+                    escrow_id,
+                    landlord: header.landlord,
+                    renter: header.renter,
+                    sequence: header.sequence,
+                    landlord_credit,
+                    renter_credit,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+                converted = converted.saturating_add(1);
+            }
+            Ok(converted)
+        }
+
+        /// Permanently stops the contract taking new escrows or leases, once
+        /// the timelock has passed and no escrow runs a lease or holds funds.
+        /// Vaults, credits, failed payouts and pending withdrawals can still
+        /// be withdrawn afterwards.
+        #[ink(message)]
+        pub fn decommission(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.ensure_decommission_unlocked()?;
+            if self.live_escrows.get_or_default() > 0 {
+                return Err(EscrowError::LeasesRemaining);
+            }
+
+            self.decommission_at.set(&None);
+            self.decommissioned.set(&true);
+            self.env().emit_event(ContractDecommissioned {
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Withdraws the caller's credit in `token` from force-refunded
+        /// escrows.
+        #[ink(message)]
+        pub fn withdraw_credit(&mut self, token: Option<AccountId>) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let amount = self
+                .credits
+                .take(&(caller, token))
+                .ok_or(EscrowError::NoCredit)?;
+            let total = self.credit_totals.get(&token).unwrap_or(0);
+            self.credit_totals
+                .insert(token, &total.saturating_sub(amount));
+            self.pay_out(token, caller, amount)?;
+            self.env().emit_event(CreditWithdrawn {
+                account: caller,
+                token,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn credit_of(&self, account: AccountId, token: Option<AccountId>) -> Balance {
+            self.credits.get(&(account, token)).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn decommission_at(&self) -> Option<u64> {
+            self.decommission_at.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn decommissioned(&self) -> bool {
+            self.decommissioned.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
+
+        #[ink(message)]
+        pub fn add_arbiter(&mut self, arbiter: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let mut registry = self.arbiter_registry.get_or_default();
+            if !registry.contains(&arbiter) {
+                registry.push(arbiter);
+                self.arbiter_registry.set(&registry);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_arbiter(&mut self, arbiter: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let mut registry = self.arbiter_registry.get_or_default();
+            registry.retain(|registered| *registered != arbiter);
+            self.arbiter_registry.set(&registry);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_inspector(&mut self, inspector: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let mut registry = self.inspector_registry.get_or_default();
+            if !registry.contains(&inspector) {
+                registry.push(inspector);
+                self.inspector_registry.set(&registry);
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_inspector(&mut self, inspector: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let mut registry = self.inspector_registry.get_or_default();
+            registry.retain(|registered| *registered != inspector);
+            self.inspector_registry.set(&registry);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn inspectors(&self) -> Vec<AccountId> {
+            self.inspector_registry.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn set_arbiter_response_window(&mut self, window: u64) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.arbiter_response_window = window;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_release_threshold(&mut self, threshold: Option<Balance>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.release_threshold = threshold;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_verified(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.verified.insert(account, &());
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_verified(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.verified.remove(account);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn is_verified(&self, account: AccountId) -> bool {
+            self.verified.contains(account)
+        }
+
+        /// When set, `create_escrow`, `accept_lease` and `rent` require
+        /// both parties to be verified.
+        #[ink(message)]
+        pub fn set_require_kyc(&mut self, required: bool) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.require_kyc.set(&required);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn require_kyc(&self) -> bool {
+            self.require_kyc.get_or_default()
+        }
+
+        /// Sets the canary limits. Tighter limits, or starting canary mode,
+        /// take effect at once; looser ones, or `None` to end canary mode,
+        /// are scheduled behind `CANARY_TIMELOCK` and applied with
+        /// `apply_canary_limits`.
+        #[ink(message)]
+        pub fn set_canary_limits(&mut self, limits: Option<CanaryLimits>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let tightens = match (self.canary.get_or_default(), limits) {
+                (_, None) => self.canary.get_or_default().is_none(),
+                (None, Some(_)) => true,
+                (Some(current), Some(new)) => {
+                    new.max_total_locked <= current.max_total_locked
+                        && new.max_escrow_balance <= current.max_escrow_balance
+                }
+            };
+            if tightens {
+                self.canary.set(&limits);
+                self.pending_canary.set(&None);
+                self.env().emit_event(CanaryLimitsChanged {
+                    limits,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+                return Ok(());
+            }
+
+            let executable_at = self.now().saturating_add(CANARY_TIMELOCK);
+            self.pending_canary.set(&Some(CanaryChange {
+                limits,
+                executable_at,
+            }));
+            self.env().emit_event(CanaryLimitsScheduled {
+                limits,
+                executable_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn apply_canary_limits(&mut self) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let change = self
+                .pending_canary
+                .get_or_default()
+                .ok_or(EscrowError::NoPendingCanaryChange)?;
+            if self.now() < change.executable_at {
+                return Err(EscrowError::TimelockActive);
+            }
+            self.canary.set(&change.limits);
+            self.pending_canary.set(&None);
+            self.env().emit_event(CanaryLimitsChanged {
+                limits: change.limits,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn canary_limits(&self) -> Option<CanaryLimits> {
+            self.canary.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn pending_canary_limits(&self) -> Option<CanaryChange> {
+            self.pending_canary.get_or_default()
+        }
+
+        /// Caps the funds a single escrow may hold. Payments that would take
+        /// an escrow above the cap are rejected; `None` lifts it.
+        #[ink(message)]
+        pub fn set_max_escrow_balance(&mut self, cap: Option<Balance>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.max_escrow_balance.set(&cap);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn max_escrow_balance(&self) -> Option<Balance> {
+            self.max_escrow_balance.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn set_withdrawal_threshold(&mut self, threshold: Option<Balance>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.withdrawal_threshold.set(&threshold);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_withdrawal_delay(&mut self, delay: u64) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.withdrawal_delay.set(&delay);
+            Ok(())
+        }
+
+        /// Sets the contract deposits are invested in. It can only change
+        /// while nothing is invested.
+        #[ink(message)]
+        pub fn set_yield_strategy(&mut self, strategy: Option<AccountId>) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            if self.total_yield_shares.get_or_default() > 0 {
+                return Err(EscrowError::StrategyInUse);
+            }
+            self.yield_strategy.set(&strategy);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_settlement_timeout(&mut self, timeout: u64) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.settlement_timeout = timeout;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_renewal_window(&mut self, window: u64) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.renewal_window = window;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_oracle_max_age(&mut self, max_age: u64) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.oracle_max_age.set(&max_age);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn rent_peg(&self, escrow_id: Hash) -> Option<RentPeg> {
+            self.rent_pegs.get(&escrow_id)
+        }
+
+        #[ink(message)]
+        pub fn set_fee(&mut self, fee_bps: u16) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            if fee_bps > MAX_BPS {
+                return Err(EscrowError::InvalidFee);
+            }
+            self.fee_bps = fee_bps;
+            Ok(())
+        }
+
+        /// Sets the fee charged to `landlord` instead of the default, or
+        /// removes the override for `None`.
+        #[ink(message)]
+        pub fn set_fee_override(
+            &mut self,
+            landlord: AccountId,
+            fee_bps: Option<u16>,
+        ) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            match fee_bps {
+                Some(fee_bps) if fee_bps > MAX_BPS => return Err(EscrowError::InvalidFee),
+                Some(fee_bps) => {
+                    self.fee_overrides.insert(landlord, &fee_bps);
+                }
+                None => self.fee_overrides.remove(landlord),
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn fee_override(&self, landlord: AccountId) -> Option<u16> {
+            self.fee_overrides.get(&landlord)
+        }
+
+        /// The fee taken from the escrow's landlord payouts, after any
+        /// override for that landlord.
+        #[ink(message)]
+        pub fn effective_fee(&self, escrow_id: Hash) -> Option<u16> {
+            let escrow = self.escrows.get(&escrow_id)?;
+            Some(self.fee_bps_for(&escrow.landlord))
+        }
+
+        #[ink(message)]
+        pub fn set_fee_collector(&mut self, collector: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.fee_collector = collector;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_rounding_policy(&mut self, policy: RoundingPolicy) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.rounding_policy = policy;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn rounding_policy(&self) -> RoundingPolicy {
+            self.rounding_policy
+        }
+
+        #[ink(message)]
+        pub fn collected_fees(&self, token: Option<AccountId>) -> Balance {
+            self.collected_fees.get(&token).unwrap_or(0)
+        }
+
+        /// Sends the fees accrued in `token` to the fee collector. Fees are
+        /// kept per currency, so each one is withdrawn separately.
+        #[ink(message)]
+        pub fn withdraw_fees(&mut self, token: Option<AccountId>) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            if caller != self.fee_collector {
+                return Err(EscrowError::NotFeeCollector);
+            }
+            let amount = self.collected_fees(token);
+            if amount == 0 {
+                return Err(EscrowError::NothingToClaim);
+            }
+            self.collected_fees.remove(token);
+            self.pay_out(token, caller, amount)?;
+            self.env().emit_event(FeesWithdrawn {
+                collector: caller,
+                token,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Schedules the return of PSP22 tokens sent to the contract by
+        /// mistake. Only the balance above what escrows, fees and failed
+        /// payouts account for can be recovered.
+        #[ink(message)]
+        pub fn schedule_token_recovery(
+            &mut self,
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            self.ensure_untracked(token, amount)?;
+
+            let executable_at = self.now().saturating_add(TOKEN_RECOVERY_TIMELOCK);
+            self.token_recoveries.insert(
+                token,
+                &TokenRecovery {
+                    to,
+                    amount,
+                    executable_at,
+                },
+            );
+            self.env().emit_event(TokenRecoveryScheduled {
+                token,
+                to,
+                amount,
+                executable_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Executes a scheduled recovery once its timelock has passed. The
+        /// accounting check is repeated, since escrows may have taken in
+        /// tokens in the meantime.
+        #[ink(message)]
+        pub fn recover_token(
+            &mut self,
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let recovery = self
+                .token_recoveries
+                .get(&token)
+                .filter(|recovery| recovery.to == to && recovery.amount == amount)
+                .ok_or(EscrowError::RecoveryNotScheduled)?;
+            if self.now() < recovery.executable_at {
+                return Err(EscrowError::TimelockActive);
+            }
+            self.ensure_untracked(token, amount)?;
+
+            self.token_recoveries.take(&token);
+            self.transfer_token(token, to, amount)?;
+            self.env().emit_event(TokenRecovered {
+                token,
+                to,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn arbiters(&self) -> Vec<AccountId> {
+            self.arbiter_registry.get_or_default()
+        }
+
+        #[ink(message)]
+        pub fn resolve_dispute(
+            &mut self,
+            escrow_id: Hash,
+            renter_share: Balance,
+            landlord_share: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            if escrow.arbiter != Some(caller) {
+                return Err(EscrowError::NotArbiter);
+            }
+            if escrow.status != LeaseStatus::Disputed {
+                return Err(EscrowError::NotDisputed);
+            }
+            self.divest(escrow_id, &mut escrow)?;
+            let held = Self::held_funds(&escrow);
+            if renter_share.try_add(landlord_share)? != held {
+                return Err(EscrowError::InvalidShares);
+            }
+
+            if renter_share > 0 {
+                let renter = escrow.renter.ok_or(EscrowError::InvalidShares)?;
+                self.refund_renters(&escrow, renter, renter_share)?;
+            }
+            if landlord_share > 0 {
+                self.pay_out(escrow.payment_token, escrow.landlord, landlord_share)?;
+            }
+
+            self.transition(&mut escrow, LeaseStatus::Settled)?;
+            escrow.escrow_balance = 0;
+            escrow.deposit_balance = 0;
+            escrow.late_fees = 0;
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            self.burn_lease_token(escrow_id);
+            self.end_subleases(escrow_id)?;
+            self.active_disputes = self.active_disputes.saturating_sub(1);
+            let loser = match renter_share.cmp(&landlord_share) {
+                core::cmp::Ordering::Less => escrow.renter,
+                core::cmp::Ordering::Greater => Some(escrow.landlord),
+                core::cmp::Ordering::Equal => None,
+            };
+            if let Some(loser) = loser {
+                self.update_reputation(loser, |reputation| {
+                    reputation.disputes_lost = reputation.disputes_lost.saturating_add(1);
+                });
+            }
+            self.log_activity(
+                escrow_id,
+                Activity::DisputeResolved {
+                    renter_share,
+                    landlord_share,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DisputeResolved {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                arbiter: caller,
+                renter_share,
+                landlord_share,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn status(&self, escrow_id: Hash) -> Result<LeaseStatus> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            Ok(self.current_status(&escrow))
+        }
+
+        #[ink(message)]
+        pub fn get_escrow(&self, escrow_id: Hash) -> Option<EscrowView> {
+            let escrow = self.escrows.get(&escrow_id)?;
+            Some(EscrowView {
+                escrow_id,
+                renter: escrow.renter,
+                landlord: escrow.landlord,
+                status: self.current_status(&escrow),
+                rent_amount: escrow.rent_amount,
+                lease_duration: escrow.lease_duration,
+                lease_start_time: escrow.lease_start_time,
+                escrow_balance: escrow.escrow_balance,
+                security_deposit: escrow.security_deposit,
+                deposit_balance: escrow.deposit_balance,
+                payment_interval: escrow.payment_interval,
+                periods_paid: escrow.periods_paid,
+                periods_claimed: escrow.periods_claimed,
+                next_due_timestamp: escrow.next_due_timestamp,
+                grace_period: escrow.grace_period,
+                late_fee_bps: escrow.late_fee_bps,
+                late_fees: escrow.late_fees,
+                arbiter: escrow.arbiter,
+                dispute_reason: escrow.dispute_reason,
+                dispute_deadline: escrow.dispute_deadline,
+                payment_token: escrow.payment_token,
+                rent_credit: escrow.rent_credit,
+                early_termination_penalty_periods: escrow.early_termination_penalty_periods,
+                cam_estimate: escrow.cam_estimate,
+                cam_collected: escrow.cam_collected,
+                cam_charge: escrow.cam_charge,
+                percentage_rent_bps: escrow.percentage_rent_bps,
+                sales_breakpoint: escrow.sales_breakpoint,
+                percentage_rent_due: escrow.percentage_rent_due,
+                overpayment: escrow.overpayment,
+                co_renters: escrow.co_renters.clone(),
+            })
+        }
+
+        /// Canonical bytes of the escrow's parties and agreed terms, for
+        /// off-chain systems and other chains to hash and compare. See
+        /// `canonical_encoding` for the layout.
+        #[ink(message)]
+        pub fn encoded_escrow(&self, escrow_id: Hash) -> Result<Vec<u8>> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            Ok(self.canonical_encoding(escrow_id, &escrow))
+        }
+
+        #[ink(message)]
+        pub fn list_escrows_by_landlord(&self, landlord: AccountId) -> Vec<Hash> {
+            self.escrows_by_landlord.get(&landlord).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn list_escrows_by_renter(&self, renter: AccountId) -> Vec<Hash> {
+            self.escrows_by_renter.get(&renter).unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn escrow_count(&self) -> u32 {
+            self.escrow_count.get_or_default()
+        }
+
+        /// Returns up to `limit` escrow ids starting at `cursor`, in the order
+        /// the escrows were created.
+        #[ink(message)]
+        pub fn escrow_ids(&self, cursor: u32, limit: u32) -> Page<Hash> {
+            Paginator::new(cursor, limit)
+                .page_indexed(self.escrow_count.get_or_default(), |index| {
+                    self.escrow_index.get(index)
+                })
+        }
+
+        #[ink(message)]
+        pub fn reputation_of(&self, account: AccountId) -> ReputationView {
+            let reputation = self.reputations.get(&account).unwrap_or_default();
+            let payments = u64::from(reputation.on_time_payments)
+                .saturating_add(u64::from(reputation.late_payments));
+            let on_time_bps = (payments > 0).then(|| {
+                (u64::from(reputation.on_time_payments).saturating_mul(u64::from(MAX_BPS))
+                    / payments) as u16
+            });
+            ReputationView {
+                account,
+                leases_completed: reputation.leases_completed,
+                on_time_payments: reputation.on_time_payments,
+                late_payments: reputation.late_payments,
+                disputes_lost: reputation.disputes_lost,
+                on_time_bps,
+            }
+        }
+
+        /// Number of events emitted for the escrow. Each event's `sequence`
+        /// is its position in this count, so a re-indexer can check it saw
+        /// every one of them.
+        #[ink(message)]
+        pub fn events_emitted(&self, escrow_id: Hash) -> u32 {
+            self.event_counts.get(&escrow_id).unwrap_or(0)
+        }
+
+        /// Number of payments recorded for the escrow, rent and deposit alike.
+        #[ink(message)]
+        pub fn payment_count(&self, escrow_id: Hash) -> u32 {
+            self.payment_counts.get(&escrow_id).unwrap_or(0)
+        }
+
+        /// The escrow's payment at `index`, counted from its first payment.
+        #[ink(message)]
+        pub fn payment_at(&self, escrow_id: Hash, index: u32) -> Option<PaymentRecord> {
+            self.payment_records.get(&(escrow_id, index))
+        }
+
+        /// Returns up to `limit` entries of the escrow's activity starting at
+        /// `cursor`, oldest first: payments, renewal proposals, inspector
+        /// assignments and disputes in one timeline.
+        #[ink(message)]
+        pub fn activity(&self, escrow_id: Hash, cursor: u32, limit: u32) -> Page<ActivityEntry> {
+            let entries = self.activity_log.get(&escrow_id).unwrap_or_default();
+            Paginator::new(cursor, limit).page(&entries)
+        }
+
+        /// Number of escrows paid in `token`, or in the native currency for
+        /// `None`.
+        #[ink(message)]
+        pub fn escrow_count_by_token(&self, token: Option<AccountId>) -> u32 {
+            self.escrows_by_token
+                .get(&token)
+                .map_or(0, |ids| ids.len() as u32)
+        }
+
+        #[ink(message)]
+        pub fn escrow_ids_by_token(
+            &self,
+            token: Option<AccountId>,
+            cursor: u32,
+            limit: u32,
+        ) -> Page<Hash> {
+            let ids = self.escrows_by_token.get(&token).unwrap_or_default();
+            Paginator::new(cursor, limit).page(&ids)
+        }
+
+        #[ink(message)]
+        pub fn failed_payouts(
+            &self,
+            account: AccountId,
+            cursor: u32,
+            limit: u32,
+        ) -> Page<FailedPayout> {
+            let ids = self
+                .failed_payouts_by_account
+                .get(&account)
+                .unwrap_or_default();
+            let page = Paginator::new(cursor, limit).page(&ids);
+            Page {
+                items: page
+                    .items
+                    .iter()
+                    .filter_map(|id| self.failed_payouts.get(id))
+                    .collect(),
+                next: page.next,
+            }
+        }
+
+        /// Attempts a failed payout again. Anyone may call it; the funds only
+        /// ever go to the original recipient.
+        #[ink(message)]
+        pub fn retry_payout(&mut self, id: u32) -> Result<()> {
+            self.ensure_not_paused()?;
+            let payout = self
+                .failed_payouts
+                .get(&id)
+                .ok_or(EscrowError::PayoutNotFound)?;
+            self.transfer_token(payout.token, payout.to, payout.amount)?;
+
+            self.failed_payouts.take(&id);
+            let queued = self.queued_payout_totals.get(&payout.token).unwrap_or(0);
+            self.queued_payout_totals
+                .insert(payout.token, &queued.saturating_sub(payout.amount));
+            if let Some(mut ids) = self.failed_payouts_by_account.get(&payout.to) {
+                ids.retain(|queued| *queued != id);
+                self.failed_payouts_by_account.insert(payout.to, &ids);
+            }
+            self.env().emit_event(PayoutRetried {
+                id,
+                to: payout.to,
+                amount: payout.amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Pays a pending withdrawal to its recipient once it has unlocked.
+        #[ink(message)]
+        pub fn execute_withdrawal(&mut self, id: u32) -> Result<()> {
+            self.ensure_not_paused()?;
+            let caller = self.env().caller();
+            let withdrawal = self
+                .pending_withdrawals
+                .get(&id)
+                .ok_or(EscrowError::WithdrawalNotFound)?;
+            if withdrawal.to != caller {
+                return Err(EscrowError::NotRecipient);
+            }
+            if withdrawal.vetoed {
+                return Err(EscrowError::WithdrawalVetoed);
+            }
+            if self.now() < withdrawal.unlock_at {
+                return Err(EscrowError::TimelockActive);
+            }
+
+            self.pending_withdrawals.take(&id);
+            if let Some(token) = withdrawal.token {
+                let pending = self.withdrawal_token_totals.get(&token).unwrap_or(0);
+                self.withdrawal_token_totals
+                    .insert(token, &pending.saturating_sub(withdrawal.amount));
+            } else {
+                let pending = self.withdrawal_native_total.get_or_default();
+                self.withdrawal_native_total
+                    .set(&pending.saturating_sub(withdrawal.amount));
+            }
+            self.pay_out(withdrawal.token, withdrawal.to, withdrawal.amount)?;
+            self.env().emit_event(WithdrawalExecuted {
+                id,
+                to: withdrawal.to,
+                amount: withdrawal.amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Freezes a pending withdrawal, e.g. when the recipient's key is
+        /// suspected to be compromised. The owner or the escrow's arbiter
+        /// may veto.
+        #[ink(message)]
+        pub fn veto_withdrawal(&mut self, id: u32) -> Result<()> {
+            let caller = self.env().caller();
+            let mut withdrawal = self
+                .pending_withdrawals
+                .get(&id)
+                .ok_or(EscrowError::WithdrawalNotFound)?;
+            let arbiter = self
+                .escrows
+                .get(&withdrawal.escrow_id)
+                .and_then(|escrow| escrow.arbiter);
+            if caller != self.owner && arbiter != Some(caller) {
+                return Err(EscrowError::NotOwner);
+            }
+
+            withdrawal.vetoed = true;
+            self.pending_withdrawals.insert(id, &withdrawal);
+            self.env().emit_event(WithdrawalVetoed {
+                id,
+                vetoed_by: caller,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Sends a vetoed withdrawal to `to`, normally the recipient's new
+        /// account, behind a fresh delay.
+        #[ink(message)]
+        pub fn requeue_withdrawal(&mut self, id: u32, to: AccountId) -> Result<()> {
+            self.ensure_caller_is_owner()?;
+            let mut withdrawal = self
+                .pending_withdrawals
+                .get(&id)
+                .ok_or(EscrowError::WithdrawalNotFound)?;
+            if !withdrawal.vetoed {
+                return Err(EscrowError::WithdrawalNotVetoed);
+            }
+
+            withdrawal.to = to;
+            withdrawal.vetoed = false;
+            withdrawal.unlock_at = self.now().saturating_add(
+                self.withdrawal_delay
+                    .get()
+                    .unwrap_or(DEFAULT_WITHDRAWAL_DELAY),
+            );
+            self.pending_withdrawals.insert(id, &withdrawal);
+            let header = self.event_header(withdrawal.escrow_id);
+            self.env().emit_event(WithdrawalQueued {
+                id,
+                escrow_id: withdrawal.escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                to,
+                amount: withdrawal.amount,
+                unlock_at: withdrawal.unlock_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn pending_withdrawal(&self, id: u32) -> Option<PendingWithdrawal> {
+            self.pending_withdrawals.get(&id)
+        }
+
+        #[ink(message)]
+        pub fn contribution(&self, escrow_id: Hash, account: AccountId) -> Balance {
+            self.contributions.get(&(escrow_id, account)).unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn terms_digest(&self, escrow_id: Hash) -> Result<(Hash, TermsSummary)> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let summary = TermsSummary {
+                escrow_id,
+                landlord: escrow.landlord,
+                terms: self.terms_of(escrow_id, &escrow),
+            };
+            let digest = self.env().hash_encoded::<Blake2x256, _>(&summary);
+            Ok((Hash::from(digest), summary))
+        }
+
+        /// Emits a `HealthReport` with the contract-wide accounting totals so
+        /// monitoring can alert on drift. Meant to be called by a keeper.
+        #[ink(message)]
+        pub fn report_health(&self) {
+            let balance = self.env().balance();
+            self.env().emit_event(HealthReport {
+                reporter: self.env().caller(),
+                total_value_locked: self.total_locked,
+                unattributed_balance: balance.saturating_sub(self.total_locked),
+                active_disputes: self.active_disputes,
+                pull_ledger_total: self.pull_ledger_total(),
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        /// Native funds owed through the pull ledger: credits not yet
+        /// withdrawn and pending withdrawals. Token ledgers are counted by
+        /// `tracked_token_balance`.
+        fn pull_ledger_total(&self) -> Balance {
+            self.credit_totals
+                .get(&None)
+                .unwrap_or(0)
+                .saturating_add(self.withdrawal_native_total.get_or_default())
+        }
+
+        /// What the caller pays in: the transferred value for the native
+        /// currency, or exactly `due` for a PSP22 token.
+        fn incoming_amount(&self, token: Option<AccountId>, due: Balance) -> Result<Balance> {
+            let value = self.env().transferred_value();
+            if token.is_none() {
+                return Ok(value);
+            }
+            if value > 0 {
+                return Err(EscrowError::UnexpectedNativeValue);
+            }
+            Ok(due)
+        }
+
+        /// Checks an `auto_pay` draw against the renter's pause switch and
+        /// caps, and counts it towards the current spending window.
+        fn ensure_within_vault_limits(
+            &mut self,
+            renter: AccountId,
+            escrow_id: Hash,
+            amount: Balance,
+        ) -> Result<()> {
+            let mut policy = self.vault_policy(renter);
+            if policy.paused {
+                return Err(EscrowError::VaultPaused);
+            }
+            if self
+                .auto_pay_cap(renter, escrow_id)
+                .map_or(false, |cap| amount > cap)
+            {
+                return Err(EscrowError::SpendingLimitExceeded);
+            }
+            let now = self.now();
+            if now >= policy.period_start.saturating_add(policy.period_length) {
+                policy.period_start = now;
+                policy.spent_in_period = 0;
+            }
+            if let Some(cap) = policy.period_cap {
+                let spent = policy.spent_in_period.try_add(amount)?;
+                if spent > cap {
+                    return Err(EscrowError::SpendingLimitExceeded);
+                }
+                policy.spent_in_period = spent;
+                self.vault_policies.insert(renter, &policy);
+            }
+            Ok(())
+        }
+
+        /// Takes `amount` out of a vault. Vault funds are already held by the
+        /// contract, so only the vault accounting changes.
+        fn draw_from_vault(
+            &mut self,
+            renter: AccountId,
+            token: Option<AccountId>,
+            amount: Balance,
+        ) -> Result<()> {
+            let balance = self.vault_balance(renter, token);
+            if amount > balance {
+                return Err(EscrowError::InsufficientVaultBalance);
+            }
+            self.vaults
+                .insert((renter, token), &balance.saturating_sub(amount));
+            if let Some(token) = token {
+                let total = self.vault_token_totals.get(&token).unwrap_or(0);
+                self.vault_token_totals
+                    .insert(token, &total.saturating_sub(amount));
+            }
+            Ok(())
+        }
+
+        fn get_program_or_revert(&self, program_id: u32) -> Result<AssistanceProgram> {
+            self.assistance_programs
+                .get(&program_id)
+                .ok_or(EscrowError::ProgramNotFound)
+        }
+
+        fn ensure_caller_is_sponsor(
+            &self,
+            program: &AssistanceProgram,
+            caller: &AccountId,
+        ) -> Result<()> {
+            if program.sponsor != *caller {
+                return Err(EscrowError::NotSponsor);
+            }
+            Ok(())
+        }
+
+        /// The escrow's program, if it is enrolled in one that is still active.
+        fn active_program_of(&self, escrow_id: Hash) -> Option<(u32, AssistanceProgram)> {
+            let program_id = self.program_enrollments.get(&escrow_id)?;
+            self.assistance_programs
+                .get(&program_id)
+                .filter(|program| program.active)
+                .map(|program| (program_id, program))
+        }
+
+        /// The program and amount assisting with the open period: the
+        /// program's share of the rent, limited to its remaining funds.
+        fn assistance_for(&self, escrow_id: Hash, escrow: &Escrow) -> Option<(u32, Balance)> {
+            let (program_id, program) = self.active_program_of(escrow_id)?;
+            let rent = escrow.rent_amount.saturating_sub(escrow.rent_credit);
+            let amount = core::cmp::min(
+                self.allocate(rent, program.terms.coverage_bps),
+                program.balance,
+            );
+            Some((program_id, amount))
+        }
+
+        /// The program as it stands once `amount` of assistance is paid out
+        /// of it.
+        fn disbursed_program(&self, program_id: u32, amount: Balance) -> Result<AssistanceProgram> {
+            let mut program = self.get_program_or_revert(program_id)?;
+            program.balance = program.balance.try_sub(amount)?;
+            program.total_disbursed = program.total_disbursed.try_add(amount)?;
+            Ok(program)
+        }
+
+        /// Moves assistance from a program's balance into an escrow. Both are
+        /// held by the contract, so only the accounting changes. `program` is
+        /// the program after the payout, from `disbursed_program`.
+        fn disburse_assistance(
+            &mut self,
+            program_id: u32,
+            program: AssistanceProgram,
+            escrow_id: Hash,
+            amount: Balance,
+        ) {
+            if amount == 0 {
+                return;
+            }
+            self.assistance_programs.insert(program_id, &program);
+            self.release_program_funds(program.terms.payment_token, amount);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(AssistancePaid {
+                program_id,
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        fn release_program_funds(&mut self, token: Option<AccountId>, amount: Balance) {
+            if let Some(token) = token {
+                let total = self.program_token_totals.get(&token).unwrap_or(0);
+                self.program_token_totals
+                    .insert(token, &total.saturating_sub(amount));
+            }
+        }
+
+        fn collect_payment(
+            &mut self,
+            token: Option<AccountId>,
+            from: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            match token {
+                None => self.total_locked = self.total_locked.try_add(amount)?,
+                Some(_) if amount == 0 => {}
+                Some(token) => {
+                    let to = self.env().account_id();
+                    build_call::<Environment>()
+                        .call(token)
+                        .exec_input(
+                            ExecutionInput::new(Selector::new(psp22::TRANSFER_FROM_SELECTOR))
+                                .push_arg(from)
+                                .push_arg(to)
+                                .push_arg(amount)
+                                .push_arg(Vec::<u8>::new()),
+                        )
+                        .returns::<core::result::Result<(), PSP22Error>>()
+                        .try_invoke()
+                        .map_err(|_| EscrowError::TokenTransferFailed)?
+                        .map_err(|_| EscrowError::TokenTransferFailed)?
+                        .map_err(|_| EscrowError::TokenTransferFailed)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn pay_out(
+            &mut self,
+            token: Option<AccountId>,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            match token {
+                None => {
+                    self.env()
+                        .transfer(to, amount)
+                        .map_err(|_| EscrowError::TransferFailed)?;
+                    self.total_locked = self.total_locked.try_sub(amount)?;
+                }
+                Some(_) if amount == 0 => {}
+                Some(token) => {
+                    if self.transfer_token(token, to, amount).is_err() {
+                        self.queue_failed_payout(token, to, amount)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Pays a landlord settlement, keeping the platform fee in
+        /// `collected_fees`.
+        fn pay_landlord(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            amount: Balance,
+        ) -> Result<()> {
+            let share = self.accrue_fee(escrow_id, escrow, amount)?;
+            self.pay_out(escrow.payment_token, escrow.landlord, share)
+        }
+
+        /// Keeps the platform fee on a landlord payment in `collected_fees`,
+        /// and the premium of an insured escrow in the insurance fund, and
+        /// returns the landlord's share.
+        fn accrue_fee(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            amount: Balance,
+        ) -> Result<Balance> {
+            let fee = self.allocate(amount, self.fee_bps_for(&escrow.landlord));
+            if fee > 0 {
+                let token = escrow.payment_token;
+                let collected = self.collected_fees.get(&token).unwrap_or(0);
+                self.collected_fees.insert(token, &collected.try_add(fee)?);
+                let header = self.event_header(escrow_id);
+                self.env().emit_event(FeeAccrued {
+                    escrow_id,
+                    landlord: header.landlord,
+                    renter: header.renter,
+                    sequence: header.sequence,
+                    token,
+                    amount: fee,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+            }
+            let premium = self.insured_escrows.get(&escrow_id).map_or(0, |policy| {
+                self.allocate(amount.saturating_sub(fee), policy.premium_bps)
+            });
+            if premium > 0 {
+                let token = escrow.payment_token;
+                let fund = self.insurance_fund.get(&token).unwrap_or(0);
+                self.insurance_fund.insert(token, &fund.try_add(premium)?);
+                let header = self.event_header(escrow_id);
+                self.env().emit_event(InsurancePremiumPaid {
+                    escrow_id,
+                    landlord: header.landlord,
+                    renter: header.renter,
+                    sequence: header.sequence,
+                    token,
+                    amount: premium,
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+            }
+            amount.try_sub(fee)?.try_sub(premium)
+        }
+
+        /// Part of `rent` that goes to the insurance fund while the fund is
+        /// still owed for arrears it paid.
+        fn insurance_recovery(&self, escrow_id: Hash, rent: Balance) -> Balance {
+            core::cmp::min(self.insurance_subrogation(escrow_id), rent)
+        }
+
+        /// Books `recovered` rent to the insurance fund, whose new balance
+        /// the caller has already worked out as `fund`.
+        fn recover_insurance(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            recovered: Balance,
+            fund: Balance,
+        ) {
+            if recovered == 0 {
+                return;
+            }
+            let remaining = self
+                .insurance_subrogation(escrow_id)
+                .saturating_sub(recovered);
+            if remaining == 0 {
+                self.insurance_subrogations.remove(escrow_id);
+            } else {
+                self.insurance_subrogations.insert(escrow_id, &remaining);
+            }
+            self.insurance_fund.insert(escrow.payment_token, &fund);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InsuranceRecovered {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount: recovered,
+                remaining,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        /// Pays out a settlement, or holds it as a pending withdrawal when it
+        /// reaches the withdrawal threshold.
+        fn release(
+            &mut self,
+            escrow_id: Hash,
+            token: Option<AccountId>,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            if !self
+                .withdrawal_threshold
+                .get_or_default()
+                .map_or(false, |threshold| amount >= threshold)
+            {
+                return self.pay_out(token, to, amount);
+            }
+            let id = self.next_withdrawal_id.get_or_default();
+            self.next_withdrawal_id.set(&id.try_add(1)?);
+            let unlock_at = self.now().saturating_add(
+                self.withdrawal_delay
+                    .get()
+                    .unwrap_or(DEFAULT_WITHDRAWAL_DELAY),
+            );
+            self.pending_withdrawals.insert(
+                id,
+                &PendingWithdrawal {
+                    escrow_id,
+                    token,
+                    to,
+                    amount,
+                    unlock_at,
+                    vetoed: false,
+                },
+            );
+            if let Some(token) = token {
+                let pending = self.withdrawal_token_totals.get(&token).unwrap_or(0);
+                self.withdrawal_token_totals
+                    .insert(token, &pending.try_add(amount)?);
+            } else {
+                let pending = self.withdrawal_native_total.get_or_default();
+                self.withdrawal_native_total.set(&pending.try_add(amount)?);
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(WithdrawalQueued {
+                id,
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                to,
+                amount,
+                unlock_at,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        fn store_agreement(&mut self, escrow_id: Hash, agreement: Agreement) {
+            self.agreements.insert(escrow_id, &agreement);
+            self.agreement_amendments.remove(escrow_id);
+        }
+
+        fn store_rent_peg(&mut self, escrow_id: Hash, rent_peg: Option<RentPeg>) {
+            match rent_peg {
+                Some(peg) => {
+                    self.rent_pegs.insert(escrow_id, &peg);
+                }
+                None => self.rent_pegs.remove(escrow_id),
+            }
+        }
+
+        fn store_deposit_plan(&mut self, escrow_id: Hash, plan: Option<DepositPlan>) {
+            if let Some(plan) = plan {
+                let schedule = DepositSchedule {
+                    plan,
+                    installments_paid: 0,
+                };
+                self.deposit_schedules.insert(escrow_id, &schedule);
+            }
+        }
+
+        fn store_insurance_requirement(
+            &mut self,
+            escrow_id: Hash,
+            requirement: Option<InsuranceRequirement>,
+        ) {
+            if let Some(requirement) = requirement {
+                let status = InsuranceStatus {
+                    requirement,
+                    policy_hash: Hash::default(),
+                    expires_at: 0,
+                    lapse_reported: false,
+                };
+                self.insurance.insert(escrow_id, &status);
+            }
+        }
+
+        fn policy_valid(&self, status: &InsuranceStatus) -> bool {
+            self.now() < status.expires_at
+        }
+
+        /// Deposit owed when the lease is accepted: the whole deposit, or only
+        /// the upfront part when it is paid in installments.
+        fn upfront_deposit(&self, escrow_id: Hash, escrow: &Escrow) -> Balance {
+            self.deposit_schedules
+                .get(&escrow_id)
+                .map_or(escrow.security_deposit, |schedule| schedule.plan.upfront)
+        }
+
+        /// Size of the next open installment.
+        fn deposit_installment(escrow: &Escrow, schedule: &DepositSchedule) -> Balance {
+            let financed = escrow
+                .security_deposit
+                .saturating_sub(schedule.plan.upfront);
+            let installments = Balance::from(schedule.plan.installments);
+            let installment = financed / installments;
+            if schedule.installments_paid.saturating_add(1) == schedule.plan.installments {
+                financed.saturating_sub(installment.saturating_mul(installments.saturating_sub(1)))
+            } else {
+                installment
+            }
+        }
+
+        fn installment_due_at(escrow: &Escrow, schedule: &DepositSchedule) -> u64 {
+            let intervals = u64::from(schedule.installments_paid).saturating_add(1);
+            escrow
+                .lease_start_time
+                .saturating_add(schedule.plan.interval.saturating_mul(intervals))
+        }
+
+        /// Late fee on the next open installment, charged at the rent's late
+        /// fee rate once its grace period has run out.
+        fn installment_late_fee(&self, escrow: &Escrow, schedule: &DepositSchedule) -> Balance {
+            if escrow.status != LeaseStatus::Active
+                || self.now()
+                    <= Self::installment_due_at(escrow, schedule)
+                        .saturating_add(escrow.grace_period)
+            {
+                return 0;
+            }
+            self.allocate(
+                Self::deposit_installment(escrow, schedule),
+                escrow.late_fee_bps,
+            )
+        }
+
+        /// Reprices the rent of a pegged escrow at the oracle's current rate.
+        /// The new amount is stored with the next payment, so claims and
+        /// refunds use the rate the period was paid at.
+        fn apply_rent_peg(&self, escrow_id: Hash, escrow: &mut Escrow) -> Result<()> {
+            let peg = match self.rent_pegs.get(&escrow_id) {
+                Some(peg) => peg,
+                None => return Ok(()),
+            };
+            let price = self.latest_price(peg.price_oracle, escrow.payment_token)?;
+            if self.now().saturating_sub(price.updated_at)
+                > self.oracle_max_age.get().unwrap_or(DEFAULT_ORACLE_MAX_AGE)
+            {
+                return Err(EscrowError::StalePrice);
+            }
+            if price.rate == 0 {
+                return Err(EscrowError::OracleUnavailable);
+            }
+            escrow.rent_amount =
+                peg.rent_fiat_amount.try_mul(price.rate)? / price_oracle::RATE_SCALE;
+            Ok(())
+        }
+
+        fn latest_price(&self, oracle: AccountId, token: Option<AccountId>) -> Result<Price> {
+            build_call::<Environment>()
+                .call(oracle)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(price_oracle::LATEST_PRICE_SELECTOR))
+                        .push_arg(token),
+                )
+                .returns::<Price>()
+                .try_invoke()
+                .map_err(|_| EscrowError::OracleUnavailable)?
+                .map_err(|_| EscrowError::OracleUnavailable)
+        }
+
+        fn fee_bps_for(&self, landlord: &AccountId) -> u16 {
+            self.fee_overrides.get(landlord).unwrap_or(self.fee_bps)
+        }
+
+        fn transfer_token(&self, token: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+            build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(psp22::TRANSFER_SELECTOR))
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<core::result::Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| EscrowError::TokenTransferFailed)?
+                .map_err(|_| EscrowError::TokenTransferFailed)?
+                .map_err(|_| EscrowError::TokenTransferFailed)
+        }
+
+        /// Records a refused token payout so a paused or blacklisting token
+        /// cannot block the settlement that triggered it.
+        fn queue_failed_payout(
+            &mut self,
+            token: AccountId,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            let id = self.next_payout_id;
+            self.next_payout_id = id.try_add(1)?;
+            self.failed_payouts.insert(
+                id,
+                &FailedPayout {
+                    id,
+                    token,
+                    to,
+                    amount,
+                },
+            );
+            let mut ids = self.failed_payouts_by_account.get(&to).unwrap_or_default();
+            ids.push(id);
+            self.failed_payouts_by_account.insert(to, &ids);
+            let queued = self.queued_payout_totals.get(&token).unwrap_or(0);
+            self.queued_payout_totals
+                .insert(token, &queued.saturating_add(amount));
+            self.env().emit_event(PayoutFailed {
+                id,
+                to,
+                token,
+                amount,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Derives an unused escrow id from the creator, the landlord, the
+        /// nonce and the block number.
+        fn next_escrow_id(&mut self, caller: AccountId, landlord: AccountId) -> Result<Hash> {
+            let nonce = self.escrow_nonce.get_or_default();
+            self.escrow_nonce.set(&nonce.try_add(1)?);
+            let seed = (caller, landlord, nonce, self.env().block_number());
+            let escrow_id = Hash::from(self.env().hash_encoded::<Blake2x256, _>(&seed));
+            if self.escrows.contains(&escrow_id) {
+                return Err(EscrowError::IdExists);
+            }
+            Ok(escrow_id)
+        }
+
+        /// Stores a new escrow together with the parts of its terms that are
+        /// kept beside `escrows`.
+        fn insert_new_escrow(
+            &mut self,
+            escrow_id: Hash,
+            renter: Option<AccountId>,
+            landlord: AccountId,
+            terms: LeaseTerms,
+        ) -> Result<()> {
+            let index = self.escrow_count.get_or_default();
+            self.escrow_count.set(&index.try_add(1)?);
+            self.store_rent_peg(escrow_id, terms.rent_peg);
+            self.store_deposit_plan(escrow_id, terms.deposit_plan);
+            if terms.jurisdiction != 0 {
+                self.jurisdictions.insert(escrow_id, &terms.jurisdiction);
+            }
+            if terms.allow_partial {
+                self.partial_payments.insert(escrow_id, &0);
+            }
+            self.store_insurance_requirement(escrow_id, terms.insurance);
+            if terms.stream_rent {
+                self.rent_streams.insert(escrow_id, &0);
+            }
+            if terms.building != Hash::default() {
+                self.buildings.insert(escrow_id, &terms.building);
+            }
+            if let Some(plan) = terms.protection {
+                self.protection_plans.insert(escrow_id, &plan);
+            }
+            if let Some(renter_bps) = terms.yield_renter_bps {
+                self.yield_splits.insert(escrow_id, &renter_bps);
+            }
+            if let Some(ownership) = &terms.joint_ownership {
+                self.joint_ownerships.insert(escrow_id, ownership);
+            }
+            self.store_agreement(
+                escrow_id,
+                Agreement {
+                    agreement_hash: terms.agreement_hash,
+                    metadata_uri: terms.metadata_uri.clone(),
+                },
+            );
+            let escrow = Self::new_escrow(renter, landlord, terms);
+            let token = escrow.payment_token;
+            self.store_escrow(escrow_id, &escrow);
+            self.escrow_index.insert(index, &escrow_id);
+            let mut ids = self.escrows_by_token.get(&token).unwrap_or_default();
+            ids.push(escrow_id);
+            self.escrows_by_token.insert(token, &ids);
+            Ok(())
+        }
+
+        fn new_escrow(renter: Option<AccountId>, landlord: AccountId, terms: LeaseTerms) -> Escrow {
+            let LeaseTerms {
+                rent_amount,
+                lease_duration,
+                security_deposit,
+                payment_interval,
+                grace_period,
+                late_fee_bps,
+                arbiter,
+                payment_token,
+                early_termination_penalty_periods,
+                cam_estimate,
+                percentage_rent_bps,
+                sales_breakpoint,
+                co_renters,
+                rent_peg: _,
+                agreement_hash: _,
+                metadata_uri: _,
+                deposit_plan: _,
+                jurisdiction: _,
+                allow_partial: _,
+                insurance: _,
+                stream_rent: _,
+                building: _,
+                protection: _,
+                yield_renter_bps: _,
+                joint_ownership: _,
+            } = terms;
+            Escrow {
+                renter,
+                landlord,
+                rent_amount,
+                lease_duration,
+                lease_start_time: 0,
+                escrow_balance: 0,
+                status: LeaseStatus::Open,
+                security_deposit,
+                deposit_balance: 0,
+                payment_interval,
+                periods_paid: 0,
+                periods_claimed: 0,
+                next_due_timestamp: 0,
+                grace_period,
+                late_fee_bps,
+                late_fees: 0,
+                arbiter,
+                dispute_reason: None,
+                dispute_deadline: 0,
+                payment_token,
+                rent_credit: 0,
+                early_termination_penalty_periods,
+                cam_estimate,
+                cam_collected: 0,
+                cam_charge: 0,
+                cam_reconciled_at: 0,
+                percentage_rent_bps,
+                sales_breakpoint,
+                percentage_rent_due: 0,
+                overpayment: 0,
+                co_renters,
+            }
+        }
+
+        /// The power-of-ten range the rent falls in, e.g. `100-999`, so a
+        /// token shows the size of the lease without its exact rent.
+        fn rent_band(rent: Balance) -> Vec<u8> {
+            if rent == 0 {
+                return b"0".to_vec();
+            }
+            let mut low: Balance = 1;
+            while low <= rent / 10 {
+                low = low.saturating_mul(10);
+            }
+            let high = low.saturating_mul(10).saturating_sub(1);
+            let mut band = low.to_string().into_bytes();
+            band.push(b'-');
+            band.extend_from_slice(high.to_string().as_bytes());
+            band
+        }
+
+        fn lease_token_id(escrow_id: Hash) -> Id {
+            Id::Bytes(escrow_id.as_ref().to_vec())
+        }
+
+        fn escrow_id_of(id: &Id) -> Option<Hash> {
+            match id {
+                Id::Bytes(bytes) if bytes.len() == 32 => {
+                    let mut raw = [0u8; 32];
+                    raw.copy_from_slice(bytes);
+                    Some(Hash::from(raw))
+                }
+                _ => None,
+            }
+        }
+
+        fn mint_lease_token(&mut self, escrow_id: Hash, to: AccountId) {
+            self.lease_token_owners.insert(escrow_id, &to);
+            self.adjust_token_balance(to, true);
+            self.record_tenancy(escrow_id, to, true, true);
+            self.lease_token_supply = self.lease_token_supply.saturating_add(1);
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                id: Self::lease_token_id(escrow_id),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        fn burn_lease_token(&mut self, escrow_id: Hash) {
+            if let Some(owner) = self.lease_token_owners.take(&escrow_id) {
+                self.adjust_token_balance(owner, false);
+                self.record_tenancy(escrow_id, owner, false, true);
+                self.lease_token_supply = self.lease_token_supply.saturating_sub(1);
+                self.assignment_approvals.take(&escrow_id);
+                self.env().emit_event(Transfer {
+                    from: Some(owner),
+                    to: None,
+                    id: Self::lease_token_id(escrow_id),
+                    schema_version: EVENT_SCHEMA_VERSION,
+                });
+            }
+        }
+
+        /// Checkpoints the tenancy weight of `renter`, and with `total` the
+        /// building's, when a lease in a building starts, moves or ends.
+        fn record_tenancy(
+            &mut self,
+            escrow_id: Hash,
+            renter: AccountId,
+            increase: bool,
+            total: bool,
+        ) {
+            let building = match self.buildings.get(&escrow_id) {
+                Some(building) => building,
+                None => return,
+            };
+            let block = self.env().block_number();
+            let mut checkpoints = self
+                .tenancy_checkpoints
+                .get(&(building, renter))
+                .unwrap_or_default();
+            Self::push_checkpoint(&mut checkpoints, block, increase);
+            self.tenancy_checkpoints
+                .insert((building, renter), &checkpoints);
+            if total {
+                let mut checkpoints = self.building_checkpoints.get(&building).unwrap_or_default();
+                Self::push_checkpoint(&mut checkpoints, block, increase);
+                self.building_checkpoints.insert(building, &checkpoints);
+            }
+        }
+
+        /// Moves the latest weight up or down by one as of `block`, folding
+        /// changes within the same block into one checkpoint.
+        fn push_checkpoint(checkpoints: &mut Vec<(u32, u32)>, block: u32, increase: bool) {
+            let weight = checkpoints.last().map_or(0, |(_, weight)| *weight);
+            let weight = if increase {
+                weight.saturating_add(1)
+            } else {
+                weight.saturating_sub(1)
+            };
+            match checkpoints.last_mut() {
+                Some(last) if last.0 == block => last.1 = weight,
+                _ => checkpoints.push((block, weight)),
+            }
+        }
+
+        fn weight_at(&self, checkpoints: Option<Vec<(u32, u32)>>, block: u32) -> Result<u32> {
+            if block >= self.env().block_number() {
+                return Err(EscrowError::FutureBlock);
+            }
+            let checkpoints = checkpoints.unwrap_or_default();
+            let index = checkpoints.partition_point(|(at, _)| *at <= block);
+            Ok(index.checked_sub(1).map_or(0, |index| checkpoints[index].1))
+        }
+
+        fn adjust_token_balance(&mut self, owner: AccountId, increase: bool) {
+            let balance = self.lease_token_balances.get(&owner).unwrap_or(0);
+            let balance = if increase {
+                balance.saturating_add(1)
+            } else {
+                balance.saturating_sub(1)
+            };
+            self.lease_token_balances.insert(owner, &balance);
+        }
+
+        /// Next registered arbiter in round-robin order, skipping the current
+        /// arbiter and both parties of the escrow.
+        fn pick_arbiter(&mut self, escrow: &Escrow) -> Result<AccountId> {
+            let registry = self.arbiter_registry.get_or_default();
+            let count = registry.len() as u32;
+            for offset in 0..count {
+                let index = (self.next_arbiter + offset) % count;
+                let candidate = registry[index as usize];
+                if Some(candidate) == escrow.arbiter
+                    || Some(candidate) == escrow.renter
+                    || candidate == escrow.landlord
+                {
+                    continue;
+                }
+                self.next_arbiter = (index + 1) % count;
+                return Ok(candidate);
+            }
+            Err(EscrowError::NoArbiterAvailable)
+        }
+
+        /// Registered inspectors other than the parties of the escrow.
+        fn eligible_inspectors(&self, escrow: &Escrow) -> Vec<AccountId> {
+            self.inspector_registry
+                .get_or_default()
+                .iter()
+                .copied()
+                .filter(|candidate| {
+                    Some(*candidate) != escrow.renter && *candidate != escrow.landlord
+                })
+                .collect()
+        }
+
+        /// Registered inspector picked by `seed`, skipping both parties of the
+        /// escrow.
+        fn draw_inspector(&self, escrow: &Escrow, seed: (Hash, Hash, Hash)) -> Result<AccountId> {
+            let eligible = self.eligible_inspectors(escrow);
+            if eligible.is_empty() {
+                return Err(EscrowError::NoInspectorAvailable);
+            }
+            let digest = self.env().hash_encoded::<Blake2x256, _>(&seed);
+            let roll = u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]);
+            Ok(eligible[(roll % eligible.len() as u32) as usize])
+        }
+
+        fn assign_inspector(
+            &mut self,
+            escrow_id: Hash,
+            draw: &mut InspectionDraw,
+            inspector: AccountId,
+        ) {
+            draw.inspector = Some(inspector);
+            self.log_activity(escrow_id, Activity::InspectorAssigned { inspector });
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(InspectorAssigned {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                inspector,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        fn add_to_index(
+            index: &mut Mapping<AccountId, Vec<Hash>>,
+            account: AccountId,
+            escrow_id: Hash,
+        ) {
+            let mut ids = index.get(&account).unwrap_or_default();
+            if !ids.contains(&escrow_id) {
+                ids.push(escrow_id);
+                index.insert(account, &ids);
+            }
+        }
+
+        fn remove_from_index(
+            index: &mut Mapping<AccountId, Vec<Hash>>,
+            account: AccountId,
+            escrow_id: Hash,
+        ) {
+            if let Some(mut ids) = index.get(&account) {
+                ids.retain(|id| *id != escrow_id);
+                index.insert(account, &ids);
+            }
+        }
+
+        /// The stored status, reporting `Expired` for an active lease whose
+        /// duration has passed.
+        fn current_status(&self, escrow: &Escrow) -> LeaseStatus {
+            if escrow.status == LeaseStatus::Active
+                && self.ensure_lease_duration_passed(escrow).is_ok()
+            {
+                return LeaseStatus::Expired;
+            }
+            escrow.status
+        }
+
+        /// What is left to pay for the open period after assistance and any
+        /// partial payments, with the rent peg and sales report applied.
+        fn period_balance(&self, escrow_id: Hash, escrow: &mut Escrow) -> Result<Balance> {
+            self.apply_rent_peg(escrow_id, escrow)?;
+            self.apply_sales_report(escrow_id, escrow)?;
+            let assisted = self
+                .assistance_for(escrow_id, escrow)
+                .map_or(0, |(_, amount)| amount);
+            let prepaid = self.partial_payments.get(&escrow_id).unwrap_or(0);
+            let due = self
+                .amount_due(escrow)?
+                .try_add(self.protection_fee(escrow_id))?;
+            Ok((due - assisted).saturating_sub(prepaid))
+        }
+
+        /// Pays the current period for `payer` from `source`, including any
+        /// protection fee, and returns the amount taken from the payer.
+        fn take_rent(
+            &mut self,
+            escrow_id: Hash,
+            payer: AccountId,
+            source: RentSource,
+        ) -> Result<Balance> {
+            let mut escrow = self.get_escrow_or_revert(escrow_id)?;
+            self.ensure_escrow_leased(&escrow)?;
+            if !Self::is_renter(&escrow, &payer) {
+                return Err(EscrowError::NotRenter);
+            }
+            self.apply_rent_peg(escrow_id, &mut escrow)?;
+            let report_applied = self.apply_sales_report(escrow_id, &mut escrow)?;
+            let late_fee = self.late_fee(&escrow);
+            let protection_fee = self.protection_fee(escrow_id);
+            let due = self.amount_due(&escrow)?.try_add(protection_fee)?;
+            let assistance = self.assistance_for(escrow_id, &escrow);
+            let assisted = assistance.map_or(0, |(_, amount)| amount);
+            let prepaid = self.partial_payments.get(&escrow_id).unwrap_or(0);
+            let owed = (due - assisted).saturating_sub(prepaid);
+            let value = match source {
+                RentSource::Transferred => self.incoming_amount(escrow.payment_token, owed)?,
+                RentSource::Batch(remaining) if escrow.payment_token.is_none() => {
+                    core::cmp::min(remaining, owed)
+                }
+                RentSource::Vault | RentSource::Batch(_) => owed,
+            };
+            let paid = value.try_add(assisted)?.try_add(prepaid)?;
+            self.ensure_rent_amount_paid(&escrow, paid.saturating_sub(protection_fee))?;
+            if escrow.periods_paid >= self.total_periods(&escrow) {
+                return Err(EscrowError::LeaseFullyPaid);
+            }
+            self.ensure_within_balance_cap(
+                &escrow,
+                value.try_add(assisted)?.saturating_sub(protection_fee),
+            )?;
+            let token = escrow.payment_token;
+            let pass_through = escrow
+                .cam_estimate
+                .try_add(escrow.cam_charge)?
+                .try_add(escrow.percentage_rent_due)?;
+            let rent = due
+                .try_sub(late_fee)?
+                .try_sub(pass_through)?
+                .try_sub(protection_fee)?;
+            let recovered = self.insurance_recovery(escrow_id, rent);
+            let fund = self
+                .insurance_fund
+                .get(&token)
+                .unwrap_or(0)
+                .try_add(recovered)?;
+            let pool = self
+                .protection_pools
+                .get(&token)
+                .unwrap_or(0)
+                .try_add(protection_fee)?;
+            let contributed = self
+                .contributions
+                .get(&(escrow_id, payer))
+                .unwrap_or(0)
+                .try_add(value)?;
+            let program = match assistance {
+                Some((program_id, amount)) => Some((
+                    program_id,
+                    self.disbursed_program(program_id, amount)?,
+                    amount,
+                )),
+                None => None,
+            };
+            // Vault funds are already counted in `total_locked`.
+            let mut total_locked = self.total_locked;
+            if token.is_none() {
+                if !matches!(source, RentSource::Vault) {
+                    total_locked = total_locked.try_add(value)?;
+                }
+                total_locked = total_locked.try_sub(pass_through)?;
+            }
+            escrow.rent_credit = escrow.rent_credit.saturating_sub(escrow.rent_amount);
+            escrow.cam_collected = escrow.cam_collected.try_add(escrow.cam_estimate)?;
+            escrow.cam_charge = 0;
+            escrow.percentage_rent_due = 0;
+            escrow.late_fees = escrow.late_fees.try_add(late_fee)?;
+            escrow.escrow_balance = escrow.escrow_balance.try_add(rent - recovered)?;
+            escrow.overpayment = escrow
+                .overpayment
+                .saturating_sub(prepaid)
+                .try_add(paid - due)?;
+            escrow.periods_paid = escrow.periods_paid.try_add(1)?;
+            if let RentSource::Vault = source {
+                if value > self.vault_balance(payer, token) {
+                    return Err(EscrowError::InsufficientVaultBalance);
+                }
+                self.ensure_within_vault_limits(payer, escrow_id, value)?;
+            }
+
+            // Every check has passed. Only the first transfer below can still
+            // fail, and it fails before anything is written, so a failed item
+            // of `pay_rent_batch` leaves no trace.
+            match token {
+                None => {
+                    if pass_through > 0 {
+                        self.env()
+                            .transfer(escrow.landlord, pass_through)
+                            .map_err(|_| EscrowError::TransferFailed)?;
+                    }
+                    if let RentSource::Vault = source {
+                        self.draw_from_vault(payer, token, value)?;
+                    }
+                    self.total_locked = total_locked;
+                }
+                Some(_) => {
+                    if let RentSource::Vault = source {
+                        self.draw_from_vault(payer, token, value)?;
+                    } else {
+                        self.collect_payment(token, payer, value)?;
+                    }
+                    // A token payout the landlord refuses is queued, not failed.
+                    self.pay_out(token, escrow.landlord, pass_through)?;
+                }
+            }
+            if let Some((program_id, program, amount)) = program {
+                self.disburse_assistance(program_id, program, escrow_id, amount);
+            }
+            if report_applied {
+                self.sales_reports.take(&escrow_id);
+            }
+            if protection_fee > 0 {
+                self.protection_pools.insert(token, &pool);
+            }
+            self.recover_insurance(escrow_id, &escrow, recovered, fund);
+            if prepaid > 0 {
+                self.partial_payments.insert(escrow_id, &0);
+            }
+            self.contributions.insert((escrow_id, payer), &contributed);
+            self.record_payment(escrow_id, &escrow, payer, value, PaymentKind::Rent);
+            escrow.next_due_timestamp = escrow
+                .next_due_timestamp
+                .saturating_add(escrow.payment_interval);
+            self.store_escrow(escrow_id, &escrow);
+            self.log_activity(
+                escrow_id,
+                Activity::Payment {
+                    payer,
+                    amount: value,
+                },
+            );
+            self.update_reputation(payer, |reputation| {
+                if late_fee > 0 {
+                    reputation.late_payments = reputation.late_payments.saturating_add(1);
+                } else {
+                    reputation.on_time_payments = reputation.on_time_payments.saturating_add(1);
+                }
+            });
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(RentPaid {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                payer,
+                amount: value,
+                late_fee,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(value)
+        }
+
+        /// Checks that a sublease of `duration` starting now ends by the time
+        /// the active parent lease does.
+        fn ensure_fits_parent_lease(&self, parent_id: Hash, duration: u64) -> Result<()> {
+            let parent = self.get_escrow_or_revert(parent_id)?;
+            self.ensure_escrow_leased(&parent)?;
+            if self.now().saturating_add(duration)
+                > parent
+                    .lease_start_time
+                    .saturating_add(parent.lease_duration)
+            {
+                return Err(EscrowError::SubleaseOutlastsLease);
+            }
+            Ok(())
+        }
+
+        /// Ends the subleases of a lease that has ended. Active ones are
+        /// terminated early on behalf of their landlord, unstarted ones are
+        /// cancelled.
+        fn end_subleases(&mut self, parent_id: Hash) -> Result<()> {
+            for child_id in self.subleases.take(&parent_id).unwrap_or_default() {
+                let mut child = self.get_escrow_or_revert(child_id)?;
+                match child.status {
+                    LeaseStatus::Active => {
+                        let sublessor = child.landlord;
+                        self.close_early(child_id, child, sublessor, false)?;
+                    }
+                    LeaseStatus::Open => {
+                        self.transition(&mut child, LeaseStatus::Terminated)?;
+                        self.store_escrow(child_id, &child);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+
+        /// Terminates an active lease before its duration has passed, as
+        /// described on `terminate_early`.
+        fn close_early(
+            &mut self,
+            escrow_id: Hash,
+            mut escrow: Escrow,
+            terminated_by: AccountId,
+            by_renter: bool,
+        ) -> Result<()> {
+            let renter = escrow.renter.ok_or(EscrowError::NotLeased)?;
+            self.transition(&mut escrow, LeaseStatus::Terminated)?;
+            self.divest(escrow_id, &mut escrow)?;
+
+            let streamed = self.rent_streams.get(&escrow_id);
+            let earned = match streamed {
+                Some(withdrawn) => self.unwithdrawn_rent(&escrow, withdrawn),
+                None => core::cmp::min(
+                    escrow.rent_amount.saturating_mul(Balance::from(
+                        self.matured_periods(&escrow)
+                            .saturating_sub(escrow.periods_claimed),
+                    )),
+                    escrow.escrow_balance,
+                ),
+            };
+            let unearned = escrow.escrow_balance.try_sub(earned)?;
+            let penalty = if by_renter {
+                core::cmp::min(
+                    unearned,
+                    escrow
+                        .rent_amount
+                        .saturating_mul(Balance::from(escrow.early_termination_penalty_periods)),
+                )
+            } else {
+                0
+            };
+            let landlord_amount = earned.try_add(penalty)?.try_add(escrow.late_fees)?;
+            // A streaming escrow keeps the unearned rent for `withdraw_unearned`.
+            let retained = if streamed.is_some() {
+                unearned.try_sub(penalty)?
+            } else {
+                0
+            };
+            let renter_refund = unearned
+                .try_sub(penalty)?
+                .try_sub(retained)?
+                .try_add(escrow.deposit_balance)?
+                .try_add(escrow.overpayment)?;
+            if landlord_amount > 0 {
+                self.pay_landlord(escrow_id, &escrow, landlord_amount)?;
+            }
+            if renter_refund > 0 {
+                self.refund_renters(&escrow, renter, renter_refund)?;
+            }
+
+            escrow.escrow_balance = retained;
+            escrow.deposit_balance = 0;
+            escrow.late_fees = 0;
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            self.burn_lease_token(escrow_id);
+            self.end_subleases(escrow_id)?;
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseTerminatedEarly {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                terminated_by,
+                landlord_amount,
+                renter_refund,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Settles an expired lease, or queues the settlement when it reaches
+        /// the release threshold. Returns whether it was settled.
+        fn end_lease(&mut self, escrow_id: Hash, escrow: Escrow) -> Result<bool> {
+            self.ensure_lease_duration_passed(&escrow)?;
+            if escrow.deposit_balance > 0 {
+                return Err(EscrowError::DepositNotSettled);
+            }
+
+            let balance = escrow.escrow_balance.try_add(escrow.late_fees)?;
+            if self
+                .release_threshold
+                .map_or(false, |threshold| balance >= threshold)
+            {
+                let now = self.now();
+                match self.pending_settlements.get(&escrow_id) {
+                    None => {
+                        self.pending_settlements.insert(
+                            escrow_id,
+                            &PendingSettlement {
+                                amount: balance,
+                                queued_at: now,
+                            },
+                        );
+                        let header = self.event_header(escrow_id);
+                        self.env().emit_event(SettlementQueued {
+                            escrow_id,
+                            landlord: header.landlord,
+                            renter: header.renter,
+                            sequence: header.sequence,
+                            amount: balance,
+                            executable_at: now.saturating_add(self.settlement_timeout),
+                            schema_version: EVENT_SCHEMA_VERSION,
+                        });
+                        return Ok(false);
+                    }
+                    Some(pending)
+                        if now < pending.queued_at.saturating_add(self.settlement_timeout) =>
+                    {
+                        return Err(EscrowError::SettlementPending);
+                    }
+                    Some(_) => {}
+                }
+            }
+            self.settle_lease(escrow_id, escrow)?;
+            Ok(true)
+        }
+
+        /// Pays the rent balance and late fees to the landlord, refunds any
+        /// overpayment to the renter and closes the escrow.
+        fn settle_lease(&mut self, escrow_id: Hash, mut escrow: Escrow) -> Result<()> {
+            self.transition(&mut escrow, LeaseStatus::Settled)?;
+
+            let landlord = escrow.landlord;
+            let balance = escrow.escrow_balance.try_add(escrow.late_fees)?;
+            let share = self.accrue_fee(escrow_id, &escrow, balance)?;
+            self.release(escrow_id, escrow.payment_token, landlord, share)?;
+            if let (Some(renter), true) = (escrow.renter, escrow.overpayment > 0) {
+                self.release(escrow_id, escrow.payment_token, renter, escrow.overpayment)?;
+            }
+
+            escrow.escrow_balance = 0;
+            escrow.late_fees = 0;
+            escrow.overpayment = 0;
+            self.store_escrow(escrow_id, &escrow);
+            self.pending_settlements.take(&escrow_id);
+            self.burn_lease_token(escrow_id);
+            self.end_subleases(escrow_id)?;
+            let completed = |reputation: &mut Reputation| {
+                reputation.leases_completed = reputation.leases_completed.saturating_add(1);
+            };
+            if let Some(renter) = escrow.renter {
+                self.update_reputation(renter, completed);
+            }
+            self.update_reputation(landlord, completed);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseEnded {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount: balance,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// Records a co-renter's acceptance of a shared listing together with
+        /// their share of the deposit. The lease starts once every co-renter
+        /// has signed.
+        fn sign_as_co_renter(
+            &mut self,
+            escrow_id: Hash,
+            mut escrow: Escrow,
+            caller: AccountId,
+        ) -> Result<()> {
+            let share = escrow
+                .co_renters
+                .iter()
+                .find(|(renter, _)| *renter == caller)
+                .map(|(_, share)| *share)
+                .ok_or(EscrowError::NotRenter)?;
+            let mut signatures = self.lease_signatures.get(&escrow_id).unwrap_or_default();
+            if signatures.iter().any(|(renter, _)| *renter == caller) {
+                return Err(EscrowError::AlreadySigned);
+            }
+            let required = self.allocate(self.upfront_deposit(escrow_id, &escrow), share);
+            let value = self.incoming_amount(escrow.payment_token, required)?;
+            if value < required {
+                return Err(EscrowError::InsufficientDeposit);
+            }
+            self.ensure_within_balance_cap(&escrow, value)?;
+            self.collect_payment(escrow.payment_token, caller, value)?;
+
+            escrow.deposit_balance = escrow.deposit_balance.try_add(value)?;
+            signatures.push((caller, value));
+            self.contributions.insert((escrow_id, caller), &value);
+            self.record_payment(escrow_id, &escrow, caller, value, PaymentKind::Deposit);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(CoRenterSigned {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                co_renter: caller,
+                deposit: value,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            if signatures.len() < escrow.co_renters.len() {
+                self.lease_signatures.insert(escrow_id, &signatures);
+                self.store_escrow(escrow_id, &escrow);
+                return Ok(());
+            }
+
+            self.lease_signatures.take(&escrow_id);
+            let primary = escrow.co_renters[0].0;
+            let lease_start_time = self.now();
+            escrow.renter = Some(primary);
+            for (renter, _) in escrow.co_renters.iter() {
+                Self::add_to_index(&mut self.escrows_by_renter, *renter, escrow_id);
+            }
+            escrow.lease_start_time = lease_start_time;
+            escrow.next_due_timestamp = lease_start_time;
+            self.transition(&mut escrow, LeaseStatus::Active)?;
+            self.store_escrow(escrow_id, &escrow);
+            self.mint_lease_token(escrow_id, primary);
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(LeaseStarted {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                lease_start_time,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        /// `bps` basis points of `amount`, rounded under the rounding policy.
+        fn allocate(&self, amount: Balance, bps: u16) -> Balance {
+            let denominator = Balance::from(MAX_BPS);
+            let bps = Balance::from(bps);
+            // Split `amount` so that no intermediate product can overflow.
+            let quotient = amount / denominator * bps + amount % denominator * bps / denominator;
+            let remainder = amount % denominator * bps % denominator;
+            let round_up = match self.rounding_policy {
+                RoundingPolicy::FloorToProtocol => remainder > 0,
+                RoundingPolicy::FloorToUser => false,
+                RoundingPolicy::Bankers => {
+                    remainder * 2 > denominator
+                        || (remainder * 2 == denominator && quotient % 2 == 1)
+                }
+            };
+            if round_up {
+                quotient + 1
+            } else {
+                quotient
+            }
+        }
+
+        /// Renters who must agree before the escrow migrates: every co-renter
+        /// of a running shared lease, the signers of an open listing, or the
+        /// sole renter.
+        fn migration_consenters(&self, escrow_id: Hash, escrow: &Escrow) -> Vec<AccountId> {
+            if escrow.renter.is_none() {
+                return self
+                    .lease_signatures
+                    .get(&escrow_id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(renter, _)| renter)
+                    .collect();
+            }
+            if escrow.co_renters.is_empty() {
+                return escrow.renter.into_iter().collect();
+            }
+            escrow
+                .co_renters
+                .iter()
+                .map(|(renter, _)| *renter)
+                .collect()
+        }
+
+        /// Whether the escrow keeps state outside `Escrow` that a successor
+        /// would not receive through `import_escrow`.
+        fn has_unexported_state(&self, escrow_id: Hash) -> bool {
+            self.deposit_schedules.contains(&escrow_id)
+                || self.rent_streams.contains(&escrow_id)
+                || self.partial_payments.contains(&escrow_id)
+                || self.rent_pegs.contains(&escrow_id)
+                || self.subleases.contains(&escrow_id)
+                || self.parent_leases.contains(&escrow_id)
+        }
+
+        fn is_renter(escrow: &Escrow, account: &AccountId) -> bool {
+            escrow.renter == Some(*account)
+                || escrow
+                    .co_renters
+                    .iter()
+                    .any(|(renter, _)| renter == account)
+        }
+
+        /// Pays a refund to the renter, or splits it by share between the
+        /// co-renters with the rounding remainder going to the last one.
+        fn refund_renters(
+            &mut self,
+            escrow: &Escrow,
+            renter: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            for (to, part) in self.renter_shares(escrow, renter, amount) {
+                self.pay_out(escrow.payment_token, to, part)?;
+            }
+            Ok(())
+        }
+
+        /// Splits `amount` among the co-renters by share, the last taking the
+        /// rounding remainder, or gives it all to `renter`.
+        fn renter_shares(
+            &self,
+            escrow: &Escrow,
+            renter: AccountId,
+            amount: Balance,
+        ) -> Vec<(AccountId, Balance)> {
+            if escrow.co_renters.is_empty() {
+                return Vec::from([(renter, amount)]);
+            }
+            let mut remaining = amount;
+            let last = escrow.co_renters.len() - 1;
+            let mut shares = Vec::with_capacity(escrow.co_renters.len());
+            for (i, (co_renter, share)) in escrow.co_renters.iter().enumerate() {
+                let part = if i == last {
+                    remaining
+                } else {
+                    core::cmp::min(remaining, self.allocate(amount, *share))
+                };
+                remaining = remaining.saturating_sub(part);
+                shares.push((*co_renter, part));
+            }
+            shares
+        }
+
+        /// Adds `amount` to what `to` may withdraw with `withdraw_credit`.
+        fn credit(
+            &mut self,
+            token: Option<AccountId>,
+            to: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            if amount == 0 {
+                return Ok(());
+            }
+            let credit = self.credits.get(&(to, token)).unwrap_or(0);
+            self.credits.insert((to, token), &credit.try_add(amount)?);
+            let total = self.credit_totals.get(&token).unwrap_or(0);
+            self.credit_totals.insert(token, &total.try_add(amount)?);
+            Ok(())
+        }
+
+        /// Tokens of `token` the contract owes to escrows, vaults, assistance
+        /// programs, the protection and insurance funds, the fee collector
+        /// and recipients of failed payouts and credits.
+        fn tracked_token_balance(&self, token: AccountId) -> Balance {
+            let held = self.held_escrow_totals.get(&Some(token)).unwrap_or(0);
+            let queued = self.queued_payout_totals.get(&token).unwrap_or(0);
+            let vaulted = self.vault_token_totals.get(&token).unwrap_or(0);
+            let sponsored = self.program_token_totals.get(&token).unwrap_or(0);
+            let pooled = self.protection_pools.get(&Some(token)).unwrap_or(0);
+            let insured = self.insurance_fund.get(&Some(token)).unwrap_or(0);
+            let credited = self.credit_totals.get(&Some(token)).unwrap_or(0);
+            let withdrawing = self.withdrawal_token_totals.get(&token).unwrap_or(0);
+            held.saturating_add(queued)
+                .saturating_add(withdrawing)
+                .saturating_add(vaulted)
+                .saturating_add(sponsored)
+                .saturating_add(pooled)
+                .saturating_add(insured)
+                .saturating_add(credited)
+                .saturating_add(self.collected_fees(Some(token)))
+        }
+
+        fn ensure_untracked(&self, token: AccountId, amount: Balance) -> Result<()> {
+            let balance = build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(psp22::BALANCE_OF_SELECTOR))
+                        .push_arg(self.env().account_id()),
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| EscrowError::TokenTransferFailed)?
+                .map_err(|_| EscrowError::TokenTransferFailed)?;
+            if amount > balance.saturating_sub(self.tracked_token_balance(token)) {
+                return Err(EscrowError::AmountTracked);
+            }
+            Ok(())
+        }
+
+        fn ensure_verified(&self, accounts: &[AccountId]) -> Result<()> {
+            if self.require_kyc.get_or_default()
+                && accounts
+                    .iter()
+                    .any(|account| !self.verified.contains(account))
+            {
+                return Err(EscrowError::NotVerified);
+            }
+            Ok(())
+        }
+
+        fn strategy_balance(&self, strategy: AccountId) -> Result<Balance> {
+            build_call::<Environment>()
+                .call(strategy)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(yield_strategy::BALANCE_OF_SELECTOR))
+                        .push_arg(self.env().account_id()),
+                )
+                .returns::<Balance>()
+                .try_invoke()
+                .map_err(|_| EscrowError::StrategyCallFailed)?
+                .map_err(|_| EscrowError::StrategyCallFailed)
+        }
+
+        fn position_value(&self, strategy: AccountId, position: &YieldPosition) -> Result<Balance> {
+            let value = self.strategy_balance(strategy)?;
+            Ok(position.shares.try_mul(value)? / self.total_yield_shares.get_or_default())
+        }
+
+        /// Withdraws the escrow's deposit from the yield strategy, if it is
+        /// invested, and pays out the yield split between renter and
+        /// landlord. A loss is taken out of the deposit.
+        fn divest(&mut self, escrow_id: Hash, escrow: &mut Escrow) -> Result<()> {
+            let position = match self.yield_positions.get(&escrow_id) {
+                Some(position) => position,
+                None => return Ok(()),
+            };
+            let strategy = self
+                .yield_strategy
+                .get_or_default()
+                .ok_or(EscrowError::NoYieldStrategy)?;
+            let value = self.position_value(strategy, &position)?;
+            build_call::<Environment>()
+                .call(strategy)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(yield_strategy::WITHDRAW_SELECTOR))
+                        .push_arg(value),
+                )
+                .returns::<core::result::Result<(), StrategyError>>()
+                .try_invoke()
+                .map_err(|_| EscrowError::StrategyCallFailed)?
+                .map_err(|_| EscrowError::StrategyCallFailed)?
+                .map_err(|_| EscrowError::StrategyCallFailed)?;
+            self.yield_positions.take(&escrow_id);
+            let total_shares = self.total_yield_shares.get_or_default();
+            self.total_yield_shares
+                .set(&total_shares.try_sub(position.shares)?);
+
+            if value < position.principal {
+                let loss = position.principal.saturating_sub(value);
+                escrow.deposit_balance = escrow.deposit_balance.saturating_sub(loss);
+                self.total_locked = self.total_locked.try_sub(loss)?;
+            }
+            let gained = value.saturating_sub(position.principal);
+            let renter_bps = self.yield_splits.get(&escrow_id).unwrap_or(0);
+            let renter_share = self.allocate(gained, renter_bps);
+            let landlord_share = gained.saturating_sub(renter_share);
+            // Yield was never counted in `total_locked`, so it is paid out
+            // directly.
+            if let (Some(renter), true) = (escrow.renter, renter_share > 0) {
+                self.env()
+                    .transfer(renter, renter_share)
+                    .map_err(|_| EscrowError::TransferFailed)?;
+            }
+            if landlord_share > 0 {
+                self.env()
+                    .transfer(escrow.landlord, landlord_share)
+                    .map_err(|_| EscrowError::TransferFailed)?;
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(YieldDistributed {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                principal: position.principal,
+                renter_share,
+                landlord_share,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        fn pay_protection_claim(&mut self, escrow_id: Hash, claim: ProtectionClaim) -> Result<()> {
+            let escrow = self.get_escrow_or_revert(escrow_id)?;
+            let token = escrow.payment_token;
+            let pool = self.protection_pools.get(&token).unwrap_or(0);
+            let amount = core::cmp::min(claim.amount, pool);
+
+            self.pending_protection_claims.take(&escrow_id);
+            self.protection_claims.insert(escrow_id, &amount);
+            self.protection_pools
+                .insert(token, &pool.saturating_sub(amount));
+            if amount > 0 {
+                self.pay_out(token, escrow.landlord, amount)?;
+            }
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(ProtectionClaimPaid {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                amount,
+                reason: claim.reason,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        fn protection_fee(&self, escrow_id: Hash) -> Balance {
+            self.protection_plans
+                .get(&escrow_id)
+                .map_or(0, |plan| plan.fee)
+        }
+
+        fn ensure_within_balance_cap(&self, escrow: &Escrow, incoming: Balance) -> Result<()> {
+            let held = Self::held_funds(escrow).saturating_add(incoming);
+            if self
+                .max_escrow_balance
+                .get_or_default()
+                .map_or(false, |cap| held > cap)
+            {
+                return Err(EscrowError::BalanceCapExceeded);
+            }
+            if self
+                .canary
+                .get_or_default()
+                .map_or(false, |limits| held > limits.max_escrow_balance)
+            {
+                return Err(EscrowError::CanaryLimitExceeded);
+            }
+            self.ensure_within_tvl_cap(escrow.payment_token, incoming)
+        }
+
+        fn ensure_within_tvl_cap(&self, token: Option<AccountId>, incoming: Balance) -> Result<()> {
+            if token.is_none()
+                && self.canary.get_or_default().map_or(false, |limits| {
+                    self.total_locked.saturating_add(incoming) > limits.max_total_locked
+                })
+            {
+                return Err(EscrowError::CanaryLimitExceeded);
+            }
+            Ok(())
+        }
+
+        /// Everything the contract holds for the escrow.
+        fn held_funds(escrow: &Escrow) -> Balance {
+            escrow
+                .escrow_balance
+                .saturating_add(escrow.deposit_balance)
+                .saturating_add(escrow.late_fees)
+                .saturating_add(escrow.overpayment)
+        }
+
+        /// Writes `escrow` and keeps `held_escrow_totals` and `live_escrows`
+        /// in step with it. Before the version 2 migration the totals are
+        /// not kept; `migrate` counts every escrow once.
+        fn store_escrow(&mut self, escrow_id: Hash, escrow: &Escrow) {
+            if self.storage_version >= 2 {
+                if let Some(previous) = self.escrows.get(&escrow_id) {
+                    self.track_escrow(&previous, false);
+                }
+                self.track_escrow(escrow, true);
+            }
+            self.escrows.insert(escrow_id, escrow);
+        }
+
+        fn track_escrow(&mut self, escrow: &Escrow, add: bool) {
+            let held = Self::held_funds(escrow);
+            if held > 0 {
+                let total = self
+                    .held_escrow_totals
+                    .get(&escrow.payment_token)
+                    .unwrap_or(0);
+                let total = if add {
+                    total.saturating_add(held)
+                } else {
+                    total.saturating_sub(held)
+                };
+                self.held_escrow_totals.insert(escrow.payment_token, &total);
+            }
+            if matches!(escrow.status, LeaseStatus::Active | LeaseStatus::Disputed) || held > 0 {
+                let live = self.live_escrows.get_or_default();
+                self.live_escrows.set(&if add {
+                    live.saturating_add(1)
+                } else {
+                    live.saturating_sub(1)
+                });
+            }
+        }
+
+        fn open_dispute(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &mut Escrow,
+            raised_by: AccountId,
+            reason_hash: Hash,
+        ) -> Result<()> {
+            self.transition(escrow, LeaseStatus::Disputed)?;
+
+            escrow.dispute_reason = Some(reason_hash);
+            escrow.dispute_deadline = self.now().saturating_add(self.arbiter_response_window);
+            self.store_escrow(escrow_id, escrow);
+            self.active_disputes = self.active_disputes.try_add(1)?;
+            self.log_activity(
+                escrow_id,
+                Activity::DisputeRaised {
+                    raised_by,
+                    reason_hash,
+                },
+            );
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(DisputeRaised {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                raised_by,
+                reason_hash,
+                timestamp: self.now(),
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+            Ok(())
+        }
+
+        fn terms_of(&self, escrow_id: Hash, escrow: &Escrow) -> LeaseTerms {
+            let agreement = self.agreements.get(&escrow_id);
+            LeaseTerms {
+                rent_amount: escrow.rent_amount,
+                lease_duration: escrow.lease_duration,
+                security_deposit: escrow.security_deposit,
+                payment_interval: escrow.payment_interval,
+                grace_period: escrow.grace_period,
+                late_fee_bps: escrow.late_fee_bps,
+                arbiter: escrow.arbiter,
+                payment_token: escrow.payment_token,
+                early_termination_penalty_periods: escrow.early_termination_penalty_periods,
+                cam_estimate: escrow.cam_estimate,
+                percentage_rent_bps: escrow.percentage_rent_bps,
+                sales_breakpoint: escrow.sales_breakpoint,
+                co_renters: escrow.co_renters.clone(),
+                rent_peg: self.rent_pegs.get(&escrow_id),
+                deposit_plan: self
+                    .deposit_schedules
+                    .get(&escrow_id)
+                    .map(|schedule| schedule.plan),
+                jurisdiction: self.jurisdictions.get(&escrow_id).unwrap_or(0),
+                allow_partial: self.partial_payments.contains(&escrow_id),
+                insurance: self
+                    .insurance
+                    .get(&escrow_id)
+                    .map(|status| status.requirement),
+                stream_rent: self.rent_streams.contains(&escrow_id),
+                building: self.buildings.get(&escrow_id).unwrap_or_default(),
+                protection: self.protection_plans.get(&escrow_id),
+                yield_renter_bps: self.yield_splits.get(&escrow_id),
+                joint_ownership: self.joint_ownerships.get(&escrow_id),
+                agreement_hash: agreement
+                    .as_ref()
+                    .map(|agreement| agreement.agreement_hash)
+                    .unwrap_or_default(),
+                metadata_uri: agreement
+                    .map(|agreement| agreement.metadata_uri)
+                    .unwrap_or_default(),
+            }
+        }
+
+        /// SCALE encoding of the tuple
+        /// `(ESCROW_ENCODING_VERSION, escrow_id, landlord, renter,
+        /// lease_start_time, terms)`, with `terms` as rebuilt by `terms_of`.
+        ///
+        /// SCALE leaves no choice in how a value is written: integers are
+        /// fixed-width little-endian, `bool` and the `Option` tag are one
+        /// byte, `Vec` and `String` carry a compact length prefix, and struct
+        /// fields follow their declaration order. The same terms therefore
+        /// always give the same bytes. Balances, paid periods and the status
+        /// are left out, so the encoding only changes when the parties or
+        /// terms do, e.g. on acceptance or renewal.
+        fn canonical_encoding(&self, escrow_id: Hash, escrow: &Escrow) -> Vec<u8> {
+            (
+                ESCROW_ENCODING_VERSION,
+                escrow_id,
+                escrow.landlord,
+                escrow.renter,
+                escrow.lease_start_time,
+                self.terms_of(escrow_id, escrow),
+            )
+                .encode()
+        }
+
+        /// The block timestamp every time check reads, so tests can drive it
+        /// through `TestClock`.
+        fn now(&self) -> u64 {
+            self.env().block_timestamp()
+        }
+
+        fn get_escrow_or_revert(&self, escrow_id: Hash) -> Result<Escrow> {
+            self.escrows
+                .get(&escrow_id)
+                .ok_or(EscrowError::EscrowNotFound)
+        }
+
+        /// Fee owed if the current period were paid now, once its grace period
+        /// has run out.
+        fn late_fee(&self, escrow: &Escrow) -> Balance {
+            if escrow.status != LeaseStatus::Active
+                || self.now()
+                    <= escrow
+                        .next_due_timestamp
+                        .saturating_add(escrow.grace_period)
+            {
+                return 0;
+            }
+            self.allocate(escrow.rent_amount, escrow.late_fee_bps)
+        }
+
+        fn total_periods(&self, escrow: &Escrow) -> u32 {
+            let periods = escrow.lease_duration / escrow.payment_interval
+                + u64::from(escrow.lease_duration % escrow.payment_interval != 0);
+            u32::try_from(periods).unwrap_or(u32::MAX)
+        }
+
+        /// Paid periods whose interval has fully elapsed.
+        fn matured_periods(&self, escrow: &Escrow) -> u32 {
+            let elapsed = self.now().saturating_sub(escrow.lease_start_time);
+            let elapsed_periods =
+                u32::try_from(elapsed / escrow.payment_interval).unwrap_or(u32::MAX);
+            core::cmp::min(elapsed_periods, escrow.periods_paid)
+        }
+
+        /// Periods that have fallen due without being paid.
+        fn periods_in_arrears(&self, escrow: &Escrow) -> u32 {
+            let unpaid = self
+                .total_periods(escrow)
+                .saturating_sub(escrow.periods_paid);
+            if unpaid == 0 || self.now() < escrow.next_due_timestamp {
+                return 0;
+            }
+            let overdue =
+                self.now().saturating_sub(escrow.next_due_timestamp) / escrow.payment_interval;
+            let due = u32::try_from(overdue).unwrap_or(u32::MAX).saturating_add(1);
+            core::cmp::min(due, unpaid)
+        }
+
+        /// Rent a streaming escrow has earned beyond what was `withdrawn`:
+        /// `rent_amount` per `payment_interval` accrued by the second over the
+        /// lease so far, capped at the rent paid in. For a prepaid lease this
+        /// is `balance * elapsed / lease_duration`.
+        fn unwithdrawn_rent(&self, escrow: &Escrow, withdrawn: Balance) -> Balance {
+            let elapsed = core::cmp::min(
+                self.now().saturating_sub(escrow.lease_start_time),
+                escrow.lease_duration,
+            );
+            let accrued = escrow.rent_amount.saturating_mul(Balance::from(elapsed))
+                / Balance::from(escrow.payment_interval);
+            let funded = escrow.escrow_balance.saturating_add(withdrawn);
+            core::cmp::min(accrued, funded).saturating_sub(withdrawn)
+        }
+
+        fn ensure_valid_terms(&self, terms: &LeaseTerms) -> Result<()> {
+            if terms.payment_interval == 0 || terms.payment_interval > terms.lease_duration {
+                return Err(EscrowError::InvalidPaymentInterval);
+            }
+            if terms.late_fee_bps > MAX_BPS {
+                return Err(EscrowError::InvalidLateFee);
+            }
+            if terms.percentage_rent_bps > MAX_BPS {
+                return Err(EscrowError::InvalidPercentageRent);
+            }
+            if matches!(terms.rent_peg, Some(peg) if peg.rent_fiat_amount == 0) {
+                return Err(EscrowError::InvalidRentPeg);
+            }
+            if terms.metadata_uri.len() > MAX_METADATA_URI_LEN {
+                return Err(EscrowError::MetadataTooLong);
+            }
+            if let Some(plan) = terms.deposit_plan {
+                let schedule_length = plan.interval.saturating_mul(u64::from(plan.installments));
+                if plan.upfront >= terms.security_deposit
+                    || plan.installments == 0
+                    || plan.interval == 0
+                    || schedule_length > terms.lease_duration
+                {
+                    return Err(EscrowError::InvalidDepositPlan);
+                }
+            }
+            if matches!(terms.yield_renter_bps, Some(bps)
+                if bps > MAX_BPS || terms.payment_token.is_some())
+            {
+                return Err(EscrowError::InvalidYieldSplit);
+            }
+            if let Some(plan) = terms.protection {
+                let periods = terms.lease_duration / terms.payment_interval
+                    + u64::from(terms.lease_duration % terms.payment_interval != 0);
+                let total_fees = plan.fee.saturating_mul(Balance::from(periods));
+                if plan.fee == 0
+                    || plan.cover > total_fees
+                    || terms.security_deposit > 0
+                    || terms.deposit_plan.is_some()
+                {
+                    return Err(EscrowError::InvalidProtectionPlan);
+                }
+            }
+            if !terms.co_renters.is_empty() {
+                let total: u32 = terms
+                    .co_renters
+                    .iter()
+                    .map(|(_, share)| u32::from(*share))
+                    .sum();
+                let distinct = terms
+                    .co_renters
+                    .iter()
+                    .enumerate()
+                    .all(|(i, (a, _))| terms.co_renters[..i].iter().all(|(b, _)| a != b));
+                if total != u32::from(MAX_BPS) || !distinct {
+                    return Err(EscrowError::InvalidShares);
+                }
+            }
+            Ok(())
+        }
+
+        fn transition(&self, escrow: &mut Escrow, to: LeaseStatus) -> Result<()> {
+            if !escrow.status.can_transition_to(to) {
+                return Err(EscrowError::InvalidTransition {
+                    from: escrow.status,
+                    to,
+                });
+            }
+            escrow.status = to;
+            Ok(())
+        }
+
+        fn ensure_not_disputed(&self, escrow: &Escrow) -> Result<()> {
+            if escrow.status == LeaseStatus::Disputed {
+                return Err(EscrowError::Disputed);
+            }
+            Ok(())
+        }
+
+        fn ensure_escrow_not_leased(&self, escrow: &Escrow) -> Result<()> {
+            if escrow.status != LeaseStatus::Open {
+                return Err(EscrowError::AlreadyLeased);
+            }
+            Ok(())
+        }
+
+        fn ensure_escrow_leased(&self, escrow: &Escrow) -> Result<()> {
+            if escrow.status != LeaseStatus::Active {
+                return Err(EscrowError::NotLeased);
+            }
+            Ok(())
+        }
+
+        fn ensure_caller_is_renter(&self, escrow: &Escrow, caller: &AccountId) -> Result<()> {
+            if escrow.renter != Some(*caller) {
+                return Err(EscrowError::NotRenter);
+            }
+            Ok(())
+        }
+
+        fn ensure_not_paused(&self) -> Result<()> {
+            if self.paused {
+                return Err(EscrowError::Paused);
+            }
+            Ok(())
+        }
+
+        fn ensure_accepting_leases(&self) -> Result<()> {
+            if self.decommissioned.get_or_default()
+                || self.decommission_at.get_or_default().is_some()
+            {
+                return Err(EscrowError::Decommissioned);
+            }
+            Ok(())
+        }
+
+        fn ensure_decommission_unlocked(&self) -> Result<()> {
+            if self.decommissioned.get_or_default() {
+                return Err(EscrowError::Decommissioned);
+            }
+            let executable_at = self
+                .decommission_at
+                .get_or_default()
+                .ok_or(EscrowError::DecommissionNotScheduled)?;
+            if self.now() < executable_at {
+                return Err(EscrowError::TimelockActive);
+            }
+            Ok(())
+        }
+
+        fn ensure_caller_is_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(EscrowError::NotOwner);
+            }
+            Ok(())
+        }
+
+        /// Decisions on the shared pools are left to the owner or an arbiter
+        /// from the registry, since the parties pick an escrow's own arbiter.
+        fn ensure_owner_or_registered_arbiter(&self, caller: &AccountId) -> Result<()> {
+            if *caller != self.owner && !self.arbiter_registry.get_or_default().contains(caller) {
+                return Err(EscrowError::NotArbiter);
+            }
+            Ok(())
+        }
+
+        fn ensure_caller_is_landlord(&self, escrow: &Escrow, caller: &AccountId) -> Result<()> {
+            if *caller != escrow.landlord {
+                return Err(EscrowError::NotLandlord);
+            }
+            Ok(())
+        }
+
+        fn update_reputation(&mut self, account: AccountId, update: impl FnOnce(&mut Reputation)) {
+            let mut reputation = self.reputations.get(&account).unwrap_or_default();
+            update(&mut reputation);
+            self.reputations.insert(account, &reputation);
+        }
+
+        fn log_activity(&mut self, escrow_id: Hash, activity: Activity) {
+            let mut entries = self.activity_log.get(&escrow_id).unwrap_or_default();
+            entries.push(ActivityEntry {
+                timestamp: self.now(),
+                activity,
+            });
+            self.activity_log.insert(escrow_id, &entries);
+        }
+
+        /// Reads the escrow's parties and hands out the next sequence number,
+        /// so indexers can spot a missed event per escrow.
+        fn event_header(&mut self, escrow_id: Hash) -> EventHeader {
+            let sequence = self.event_counts.get(&escrow_id).unwrap_or(0);
+            self.event_counts
+                .insert(escrow_id, &sequence.saturating_add(1));
+            let escrow = self.escrows.get(&escrow_id).unwrap_or_default();
+            EventHeader {
+                landlord: escrow.landlord,
+                renter: escrow.renter,
+                sequence,
+            }
+        }
+
+        fn emit_counter_decision(
+            &mut self,
+            escrow_id: Hash,
+            offered_by: AccountId,
+            accepted: bool,
+        ) {
+            let header = self.event_header(escrow_id);
+            self.env().emit_event(CounterOfferDecided {
+                escrow_id,
+                landlord: header.landlord,
+                renter: header.renter,
+                sequence: header.sequence,
+                offered_by,
+                accepted,
+                schema_version: EVENT_SCHEMA_VERSION,
+            });
+        }
+
+        fn record_payment(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            payer: AccountId,
+            amount: Balance,
+            kind: PaymentKind,
+        ) {
+            let index = self.payment_count(escrow_id);
+            self.payment_records.insert(
+                (escrow_id, index),
+                &PaymentRecord {
+                    payer,
+                    amount,
+                    timestamp: self.now(),
+                    period: escrow.periods_paid,
+                    kind,
+                },
+            );
+            self.payment_counts
+                .insert(escrow_id, &index.saturating_add(1));
+        }
+
+        fn ensure_batch_size(len: usize) -> Result<()> {
+            if len > MAX_BATCH_SIZE {
+                return Err(EscrowError::BatchTooLarge);
+            }
+            Ok(())
+        }
+
+        /// Like `ensure_caller_is_landlord`, but also accepts the escrow's
+        /// property manager.
+        fn ensure_caller_can_manage(
+            &self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            caller: &AccountId,
+        ) -> Result<()> {
+            if self.managers.get(&escrow_id) == Some(*caller) {
+                return Ok(());
+            }
+            self.ensure_caller_is_landlord(escrow, caller)
+        }
+
+        fn is_co_owner(&self, escrow_id: Hash, account: &AccountId) -> bool {
+            self.joint_ownerships
+                .get(&escrow_id)
+                .map_or(false, |ownership| ownership.co_owners.contains(account))
+        }
+
+        /// Lets `caller` take a landlord-gated action: the landlord alone, or
+        /// for a jointly owned escrow any owner once the action has enough
+        /// approvals, which the call then uses up.
+        fn ensure_landlord_action(
+            &mut self,
+            escrow_id: Hash,
+            escrow: &Escrow,
+            caller: &AccountId,
+            action: LandlordAction,
+        ) -> Result<()> {
+            let ownership = match self.joint_ownerships.get(&escrow_id) {
+                Some(ownership) => ownership,
+                None => return self.ensure_caller_is_landlord(escrow, caller),
+            };
+            if *caller != escrow.landlord && !ownership.co_owners.contains(caller) {
+                return Err(EscrowError::NotLandlord);
+            }
+            let action_id = self.landlord_action_id(escrow_id, action);
+            if self.action_approvals(escrow_id, action_id).len() < usize::from(ownership.threshold)
+            {
+                return Err(EscrowError::ApprovalsMissing);
+            }
+            self.action_approvals.remove((escrow_id, action_id));
+            Ok(())
+        }
+
+        fn ensure_valid_ownership(landlord: &AccountId, terms: &LeaseTerms) -> Result<()> {
+            let ownership = match &terms.joint_ownership {
+                Some(ownership) => ownership,
+                None => return Ok(()),
+            };
+            let owners = ownership.co_owners.len() + 1;
+            let distinct = ownership
+                .co_owners
+                .iter()
+                .enumerate()
+                .all(|(i, a)| a != landlord && !ownership.co_owners[..i].contains(a));
+            if owners == 1
+                || !distinct
+                || ownership.threshold == 0
+                || usize::from(ownership.threshold) > owners
+            {
+                return Err(EscrowError::InvalidJointOwnership);
+            }
+            Ok(())
+        }
+
+        /// Rent for the current period after any reimbursement credit, plus
+        /// CAM and percentage rent charges and the late fee if one applies.
+        fn amount_due(&self, escrow: &Escrow) -> Result<Balance> {
+            self.scheduled_amount(escrow)?
+                .try_add(self.late_fee(escrow))
+        }
+
+        fn scheduled_amount(&self, escrow: &Escrow) -> Result<Balance> {
+            escrow
+                .rent_amount
+                .saturating_sub(escrow.rent_credit)
+                .try_add(escrow.cam_estimate)?
+                .try_add(escrow.cam_charge)?
+                .try_add(escrow.percentage_rent_due)
+        }
+
+        /// Moves the percentage rent of an unchallenged sales report into
+        /// `percentage_rent_due` once its challenge window has passed. Returns
+        /// whether a report was applied; the caller removes it from storage.
+        fn apply_sales_report(&self, escrow_id: Hash, escrow: &mut Escrow) -> Result<bool> {
+            let report = match self.sales_reports.get(&escrow_id) {
+                Some(report) => report,
+                None => return Ok(false),
+            };
+            if self.now() < report.submitted_at.saturating_add(SALES_CHALLENGE_WINDOW) {
+                return Ok(false);
+            }
+            let above = report.sales_amount.saturating_sub(escrow.sales_breakpoint);
+            escrow.percentage_rent_due = escrow
+                .percentage_rent_due
+                .try_add(self.allocate(above, escrow.percentage_rent_bps))?;
+            Ok(true)
+        }
+
+        fn ensure_rent_amount_paid(&self, escrow: &Escrow, value: Balance) -> Result<()> {
+            if value < self.amount_due(escrow)? {
+                return Err(EscrowError::InsufficientRent);
+            }
+            Ok(())
+        }
+
+        fn ensure_lease_duration_passed(&self, escrow: &Escrow) -> Result<()> {
+            let current_time = self.now();
+            if escrow
+                .lease_start_time
+                .saturating_add(escrow.lease_duration)
+                > current_time
+            {
+                return Err(EscrowError::LeaseNotExpired);
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::primitives::AccountId as AccountIdType;
+
+        /// Moves the block timestamp the contract reads through `now`.
+        struct TestClock;
+
+        impl TestClock {
+            fn now() -> u64 {
+                ink::env::block_timestamp::<Environment>()
+            }
+
+            fn set(timestamp: u64) {
+                ink::env::test::set_block_timestamp::<Environment>(timestamp);
+            }
+
+            fn advance(by: u64) {
+                Self::set(Self::now() + by);
+            }
+
+            /// Advances by `periods` payment intervals of `interval` each.
+            fn advance_periods(periods: u64, interval: u64) {
+                Self::advance(periods * interval);
+            }
+        }
+
+        fn terms(rent_amount: Balance, lease_duration: u64) -> LeaseTerms {
+            LeaseTerms {
+                rent_amount,
+                lease_duration,
+                security_deposit: 0,
+                payment_interval: lease_duration,
+                grace_period: 0,
+                late_fee_bps: 0,
+                arbiter: None,
+                payment_token: None,
+                early_termination_penalty_periods: 0,
+                cam_estimate: 0,
+                percentage_rent_bps: 0,
+                sales_breakpoint: 0,
+                co_renters: Vec::new(),
+                rent_peg: None,
+                agreement_hash: Hash::default(),
+                metadata_uri: Vec::new(),
+                deposit_plan: None,
+                jurisdiction: 0,
+                allow_partial: false,
+                insurance: None,
+                stream_rent: false,
+                building: Hash::default(),
+                protection: None,
+                yield_renter_bps: None,
+                joint_ownership: None,
+            }
+        }
+
+        #[ink::test]
+        fn create_escrow_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let rent_amount = 100;
+            let lease_duration = 10;
+
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+
+            assert_eq!(ink::env::test::recorded_events().count(), 1);
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.renter, Some(AccountIdType::from([0x0; 32])));
+            assert_eq!(escrow.landlord, landlord);
+            assert_eq!(escrow.rent_amount, rent_amount);
+            assert_eq!(escrow.lease_duration, lease_duration);
+            assert_eq!(escrow.lease_start_time, 0);
+            assert_eq!(escrow.escrow_balance, 0);
+            assert_eq!(escrow.status, LeaseStatus::Open);
+            assert_eq!(escrow.security_deposit, 0);
+            assert_eq!(escrow.deposit_balance, 0);
+            assert_eq!(escrow.payment_interval, lease_duration);
+            assert_eq!(escrow.periods_paid, 0);
+        }
+
+        #[ink::test]
+        fn create_escrow_generates_unique_ids() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let first = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            let second = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_ne!(first, second);
+            assert_eq!(contract.escrow_count(), 2);
+
+            let next = contract.next_escrow_id(renter, landlord).unwrap();
+            contract
+                .escrow_nonce
+                .set(&(contract.escrow_nonce.get_or_default() - 1));
+            contract.store_escrow(next, &Escrow::default());
+            assert_eq!(
+                contract.create_escrow(landlord, terms(100, 10)),
+                Err(EscrowError::IdExists)
+            );
+        }
+
+        #[ink::test]
+        fn events_carry_schema_version() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+
+            let event = ink::env::test::recorded_events().last().unwrap();
+            let created = <EscrowCreated as scale::Decode>::decode(&mut &event.data[..]).unwrap();
+            assert_eq!(created.schema_version, EVENT_SCHEMA_VERSION);
+            assert_eq!(created.escrow_id, escrow_id);
+            assert_eq!(created.landlord, landlord);
+        }
+
+        #[ink::test]
+        fn escrow_events_carry_parties_and_sequence() {
+            let mut contract = MyPSP34::new();
+            let renter = AccountIdType::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_eq!(contract.events_emitted(escrow_id), 1);
+
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.events_emitted(escrow_id), 2);
+
+            let event = ink::env::test::recorded_events().last().unwrap();
+            assert_eq!(event.topics.len(), 4);
+            let started = <LeaseStarted as scale::Decode>::decode(&mut &event.data[..]).unwrap();
+            assert_eq!(started.escrow_id, escrow_id);
+            assert_eq!(started.landlord, landlord);
+            assert_eq!(started.renter, Some(renter));
+            assert_eq!(started.sequence, 1);
+        }
+
+        #[ink::test]
+        fn create_escrow_fails_if_invalid_payment_interval() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 0,
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidPaymentInterval)
+            );
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 11,
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidPaymentInterval)
+            );
+        }
+
+        #[ink::test]
+        fn rent_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let rent_amount = 100;
+            let lease_duration = 10;
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+            assert_eq!(contract.rent(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.status, LeaseStatus::Active);
+            assert_eq!(escrow.lease_start_time > 0, true);
+        }
+
+        #[ink::test]
+        fn pay_rent_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let rent_amount = 100;
+            let lease_duration = 10;
+            let renter = AccountIdType::from([3; 32]);
+            let rent_payment = 150;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(rent_payment);
+            assert_eq!(contract.pay_rent(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, rent_amount);
+            assert_eq!(escrow.overpayment, rent_payment - rent_amount);
+            // EscrowCreated, LeaseStarted, RentPaid
+            assert_eq!(ink::env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn partial_rent_payments_track_outstanding_balance() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_renewal_window(20).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let strict = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(strict).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(30);
+            assert_eq!(
+                contract.pay_partial_rent(strict, 0),
+                Err(EscrowError::PartialPaymentsNotAllowed)
+            );
+
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        allow_partial: true,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.pay_partial_rent(escrow_id, 0), Ok(()));
+            assert_eq!(contract.outstanding_balance(escrow_id), Ok(70));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.periods_paid, 0);
+            assert_eq!(escrow.overpayment, 30);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.propose_renewal(escrow_id, 10, 100).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.accept_renewal(escrow_id),
+                Err(EscrowError::RentInArrears)
+            );
+
+            ink::env::test::set_value_transferred::<Environment>(70);
+            assert_eq!(contract.pay_partial_rent(escrow_id, 0), Ok(()));
+            ink::env::test::set_value_transferred::<Environment>(0);
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.periods_paid, 1);
+            assert_eq!(escrow.escrow_balance, 100);
+            assert_eq!(escrow.overpayment, 0);
+            assert_eq!(contract.outstanding_balance(escrow_id), Ok(0));
+            assert_eq!(contract.accept_renewal(escrow_id), Ok(()));
+        }
+
+        #[ink::test]
+        fn auto_pay_draws_from_vault() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let keeper = AccountIdType::from([7; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(
+                contract.auto_pay(escrow_id),
+                Err(EscrowError::InsufficientVaultBalance)
+            );
+
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(150);
+            contract.fund_vault(None, 0).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+            assert_eq!(contract.vault_balance(renter, None), 150);
+
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(contract.auto_pay(escrow_id), Ok(()));
+            assert_eq!(
+                contract.auto_pay(escrow_id),
+                Err(EscrowError::PaymentNotDue)
+            );
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 100);
+            assert_eq!(escrow.periods_paid, 1);
+            assert_eq!(contract.vault_balance(renter, None), 50);
+            assert_eq!(contract.total_locked, 150);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.withdraw_from_vault(None, 51),
+                Err(EscrowError::InsufficientVaultBalance)
+            );
+            assert_eq!(contract.withdraw_from_vault(None, 50), Ok(()));
+            assert_eq!(contract.vault_balance(renter, None), 0);
+            assert_eq!(contract.total_locked, 100);
+        }
+
+        #[ink::test]
+        fn auto_pay_respects_vault_limits() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let keeper = AccountIdType::from([7; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        ..terms(100, 20)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(1_000);
+            contract.fund_vault(None, 0).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            contract.set_vault_paused(true).unwrap();
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(contract.auto_pay(escrow_id), Err(EscrowError::VaultPaused));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.set_vault_paused(false).unwrap();
+            contract.set_auto_pay_cap(escrow_id, Some(99)).unwrap();
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(
+                contract.auto_pay(escrow_id),
+                Err(EscrowError::SpendingLimitExceeded)
+            );
+
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.set_auto_pay_cap(escrow_id, None).unwrap();
+            contract.set_vault_period_cap(10, Some(150)).unwrap();
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(contract.auto_pay(escrow_id), Ok(()));
+            TestClock::set(5);
+            assert_eq!(
+                contract.auto_pay(escrow_id),
+                Err(EscrowError::SpendingLimitExceeded)
+            );
+            TestClock::set(10);
+            assert_eq!(contract.auto_pay(escrow_id), Ok(()));
+            assert_eq!(contract.vault_policy(renter).spent_in_period, 100);
+            assert_eq!(contract.vault_balance(renter, None), 800);
+        }
+
+        #[ink::test]
+        fn assistance_program_covers_part_of_rent() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let sponsor = AccountIdType::from([5; 32]);
+            let local = LeaseTerms {
+                payment_interval: 5,
+                jurisdiction: 7,
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(sponsor);
+            assert_eq!(
+                contract.create_assistance_program(ProgramTerms {
+                    payment_token: None,
+                    coverage_bps: 5_000,
+                    jurisdiction: 7,
+                    min_rent: 200,
+                    max_rent: 50,
+                    max_enrollments: 1,
+                }),
+                Err(EscrowError::InvalidProgram)
+            );
+            let program_id = contract
+                .create_assistance_program(ProgramTerms {
+                    payment_token: None,
+                    coverage_bps: 5_000,
+                    jurisdiction: 7,
+                    min_rent: 50,
+                    max_rent: 200,
+                    max_enrollments: 1,
+                })
+                .unwrap();
+            ink::env::test::set_value_transferred::<Environment>(1_000);
+            contract.fund_program(program_id, 0).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, local.clone()).unwrap();
+            let elsewhere = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        jurisdiction: 8,
+                        ..local.clone()
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                contract.enroll_in_program(program_id, elsewhere),
+                Err(EscrowError::NotEligible)
+            );
+            contract.enroll_in_program(program_id, escrow_id).unwrap();
+            assert_eq!(contract.enrolled_program(escrow_id), Some(program_id));
+            let second = contract.create_escrow(landlord, local).unwrap();
+            assert_eq!(
+                contract.enroll_in_program(program_id, second),
+                Err(EscrowError::EnrollmentFull)
+            );
+
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.rent_due(escrow_id).unwrap().1, 50);
+            ink::env::test::set_value_transferred::<Environment>(50);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 100);
+            let program = contract.assistance_program(program_id).unwrap();
+            assert_eq!(program.balance, 950);
+            assert_eq!(program.total_disbursed, 50);
+
+            assert_eq!(
+                contract.terminate_program(program_id),
+                Err(EscrowError::NotSponsor)
+            );
+            ink::env::test::set_caller::<Environment>(sponsor);
+            contract.terminate_program(program_id).unwrap();
+            assert!(!contract.assistance_program(program_id).unwrap().active);
+            assert_eq!(contract.rent_due(escrow_id).unwrap().1, 100);
+        }
+
+        #[ink::test]
+        fn pay_rent_fails_if_insufficient_rent() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(50);
+
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::InsufficientRent)
+            );
+        }
+
+        #[ink::test]
+        fn pay_rent_tracks_periods() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let rent_amount = 100;
+            let payment_interval = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: payment_interval,
+                        ..terms(rent_amount, 30)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            assert_eq!(contract.rent_due(escrow_id), Ok((start, rent_amount)));
+
+            ink::env::test::set_value_transferred::<Environment>(rent_amount);
+            for _ in 0..3 {
+                contract.pay_rent(escrow_id).unwrap();
+            }
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.periods_paid, 3);
+            assert_eq!(escrow.next_due_timestamp, start + 3 * payment_interval);
+            assert_eq!(contract.rent_due(escrow_id), Ok((start + 30, 0)));
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::LeaseFullyPaid)
+            );
+        }
+
+        #[ink::test]
+        fn claim_rent_withdraws_matured_periods() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let rent_amount = 100;
+            let payment_interval = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: payment_interval,
+                        ..terms(rent_amount, 30)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(rent_amount);
+            contract.pay_rent(escrow_id).unwrap();
+            contract.pay_rent(escrow_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.claim_rent(escrow_id),
+                Err(EscrowError::NothingToClaim)
+            );
+
+            // One full period has elapsed
+            TestClock::advance_periods(1, payment_interval);
+            assert_eq!(contract.claim_rent(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.periods_claimed, 1);
+            assert_eq!(escrow.escrow_balance, rent_amount);
+            assert_eq!(
+                contract.claim_rent(escrow_id),
+                Err(EscrowError::NothingToClaim)
+            );
+        }
+
+        #[ink::test]
+        fn streamed_rent_accrues_by_the_second() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        stream_rent: true,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+            let start = TestClock::now();
+
+            TestClock::set(start + 4);
+            assert_eq!(contract.earned_rent(escrow_id), Ok(40));
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.claim_rent(escrow_id),
+                Err(EscrowError::RentIsStreamed)
+            );
+            assert_eq!(contract.withdraw_earned(escrow_id), Ok(()));
+            assert_eq!(contract.withdrawn_by_landlord(escrow_id), 40);
+            assert_eq!(
+                contract.withdraw_earned(escrow_id),
+                Err(EscrowError::NothingToClaim)
+            );
+
+            TestClock::set(start + 6);
+            assert_eq!(contract.earned_rent(escrow_id), Ok(20));
+            contract.terminate_early(escrow_id).unwrap();
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 40);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.withdraw_unearned(escrow_id), Ok(()));
+            assert_eq!(
+                contract.withdraw_unearned(escrow_id),
+                Err(EscrowError::NothingToClaim)
+            );
+        }
+
+        #[ink::test]
+        fn create_escrow_fails_if_invalid_late_fee() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        late_fee_bps: 10_001,
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidLateFee)
+            );
+        }
+
+        #[ink::test]
+        fn amend_agreement_needs_both_parties() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let signed = Hash::from([4; 32]);
+            let amended = Hash::from([5; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        metadata_uri: vec![b'x'; MAX_METADATA_URI_LEN + 1],
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::MetadataTooLong)
+            );
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        agreement_hash: signed,
+                        metadata_uri: b"ipfs://lease".to_vec(),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                contract.agreement(escrow_id).unwrap().agreement_hash,
+                signed
+            );
+
+            contract.amend_agreement(escrow_id, amended).unwrap();
+            assert_eq!(
+                contract.pending_amendment(escrow_id),
+                Some(AgreementAmendment {
+                    new_hash: amended,
+                    proposed_by: renter,
+                })
+            );
+            contract.amend_agreement(escrow_id, amended).unwrap();
+            assert_eq!(
+                contract.agreement(escrow_id).unwrap().agreement_hash,
+                signed
+            );
+
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([9; 32]));
+            assert_eq!(
+                contract.amend_agreement(escrow_id, amended),
+                Err(EscrowError::NotParty)
+            );
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.amend_agreement(escrow_id, amended).unwrap();
+            let agreement = contract.agreement(escrow_id).unwrap();
+            assert_eq!(agreement.agreement_hash, amended);
+            assert_eq!(agreement.metadata_uri, b"ipfs://lease".to_vec());
+            assert_eq!(contract.pending_amendment(escrow_id), None);
+        }
+
+        #[ink::test]
+        fn rent_peg_is_validated_and_stored() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let peg = RentPeg {
+                price_oracle: AccountIdType::from([8; 32]),
+                rent_fiat_amount: 1_200,
+            };
+
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        rent_peg: Some(RentPeg {
+                            rent_fiat_amount: 0,
+                            ..peg
+                        }),
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidRentPeg)
+            );
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        rent_peg: Some(peg),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            assert_eq!(contract.rent_peg(escrow_id), Some(peg));
+            let (_, summary) = contract.terms_digest(escrow_id).unwrap();
+            assert_eq!(summary.terms.rent_peg, Some(peg));
+
+            let unpegged = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_eq!(contract.rent_peg(unpegged), None);
+        }
+
+        #[ink::test]
+        fn pay_rent_requires_late_fee_after_grace_period() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let rent_amount = 100;
+            let grace_period = 5;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        grace_period,
+                        late_fee_bps: 500,
+                        ..terms(rent_amount, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+
+            // Still inside the grace period
+            let start = TestClock::now();
+            TestClock::set(start + grace_period);
+            assert_eq!(contract.compute_late_fee(escrow_id), Ok(0));
+
+            TestClock::set(start + grace_period + 1);
+            assert_eq!(contract.compute_late_fee(escrow_id), Ok(5));
+
+            ink::env::test::set_value_transferred::<Environment>(rent_amount);
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::InsufficientRent)
+            );
+
+            ink::env::test::set_value_transferred::<Environment>(rent_amount + 5);
+            assert_eq!(contract.pay_rent(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, rent_amount);
+            assert_eq!(escrow.late_fees, 5);
+        }
+
+        #[ink::test]
+        fn list_property_works() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.list_property(escrow_id, terms(100, 10)), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.renter, None);
+            assert_eq!(escrow.landlord, landlord);
+            assert_eq!(escrow.status, LeaseStatus::Open);
+        }
+
+        #[ink::test]
+        fn accept_lease_works() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let security_deposit = 200;
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        security_deposit,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(security_deposit - 1);
+            assert_eq!(
+                contract.accept_lease(escrow_id),
+                Err(EscrowError::InsufficientDeposit)
+            );
+            ink::env::test::set_value_transferred::<Environment>(security_deposit);
+            assert_eq!(contract.accept_lease(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.renter, Some(renter));
+            assert_eq!(escrow.status, LeaseStatus::Active);
+            assert_eq!(escrow.deposit_balance, security_deposit);
+
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([4; 32]));
+            assert_eq!(contract.accept_lease(escrow_id), Err(EscrowError::NotOpen));
+        }
+
+        #[ink::test]
+        fn accepted_counter_offer_reserves_listing_at_new_terms() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let other = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.list_property(escrow_id, terms(100, 20)).unwrap();
+            assert_eq!(
+                contract.accept_counter(escrow_id),
+                Err(EscrowError::NoCounterOffer)
+            );
+
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.counter_offer(escrow_id, 90, 40).unwrap();
+            ink::env::test::set_caller::<Environment>(other);
+            assert_eq!(
+                contract.counter_offer(escrow_id, 80, 40),
+                Err(EscrowError::CounterOfferPending)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.accept_counter(escrow_id).unwrap();
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!((escrow.rent_amount, escrow.lease_duration), (90, 40));
+
+            ink::env::test::set_caller::<Environment>(other);
+            assert_eq!(
+                contract.accept_lease(escrow_id),
+                Err(EscrowError::CounterOfferPending)
+            );
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.accept_lease(escrow_id), Ok(()));
+            assert_eq!(contract.pending_counter_offer(escrow_id), None);
+        }
+
+        #[ink::test]
+        fn deposit_can_be_paid_in_installments() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let plan = DepositPlan {
+                upfront: 100,
+                installments: 2,
+                interval: 4,
+            };
+            let financed = LeaseTerms {
+                security_deposit: 300,
+                grace_period: 1,
+                late_fee_bps: 1_000,
+                deposit_plan: Some(plan),
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        deposit_plan: Some(DepositPlan {
+                            interval: 6,
+                            ..plan
+                        }),
+                        ..financed.clone()
+                    }
+                ),
+                Err(EscrowError::InvalidDepositPlan)
+            );
+            contract.list_property(escrow_id, financed).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.accept_lease(escrow_id).unwrap();
+            let start = TestClock::now();
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_balance, 100);
+            assert_eq!(
+                contract.deposit_installment_due(escrow_id),
+                Ok((start + 4, 100))
+            );
+
+            // A missed installment picks up the rent's late fee.
+            TestClock::set(start + 6);
+            assert_eq!(
+                contract.deposit_installment_due(escrow_id),
+                Ok((start + 4, 110))
+            );
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(
+                contract.pay_deposit_installment(escrow_id),
+                Err(EscrowError::InsufficientDeposit)
+            );
+            ink::env::test::set_value_transferred::<Environment>(110);
+            contract.pay_deposit_installment(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_deposit_installment(escrow_id).unwrap();
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_balance, 300);
+            assert_eq!(escrow.late_fees, 10);
+            assert_eq!(
+                contract
+                    .deposit_schedule(escrow_id)
+                    .unwrap()
+                    .installments_paid,
+                2
+            );
+            assert_eq!(contract.deposit_installment_due(escrow_id), Ok((0, 0)));
+            assert_eq!(
+                contract.pay_deposit_installment(escrow_id),
+                Err(EscrowError::NoInstallmentDue)
+            );
+        }
+
+        #[ink::test]
+        fn terms_digest_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let listed = LeaseTerms {
+                security_deposit: 200,
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(Hash::from([1; 32]), listed.clone())
+                .unwrap();
+            contract
+                .list_property(Hash::from([2; 32]), listed.clone())
+                .unwrap();
+            contract
+                .list_property(
+                    Hash::from([3; 32]),
+                    LeaseTerms {
+                        rent_amount: 101,
+                        ..listed.clone()
+                    },
+                )
+                .unwrap();
+
+            let (digest, summary) = contract.terms_digest(Hash::from([1; 32])).unwrap();
+            assert_eq!(summary.escrow_id, Hash::from([1; 32]));
+            assert_eq!(summary.landlord, landlord);
+            assert_eq!(summary.terms, listed);
+            assert_eq!(
+                contract.terms_digest(Hash::from([1; 32])).unwrap().0,
+                digest
+            );
+
+            // The digest binds the escrow id as well as the terms.
+            assert_ne!(
+                contract.terms_digest(Hash::from([2; 32])).unwrap().0,
+                digest
+            );
+            assert_ne!(
+                contract.terms_digest(Hash::from([3; 32])).unwrap().0,
+                contract.terms_digest(Hash::from([1; 32])).unwrap().0
+            );
+        }
+
+        #[ink::test]
+        fn get_escrow_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            assert_eq!(contract.get_escrow(Hash::from([1; 32])), None);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+
+            let view = contract.get_escrow(escrow_id).unwrap();
+            assert_eq!(view.escrow_id, escrow_id);
+            assert_eq!(view.renter, Some(renter));
+            assert_eq!(view.landlord, landlord);
+            assert_eq!(view.status, LeaseStatus::Active);
+            assert_eq!(view.rent_amount, 100);
+            assert_eq!(view.periods_claimed, 0);
+            assert_eq!(view.dispute_reason, None);
+            assert_eq!(view.rent_credit, 0);
+        }
+
+        #[ink::test]
+        fn encoded_escrow_covers_terms_not_balances() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            assert_eq!(
+                contract.encoded_escrow(Hash::from([1; 32])),
+                Err(EscrowError::EscrowNotFound)
+            );
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            let encoded = contract.encoded_escrow(escrow_id).unwrap();
+
+            type Canonical = (u8, Hash, AccountId, Option<AccountId>, u64, LeaseTerms);
+            let (version, id, decoded_landlord, decoded_renter, start, decoded_terms) =
+                <Canonical as scale::Decode>::decode(&mut &encoded[..]).unwrap();
+            assert_eq!(version, ESCROW_ENCODING_VERSION);
+            assert_eq!(id, escrow_id);
+            assert_eq!((decoded_landlord, decoded_renter), (landlord, Some(renter)));
+            assert_eq!(start, TestClock::now());
+            assert_eq!(decoded_terms, terms(100, 10));
+
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            assert_eq!(contract.encoded_escrow(escrow_id), Ok(encoded));
+        }
+
+        #[ink::test]
+        fn list_escrows_by_party_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let other_renter = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(Hash::from([2; 32]), terms(100, 10))
+                .unwrap();
+
+            assert_eq!(
+                contract.list_escrows_by_landlord(landlord),
+                vec![escrow_id, Hash::from([2; 32])]
+            );
+            assert_eq!(contract.list_escrows_by_renter(renter), vec![escrow_id]);
+            assert_eq!(contract.list_escrows_by_renter(other_renter), vec![]);
+
+            ink::env::test::set_caller::<Environment>(other_renter);
+            contract.accept_lease(Hash::from([2; 32])).unwrap();
+            assert_eq!(
+                contract.list_escrows_by_renter(other_renter),
+                vec![Hash::from([2; 32])]
+            );
+        }
+
+        #[ink::test]
+        fn escrow_ids_pages_in_creation_order() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            for id in 1..=5u8 {
+                contract
+                    .list_property(Hash::from([id; 32]), terms(100, 10))
+                    .unwrap();
+            }
+            assert_eq!(
+                contract.list_property(Hash::from([3; 32]), terms(200, 10)),
+                Err(EscrowError::IdExists)
+            );
+
+            assert_eq!(contract.escrow_count(), 5);
+            assert_eq!(
+                contract.escrow_ids(0, 2),
+                Page {
+                    items: vec![Hash::from([1; 32]), Hash::from([2; 32])],
+                    next: Some(2),
+                }
+            );
+            assert_eq!(
+                contract.escrow_ids(4, 2),
+                Page {
+                    items: vec![Hash::from([5; 32])],
+                    next: None,
+                }
+            );
+            assert_eq!(contract.escrow_ids(5, 2).items, vec![]);
+        }
+
+        #[ink::test]
+        fn pages_are_capped_and_resume_from_cursor() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            for id in 0..=MAX_PAGE_SIZE {
+                let mut bytes = [1; 32];
+                bytes[..4].copy_from_slice(&id.to_le_bytes());
+                contract
+                    .list_property(Hash::from(bytes), terms(100, 10))
+                    .unwrap();
+            }
+
+            let first = contract.escrow_ids(0, u32::MAX);
+            assert_eq!(first.items.len(), MAX_PAGE_SIZE as usize);
+            assert_eq!(first.next, Some(MAX_PAGE_SIZE));
+            let rest = contract.escrow_ids(first.next.unwrap(), u32::MAX);
+            assert_eq!(rest.items.len(), 1);
+            assert_eq!(rest.next, None);
+        }
+
+        #[ink::test]
+        fn escrows_are_indexed_by_payment_token() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let token = AccountIdType::from([9; 32]);
+            let token_terms = LeaseTerms {
+                payment_token: Some(token),
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(Hash::from([1; 32]), terms(100, 10))
+                .unwrap();
+            contract
+                .list_property(Hash::from([2; 32]), token_terms.clone())
+                .unwrap();
+            contract
+                .list_property(Hash::from([3; 32]), token_terms.clone())
+                .unwrap();
+
+            assert_eq!(contract.escrow_count_by_token(None), 1);
+            assert_eq!(contract.escrow_count_by_token(Some(token)), 2);
+            assert_eq!(
+                contract.escrow_ids_by_token(Some(token), 1, 5).items,
+                vec![Hash::from([3; 32])]
+            );
+
+            assert_eq!(
+                contract.list_property(Hash::from([1; 32]), token_terms),
+                Err(EscrowError::IdExists)
+            );
+            assert_eq!(contract.escrow_count_by_token(None), 1);
+            assert_eq!(contract.escrow_count_by_token(Some(token)), 2);
+        }
+
+        #[ink::test]
+        fn co_renters_must_all_accept_listing() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let alice = AccountIdType::from([3; 32]);
+            let bob = AccountIdType::from([4; 32]);
+            let shared = LeaseTerms {
+                security_deposit: 200,
+                co_renters: vec![(alice, 6_000), (bob, 4_000)],
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        co_renters: vec![(alice, 6_000), (bob, 3_000)],
+                        ..shared.clone()
+                    }
+                ),
+                Err(EscrowError::InvalidShares)
+            );
+            contract.list_property(escrow_id, shared).unwrap();
+
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(79);
+            assert_eq!(
+                contract.accept_lease(escrow_id),
+                Err(EscrowError::InsufficientDeposit)
+            );
+            ink::env::test::set_value_transferred::<Environment>(80);
+            assert_eq!(contract.accept_lease(escrow_id), Ok(()));
+            assert_eq!(
+                contract.accept_lease(escrow_id),
+                Err(EscrowError::AlreadySigned)
+            );
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Open));
+
+            ink::env::test::set_caller::<Environment>(alice);
+            ink::env::test::set_value_transferred::<Environment>(120);
+            assert_eq!(contract.accept_lease(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Active));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.renter, Some(alice));
+            assert_eq!(escrow.deposit_balance, 200);
+            assert_eq!(contract.list_escrows_by_renter(bob), vec![escrow_id]);
+
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.pay_rent(escrow_id), Ok(()));
+            assert_eq!(contract.contribution(escrow_id, bob), 180);
+        }
+
+        #[ink::test]
+        fn co_renters_must_all_approve_termination() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let alice = AccountIdType::from([3; 32]);
+            let bob = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        co_renters: vec![(alice, 5_000), (bob, 5_000)],
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(alice);
+            contract.accept_lease(escrow_id).unwrap();
+            ink::env::test::set_caller::<Environment>(bob);
+            contract.accept_lease(escrow_id).unwrap();
+
+            assert_eq!(contract.terminate_early(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Active));
+            ink::env::test::set_caller::<Environment>(alice);
+            assert_eq!(contract.terminate_early(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Terminated));
+        }
+
+        #[ink::test]
+        fn cancel_lease_delists_open_listing() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.list_property(escrow_id, terms(100, 10)).unwrap();
+            assert_eq!(contract.cancel_lease(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Terminated));
+        }
+
+        #[ink::test]
+        fn token_escrow_rejects_native_value() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let token = AccountIdType::from([8; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_token: Some(token),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.payment_token, Some(token));
+
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::UnexpectedNativeValue)
+            );
+            assert_eq!(
+                contract.pay_deposit(escrow_id),
+                Err(EscrowError::UnexpectedNativeValue)
+            );
+        }
+
+        #[ink::test]
+        fn lease_token_minted_on_start_and_burned_on_end() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let lease_duration = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(100, lease_duration))
+                .unwrap();
+            let id = MyPSP34::lease_token_id(escrow_id);
+            assert_eq!(PSP34::owner_of(&contract, id.clone()), None);
+
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(PSP34::owner_of(&contract, id.clone()), Some(renter));
+            assert_eq!(PSP34::balance_of(&contract, renter), 1);
+            assert_eq!(PSP34::total_supply(&contract), 1);
+
+            TestClock::advance(lease_duration + 1);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+
+            assert_eq!(PSP34::owner_of(&contract, id), None);
+            assert_eq!(PSP34::balance_of(&contract, renter), 0);
+            assert_eq!(PSP34::total_supply(&contract), 0);
+        }
+
+        #[ink::test]
+        fn stale_lease_tokens_are_burned_by_anyone() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let live = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(live).unwrap();
+            let stale = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(stale).unwrap();
+            // Left behind as if the escrow had settled without burning it.
+            let mut escrow = contract.get_escrow_or_revert(stale).unwrap();
+            escrow.status = LeaseStatus::Settled;
+            contract.store_escrow(stale, &escrow);
+
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([9; 32]));
+            assert_eq!(contract.burn_stale_tokens(vec![live, stale]), Ok(1));
+            assert_eq!(
+                PSP34::owner_of(&contract, MyPSP34::lease_token_id(stale)),
+                None
+            );
+            assert_eq!(
+                PSP34::owner_of(&contract, MyPSP34::lease_token_id(live)),
+                Some(renter)
+            );
+            assert_eq!(PSP34::balance_of(&contract, renter), 1);
+        }
+
+        #[ink::test]
+        fn lease_token_exposes_metadata_attributes() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let attribute = |contract: &MyPSP34, id: &Id, key: &[u8]| {
+                contract.get_attribute(id.clone(), key.to_vec())
+            };
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        metadata_uri: b"ipfs://lease".to_vec(),
+                        ..terms(1_500, 10)
+                    },
+                )
+                .unwrap();
+            let id = MyPSP34::lease_token_id(escrow_id);
+            assert_eq!(attribute(&contract, &id, b"rent_band"), None);
+
+            let start = TestClock::now();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                attribute(&contract, &id, b"rent_band"),
+                Some(b"1000-9999".to_vec())
+            );
+            assert_eq!(
+                attribute(&contract, &id, b"start"),
+                Some(start.to_string().into_bytes())
+            );
+            assert_eq!(
+                attribute(&contract, &id, b"end"),
+                Some((start + 10).to_string().into_bytes())
+            );
+            assert_eq!(
+                attribute(&contract, &id, b"property_cid"),
+                Some(b"ipfs://lease".to_vec())
+            );
+            assert_eq!(attribute(&contract, &id, b"unknown"), None);
+            assert_eq!(
+                attribute(&contract, &contract.collection_id(), b"name"),
+                Some(b"Rental lease".to_vec())
+            );
+        }
+
+        #[ink::test]
+        fn lease_token_transfer_requires_landlord_approval() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let assignee = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            let id = MyPSP34::lease_token_id(escrow_id);
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                PSP34::transfer(&mut contract, assignee, id.clone(), Vec::new()),
+                Err(PSP34Error::NotApproved)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.approve_assignment(escrow_id, assignee), Ok(()));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                PSP34::transfer(&mut contract, assignee, id.clone(), Vec::new()),
+                Ok(())
+            );
+            assert_eq!(PSP34::owner_of(&contract, id), Some(assignee));
+            assert_eq!(PSP34::balance_of(&contract, renter), 0);
+            assert_eq!(PSP34::balance_of(&contract, assignee), 1);
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.renter, Some(assignee));
+        }
+
+        #[ink::test]
+        fn tenancy_weight_is_checkpointed_per_block() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let building = Hash::from([6; 32]);
+            let in_building = LeaseTerms {
+                building,
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let first = contract
+                .create_escrow(landlord, in_building.clone())
+                .unwrap();
+            let second = contract.create_escrow(landlord, in_building).unwrap();
+            let elsewhere = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(first).unwrap();
+            contract.rent(elsewhere).unwrap();
+            assert_eq!(
+                contract.tenancy_weight_at(building, renter, 0),
+                Err(EscrowError::FutureBlock)
+            );
+
+            ink::env::test::advance_block::<Environment>();
+            contract.rent(second).unwrap();
+            ink::env::test::advance_block::<Environment>();
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.terminate_early(first).unwrap();
+            ink::env::test::advance_block::<Environment>();
+
+            assert_eq!(contract.tenancy_weight_at(building, renter, 0), Ok(1));
+            assert_eq!(contract.tenancy_weight_at(building, renter, 1), Ok(2));
+            assert_eq!(contract.tenancy_weight_at(building, renter, 2), Ok(1));
+            assert_eq!(contract.total_tenancy_at(building, 1), Ok(2));
+            assert_eq!(contract.tenancy_weight_at(building, landlord, 1), Ok(0));
+        }
+
+        #[ink::test]
+        fn reputation_tracks_payments_and_completed_leases() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.reputation_of(renter).on_time_bps, None);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+
+            assert_eq!(
+                contract.reputation_of(renter),
+                ReputationView {
+                    account: renter,
+                    leases_completed: 1,
+                    on_time_payments: 1,
+                    late_payments: 0,
+                    disputes_lost: 0,
+                    on_time_bps: Some(10_000),
+                }
+            );
+            assert_eq!(contract.reputation_of(landlord).leases_completed, 1);
+        }
+
+        #[ink::test]
+        fn protection_fees_fund_claims_on_zero_deposit_leases() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let plan = ProtectionPlan { fee: 5, cover: 8 };
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 50,
+                        protection: Some(plan),
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidProtectionPlan)
+            );
+            // Two periods pay in 10 in fees, so the cover cannot exceed it.
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        protection: Some(ProtectionPlan { fee: 5, cover: 11 }),
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidProtectionPlan)
+            );
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        protection: Some(plan),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.rent_due(escrow_id).unwrap().1, 105);
+            ink::env::test::set_value_transferred::<Environment>(105);
+            contract.pay_rent(escrow_id).unwrap();
+            contract.pay_rent(escrow_id).unwrap();
+            assert_eq!(contract.protection_pool(None), 10);
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 200);
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            let reason = Hash::from([9; 32]);
+            assert_eq!(
+                contract.claim_protection(escrow_id, 9, reason),
+                Err(EscrowError::ClaimExceedsCover)
+            );
+            assert_eq!(contract.claim_protection(escrow_id, 8, reason), Ok(()));
+            assert_eq!(
+                contract.claim_protection(escrow_id, 1, reason),
+                Err(EscrowError::ProtectionAlreadyClaimed)
+            );
+            assert_eq!(contract.protection_pool(None), 10);
+            assert_eq!(contract.lease_ended(escrow_id), Ok(()));
+            assert_eq!(
+                contract.execute_protection_claim(escrow_id),
+                Err(EscrowError::TimelockActive)
+            );
+
+            // A disputed claim waits for the owner or a registry arbiter.
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.dispute_protection_claim(escrow_id), Ok(()));
+            TestClock::advance(PROTECTION_CHALLENGE_WINDOW);
+            assert_eq!(
+                contract.execute_protection_claim(escrow_id),
+                Err(EscrowError::ProtectionClaimDisputed)
+            );
+            assert_eq!(
+                contract.decide_protection_claim(escrow_id, true),
+                Err(EscrowError::NotArbiter)
+            );
+            let owner = contract.owner();
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.decide_protection_claim(escrow_id, true), Ok(()));
+            assert_eq!(contract.protection_claim(escrow_id), Some(8));
+            assert_eq!(contract.pending_protection_claim(escrow_id), None);
+            assert_eq!(contract.protection_pool(None), 2);
+        }
+
+        #[ink::test]
+        fn protection_claim_is_capped_by_fees_paid_in() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            contract.protection_pools.insert(None, &1_000);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        protection: Some(ProtectionPlan { fee: 5, cover: 10 }),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(105);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            let reason = Hash::from([9; 32]);
+            assert_eq!(
+                contract.claim_protection(escrow_id, 6, reason),
+                Err(EscrowError::ClaimExceedsCover)
+            );
+            assert_eq!(contract.claim_protection(escrow_id, 5, reason), Ok(()));
+            TestClock::advance(PROTECTION_CHALLENGE_WINDOW);
+            assert_eq!(contract.execute_protection_claim(escrow_id), Ok(()));
+            assert_eq!(contract.protection_claim(escrow_id), Some(5));
+            assert_eq!(contract.protection_pool(None), 1_000);
+        }
+
+        #[ink::test]
+        fn insurance_fund_pays_approved_default_claims() {
+            let mut contract = MyPSP34::new();
+            let owner = contract.owner();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let payment_interval = 10;
+            let arbiter = AccountIdType::from([4; 32]);
+            let policy = InsurancePolicy {
+                premium_bps: 1_000,
+                default_periods: 2,
+                cover_periods: 1,
+                max_payout: 60,
+            };
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval,
+                        arbiter: Some(arbiter),
+                        ..terms(100, 50)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.opt_into_insurance(escrow_id),
+                Err(EscrowError::NoInsurancePolicy)
+            );
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(
+                contract.set_insurance_policy(Some(InsurancePolicy {
+                    premium_bps: 0,
+                    ..policy
+                })),
+                Err(EscrowError::InvalidInsurancePolicy)
+            );
+            contract.set_insurance_policy(Some(policy)).unwrap();
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.opt_into_insurance(escrow_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            TestClock::advance_periods(2, payment_interval);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.claim_rent(escrow_id).unwrap();
+            assert_eq!(contract.insurance_fund(None), 20);
+            assert_eq!(
+                contract.claim_insurance(escrow_id),
+                Err(EscrowError::NotInDefault)
+            );
+
+            TestClock::advance_periods(1, payment_interval);
+            assert_eq!(contract.claim_insurance(escrow_id), Ok(()));
+            let claim = contract.insurance_claim(escrow_id).unwrap();
+            assert_eq!((claim.amount, claim.periods_in_arrears), (60, 2));
+            assert_eq!(
+                contract.claim_insurance(escrow_id),
+                Err(EscrowError::InsuranceClaimPending)
+            );
+
+            // The parties chose the escrow's arbiter, so it cannot decide.
+            for account in [renter, arbiter] {
+                ink::env::test::set_caller::<Environment>(account);
+                assert_eq!(
+                    contract.decide_insurance_claim(escrow_id, true),
+                    Err(EscrowError::NotArbiter)
+                );
+            }
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.decide_insurance_claim(escrow_id, false).unwrap();
+            assert_eq!(contract.insurance_claim(escrow_id), None);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.claim_insurance(escrow_id).unwrap();
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.decide_insurance_claim(escrow_id, true).unwrap();
+            assert_eq!(
+                contract.insurance_claim(escrow_id).unwrap().paid_out,
+                Some(20)
+            );
+            assert_eq!(contract.insurance_fund(None), 0);
+            assert_eq!(contract.insurance_subrogation(escrow_id), 20);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.claim_insurance(escrow_id),
+                Err(EscrowError::InsuranceAlreadyClaimed)
+            );
+
+            // The renter's next rent repays the fund before the landlord.
+            let balance_before = contract
+                .get_escrow_or_revert(escrow_id)
+                .unwrap()
+                .escrow_balance;
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, balance_before + 80);
+            assert_eq!(contract.insurance_fund(None), 20);
+            assert_eq!(contract.insurance_subrogation(escrow_id), 0);
+        }
+
+        #[ink::test]
+        fn invest_deposit_needs_strategy_and_opt_in() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        yield_renter_bps: Some(MAX_BPS + 1),
+                        ..terms(100, 10)
+                    }
+                ),
+                Err(EscrowError::InvalidYieldSplit)
+            );
+            let plain = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 50,
+                        yield_renter_bps: Some(5_000),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                contract.invest_deposit(escrow_id),
+                Err(EscrowError::NoYieldStrategy)
+            );
+
+            ink::env::test::set_caller::<Environment>(contract.owner());
+            contract
+                .set_yield_strategy(Some(AccountIdType::from([7; 32])))
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.rent(plain).unwrap();
+            assert_eq!(
+                contract.invest_deposit(plain),
+                Err(EscrowError::YieldNotEnabled)
+            );
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.invest_deposit(escrow_id),
+                Err(EscrowError::DepositNotPaid)
+            );
+            assert_eq!(contract.accrued_yield(escrow_id), Ok(0));
+        }
+
+        #[ink::test]
+        fn lease_ended_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let rent_amount = 100;
+            let lease_duration = 10;
+            let renter = AccountIdType::from([3; 32]);
+            let rent_payment = 150;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(rent_payment);
+            contract.pay_rent(escrow_id).unwrap();
+
+            // Increase block timestamp to simulate lease duration passed
+            TestClock::advance(lease_duration + 1);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Expired));
+            assert_eq!(contract.lease_ended(escrow_id), Ok(()));
+
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Settled));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 0);
+        }
+
+        #[ink::test]
+        fn anyone_settles_expired_lease_for_keeper_reward() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let keeper = AccountIdType::from([4; 32]);
+            contract.set_fee(1_000).unwrap();
+            contract.set_keeper_reward(5).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(keeper);
+            assert_eq!(
+                contract.settle_expired(escrow_id),
+                Err(EscrowError::LeaseNotExpired)
+            );
+            let now = TestClock::now();
+            TestClock::set(now + 11);
+            assert_eq!(contract.settle_expired(escrow_id), Ok(()));
+
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Settled));
+            // 10 of fees on the settlement, 5 of them paid to the keeper.
+            assert_eq!(contract.collected_fees(None), 5);
+        }
+
+        #[ink::test]
+        fn kyc_gate_requires_both_parties_verified() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let owner = contract.owner();
+            contract.set_require_kyc(true).unwrap();
+            contract.add_verified(renter).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(landlord, terms(100, 10)),
+                Err(EscrowError::NotVerified)
+            );
+            assert_eq!(contract.add_verified(landlord), Err(EscrowError::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.add_verified(landlord).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.remove_verified(landlord).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.rent(escrow_id), Err(EscrowError::NotVerified));
+
+            // The lease cannot be handed to an unverified assignee either.
+            let assignee = AccountIdType::from([4; 32]);
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.add_verified(landlord).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.approve_assignment(escrow_id, assignee),
+                Err(EscrowError::NotVerified)
+            );
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.add_verified(assignee).unwrap();
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.approve_assignment(escrow_id, assignee).unwrap();
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.remove_verified(assignee).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let id = MyPSP34::lease_token_id(escrow_id);
+            assert_eq!(
+                PSP34::transfer(&mut contract, assignee, id, Vec::new()),
+                Err(PSP34Error::Custom("assignee is not verified".into()))
+            );
+        }
+
+        #[ink::test]
+        fn payments_above_balance_cap_are_rejected() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            contract.set_max_escrow_balance(Some(150)).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.set_max_escrow_balance(None),
+                Err(EscrowError::NotOwner)
+            );
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 5,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::BalanceCapExceeded)
+            );
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.periods_paid, 1);
+        }
+
+        #[ink::test]
+        fn canary_limits_loosen_only_after_timelock() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let owner = contract.owner();
+            let tight = CanaryLimits {
+                max_total_locked: 150,
+                max_escrow_balance: 100,
+            };
+            contract.set_canary_limits(Some(tight)).unwrap();
+            assert_eq!(contract.canary_limits(), Some(tight));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let first = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            let second = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(first).unwrap();
+            contract.rent(second).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(first).unwrap();
+            assert_eq!(
+                contract.pay_rent(second),
+                Err(EscrowError::CanaryLimitExceeded)
+            );
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.set_canary_limits(None).unwrap();
+            assert_eq!(contract.canary_limits(), Some(tight));
+            assert_eq!(
+                contract.apply_canary_limits(),
+                Err(EscrowError::TimelockActive)
+            );
+            let now = TestClock::now();
+            TestClock::set(now + CANARY_TIMELOCK);
+            contract.apply_canary_limits().unwrap();
+            assert_eq!(contract.canary_limits(), None);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.pay_rent(second), Ok(()));
+        }
+
+        #[ink::test]
+        fn large_settlement_is_withdrawn_after_delay() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let owner = contract.owner();
+            contract.set_withdrawal_threshold(Some(100)).unwrap();
+            contract.set_withdrawal_delay(50).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+            let withdrawal = contract.pending_withdrawal(0).unwrap();
+            assert_eq!(withdrawal.to, landlord);
+            assert_eq!(withdrawal.amount, 100);
+            assert_eq!(contract.total_locked, 100);
+            assert_eq!(contract.pull_ledger_total(), 100);
+            assert_eq!(
+                contract.execute_withdrawal(0),
+                Err(EscrowError::TimelockActive)
+            );
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.veto_withdrawal(0).unwrap();
+            let new_account = AccountIdType::from([5; 32]);
+            contract.requeue_withdrawal(0, new_account).unwrap();
+
+            TestClock::advance(50);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.execute_withdrawal(0),
+                Err(EscrowError::NotRecipient)
+            );
+            ink::env::test::set_caller::<Environment>(new_account);
+            assert_eq!(contract.execute_withdrawal(0), Ok(()));
+            assert_eq!(contract.total_locked, 0);
+            assert_eq!(contract.pull_ledger_total(), 0);
+            assert_eq!(contract.pending_withdrawal(0), None);
+        }
+
+        #[ink::test]
+        fn large_settlement_waits_for_renter_confirmation() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_release_threshold(Some(100)).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.confirm_settlement(escrow_id),
+                Err(EscrowError::NoPendingSettlement)
+            );
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.lease_ended(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Expired));
+            assert_eq!(
+                contract.lease_ended(escrow_id),
+                Err(EscrowError::SettlementPending)
+            );
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.confirm_settlement(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Settled));
+            assert_eq!(contract.pending_settlement(escrow_id), None);
+        }
+
+        #[ink::test]
+        fn queued_settlement_executes_after_timeout() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_release_threshold(Some(100)).unwrap();
+            contract.set_settlement_timeout(50).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+
+            TestClock::advance(50);
+            assert_eq!(contract.lease_ended(escrow_id), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Settled));
+        }
+
+        #[ink::test]
+        fn pause_blocks_mutations_but_not_reads() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let owner = contract.owner();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_eq!(contract.pause(), Err(EscrowError::NotOwner));
+
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.pause(), Ok(()));
+            assert!(contract.paused());
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.rent(escrow_id), Err(EscrowError::Paused));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Open));
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.set_manager(escrow_id, AccountIdType::from([4; 32])),
+                Err(EscrowError::Paused)
+            );
+            assert_eq!(contract.revoke_manager(escrow_id), Err(EscrowError::Paused));
+            assert_eq!(contract.leave_program(escrow_id), Err(EscrowError::Paused));
+            assert_eq!(
+                contract.report_insurance_lapse(escrow_id),
+                Err(EscrowError::Paused)
+            );
+            assert_eq!(
+                contract.opt_into_inspection(escrow_id, Hash::from([5; 32])),
+                Err(EscrowError::Paused)
+            );
+            assert_eq!(
+                contract.reveal_inspection_secret(escrow_id, Hash::from([5; 32])),
+                Err(EscrowError::Paused)
+            );
+
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.unpause(), Ok(()));
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.rent(escrow_id), Ok(()));
+        }
+
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let mut contract = MyPSP34::new();
+            let owner = contract.owner();
+            let new_owner = AccountIdType::from([5; 32]);
+
+            ink::env::test::set_caller::<Environment>(new_owner);
+            assert_eq!(
+                contract.transfer_ownership(new_owner),
+                Err(EscrowError::NotOwner)
+            );
+
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.transfer_ownership(new_owner), Ok(()));
+            assert_eq!(contract.owner(), new_owner);
+            assert_eq!(contract.pause(), Err(EscrowError::NotOwner));
+        }
+
+        #[ink::test]
+        fn migrate_brings_storage_up_to_date() {
+            let mut contract = MyPSP34::new();
+            let owner = contract.owner();
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+            assert_eq!(contract.migrate(), Err(EscrowError::NothingToMigrate));
+
+            contract.storage_version = STORAGE_VERSION - 1;
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([5; 32]));
+            assert_eq!(contract.migrate(), Err(EscrowError::NotOwner));
+            assert_eq!(
+                contract.upgrade(Hash::from([7; 32])),
+                Err(EscrowError::NotOwner)
+            );
+
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.migrate(), Ok(()));
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+
+            // A version 1 deployment kept the registry and index in its root.
+            let arbiter = AccountIdType::from([4; 32]);
+            let escrow_id = Hash::from([9; 32]);
+            contract.storage_version = 1;
+            contract.legacy_arbiter_registry = vec![arbiter];
+            contract.legacy_escrow_index = vec![escrow_id];
+            contract.store_escrow(
+                escrow_id,
+                &Escrow {
+                    status: LeaseStatus::Active,
+                    escrow_balance: 10,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(contract.live_escrows.get_or_default(), 0);
+            assert_eq!(contract.migrate(), Ok(()));
+            assert_eq!(contract.live_escrows.get_or_default(), 1);
+            assert_eq!(contract.held_escrow_totals.get(&None), Some(10));
+            assert_eq!(contract.arbiters(), vec![arbiter]);
+            assert_eq!(contract.escrow_count(), 1);
+            assert_eq!(contract.escrow_ids(0, 10).items, vec![escrow_id]);
+            assert!(contract.legacy_arbiter_registry.is_empty());
+            assert!(contract.legacy_escrow_index.is_empty());
+        }
+
+        #[ink::test]
+        fn decommission_converts_leases_to_credits() {
+            let mut contract = MyPSP34::new();
+            let owner = contract.owner();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: DECOMMISSION_TIMELOCK,
+                        ..terms(100, 2 * DECOMMISSION_TIMELOCK)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            contract.pay_rent(escrow_id).unwrap();
+            let listing_id = Hash::from([8; 32]);
+            let alice = AccountIdType::from([4; 32]);
+            let bob = AccountIdType::from([5; 32]);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    listing_id,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        co_renters: vec![(alice, 6_000), (bob, 4_000)],
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(80);
+            contract.accept_lease(listing_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.set_fee(1_000).unwrap();
+            assert_eq!(
+                contract.decommission(),
+                Err(EscrowError::DecommissionNotScheduled)
+            );
+            contract.schedule_decommission().unwrap();
+            assert_eq!(contract.decommission(), Err(EscrowError::TimelockActive));
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(landlord, terms(100, 10)),
+                Err(EscrowError::Decommissioned)
+            );
+
+            TestClock::advance(DECOMMISSION_TIMELOCK);
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(contract.decommission(), Err(EscrowError::LeasesRemaining));
+            assert_eq!(contract.force_refund(vec![escrow_id, listing_id]), Ok(2));
+            assert_eq!(contract.credit_of(landlord, None), 90);
+            assert_eq!(contract.collected_fees(None), 10);
+            assert_eq!(contract.credit_of(renter, None), 100);
+            assert_eq!(contract.credit_of(bob, None), 80);
+            assert_eq!(contract.credit_of(alice, None), 0);
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Terminated));
+            assert_eq!(contract.decommission(), Ok(()));
+            assert!(contract.decommissioned());
+            assert_eq!(
+                contract.schedule_decommission(),
+                Err(EscrowError::Decommissioned)
+            );
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.withdraw_credit(None), Ok(()));
+            assert_eq!(contract.withdraw_credit(None), Err(EscrowError::NoCredit));
+            assert_eq!(contract.credit_of(landlord, None), 90);
+        }
+
+        #[ink::test]
+        fn failed_token_payout_is_queued() {
+            let mut contract = MyPSP34::new();
+            let token = AccountIdType::from([9; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.queue_failed_payout(token, landlord, 40).unwrap();
+            contract.queue_failed_payout(token, renter, 60).unwrap();
+
+            assert_eq!(
+                contract.failed_payouts(landlord, 0, 10).items,
+                vec![FailedPayout {
+                    id: 0,
+                    token,
+                    to: landlord,
+                    amount: 40,
+                }]
+            );
+            assert_eq!(contract.failed_payouts(renter, 0, 10).items[0].id, 1);
+            assert_eq!(contract.retry_payout(2), Err(EscrowError::PayoutNotFound));
+        }
+
+        #[ink::test]
+        fn platform_fee_is_taken_from_landlord_payouts() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let collector = AccountIdType::from([6; 32]);
+
+            assert_eq!(contract.set_fee(MAX_BPS + 1), Err(EscrowError::InvalidFee));
+            contract.set_fee(100).unwrap();
+            contract.set_fee_collector(collector).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(1_000, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(1_000);
+            contract.pay_rent(escrow_id).unwrap();
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+            assert_eq!(contract.collected_fees(None), 10);
+            assert_eq!(contract.total_locked, 10);
+
+            assert_eq!(
+                contract.withdraw_fees(None),
+                Err(EscrowError::NotFeeCollector)
+            );
+            ink::env::test::set_caller::<Environment>(collector);
+            assert_eq!(contract.withdraw_fees(None), Ok(()));
+            assert_eq!(contract.collected_fees(None), 0);
+            assert_eq!(contract.total_locked, 0);
+            assert_eq!(
+                contract.withdraw_fees(None),
+                Err(EscrowError::NothingToClaim)
+            );
+        }
+
+        #[ink::test]
+        fn fee_override_replaces_default_fee() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_fee(100).unwrap();
+            assert_eq!(
+                contract.set_fee_override(landlord, Some(MAX_BPS + 1)),
+                Err(EscrowError::InvalidFee)
+            );
+            contract.set_fee_override(landlord, Some(50)).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.set_fee_override(landlord, None),
+                Err(EscrowError::NotOwner)
+            );
+            let escrow_id = contract.create_escrow(landlord, terms(1_000, 10)).unwrap();
+            assert_eq!(contract.effective_fee(escrow_id), Some(50));
+            assert_eq!(contract.effective_fee(Hash::from([9; 32])), None);
+
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(1_000);
+            contract.pay_rent(escrow_id).unwrap();
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.lease_ended(escrow_id).unwrap();
+            assert_eq!(contract.collected_fees(None), 5);
+        }
+
+        #[ink::test]
+        fn tracked_token_balance_counts_escrows_fees_and_failed_payouts() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let token = AccountIdType::from([9; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    Hash::from([1; 32]),
+                    LeaseTerms {
+                        payment_token: Some(token),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract
+                .list_property(Hash::from([2; 32]), terms(100, 10))
+                .unwrap();
+            for id in [Hash::from([1; 32]), Hash::from([2; 32])].iter() {
+                let mut escrow = contract.get_escrow_or_revert(*id).unwrap();
+                escrow.escrow_balance = 100;
+                escrow.deposit_balance = 50;
+                contract.store_escrow(*id, &escrow);
+            }
+            let fees: Balance = 7;
+            contract.collected_fees.insert(Some(token), &fees);
+            contract.queue_failed_payout(token, landlord, 3).unwrap();
+
+            assert_eq!(contract.tracked_token_balance(token), 160);
+        }
+
+        #[ink::test]
+        fn recover_token_requires_schedule() {
+            let mut contract = MyPSP34::new();
+            let token = AccountIdType::from([9; 32]);
+            let to = AccountIdType::from([5; 32]);
+
+            assert_eq!(
+                contract.recover_token(token, to, 10),
+                Err(EscrowError::RecoveryNotScheduled)
+            );
+            ink::env::test::set_caller::<Environment>(to);
+            assert_eq!(
+                contract.recover_token(token, to, 10),
+                Err(EscrowError::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn allocate_follows_rounding_policy() {
+            let mut contract = MyPSP34::new();
+            let cases = [
+                (RoundingPolicy::FloorToUser, [2, 3, 3]),
+                (RoundingPolicy::FloorToProtocol, [3, 4, 3]),
+                (RoundingPolicy::Bankers, [2, 4, 3]),
+            ];
+            for (policy, expected) in cases.iter() {
+                contract.set_rounding_policy(*policy).unwrap();
+                assert_eq!(contract.rounding_policy(), *policy);
+                assert_eq!(contract.allocate(250, 100), expected[0]);
+                assert_eq!(contract.allocate(350, 100), expected[1]);
+                assert_eq!(contract.allocate(300, 100), expected[2]);
+            }
+        }
+
+        #[ink::test]
+        fn allocate_handles_max_balance() {
+            let mut contract = MyPSP34::new();
+            let half = Balance::MAX / 2;
+            let cases = [
+                (RoundingPolicy::FloorToUser, half),
+                (RoundingPolicy::FloorToProtocol, half + 1),
+                (RoundingPolicy::Bankers, half + 1),
+            ];
+            for (policy, expected) in cases.iter() {
+                contract.set_rounding_policy(*policy).unwrap();
+                assert_eq!(contract.allocate(Balance::MAX, MAX_BPS), Balance::MAX);
+                assert_eq!(contract.allocate(Balance::MAX, 5_000), *expected);
+            }
+        }
+
+        #[ink::test]
+        fn pay_rent_reports_overflow() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        cam_estimate: Balance::MAX,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(contract.pay_rent(escrow_id), Err(EscrowError::Overflow));
+            assert_eq!(contract.rent_due(escrow_id), Err(EscrowError::Overflow));
+        }
+
+        #[ink::test]
+        fn resolve_dispute_rejects_overflowing_shares() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let arbiter = AccountIdType::from([4; 32]);
+            contract.store_escrow(
+                escrow_id,
+                &Escrow {
+                    status: LeaseStatus::Disputed,
+                    arbiter: Some(arbiter),
+                    escrow_balance: 1,
+                    ..Default::default()
+                },
+            );
+
+            ink::env::test::set_caller::<Environment>(arbiter);
+            assert_eq!(
+                contract.resolve_dispute(escrow_id, Balance::MAX, 2),
+                Err(EscrowError::Overflow)
+            );
+        }
+
+        #[ink::test]
+        fn max_lease_duration_does_not_overflow() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+
+            let escrow_id = contract
+                .create_escrow(landlord, terms(100, u64::MAX))
+                .unwrap();
+            TestClock::set(10);
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.rent_due(escrow_id), Ok((10, 100)));
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.lease_ended(escrow_id),
+                Err(EscrowError::LeaseNotExpired)
+            );
+            assert_eq!(
+                contract.propose_renewal(escrow_id, u64::MAX, 100),
+                Err(EscrowError::RenewalNotDue)
+            );
+        }
+
+        #[ink::test]
+        fn repeated_settlements_round_identically() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_fee(100).unwrap();
+            contract
+                .set_rounding_policy(RoundingPolicy::Bankers)
+                .unwrap();
+            let mut escrow_ids = Vec::new();
+            for _ in 0..3 {
+                ink::env::test::set_caller::<Environment>(renter);
+                let escrow_id = contract.create_escrow(landlord, terms(350, 10)).unwrap();
+                contract.rent(escrow_id).unwrap();
+                ink::env::test::set_value_transferred::<Environment>(350);
+                contract.pay_rent(escrow_id).unwrap();
+                ink::env::test::set_value_transferred::<Environment>(0);
+                escrow_ids.push(escrow_id);
+            }
+
+            TestClock::advance(11);
+            ink::env::test::set_caller::<Environment>(landlord);
+            for (settled, escrow_id) in (1..).zip(escrow_ids) {
+                contract.lease_ended(escrow_id).unwrap();
+                assert_eq!(contract.collected_fees(None), 4 * settled);
+            }
+        }
+
+        #[ink::test]
+        fn cancel_lease_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let rent_amount = 100;
+            let lease_duration = 10;
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.cancel_lease(escrow_id), Ok(()));
+
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Terminated));
+            assert_eq!(contract.rent(escrow_id), Err(EscrowError::AlreadyLeased));
+        }
+
+        #[ink::test]
+        fn cancel_lease_refunds_renter() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.store_escrow(
+                escrow_id,
+                &Escrow {
+                    renter: Some(renter),
+                    landlord,
+                    rent_amount: 100,
+                    lease_duration: 10,
+                    payment_interval: 10,
+                    escrow_balance: 100,
+                    overpayment: 20,
+                    ..Default::default()
+                },
+            );
+            contract.total_locked = 120;
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.cancel_lease(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 0);
+            assert_eq!(escrow.overpayment, 0);
+            assert_eq!(contract.total_locked, 0);
+        }
+
+        #[ink::test]
+        fn refund_overpayment_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.refund_overpayment(escrow_id),
+                Err(EscrowError::NothingToClaim)
+            );
+            ink::env::test::set_value_transferred::<Environment>(150);
+            contract.pay_rent(escrow_id).unwrap();
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 100);
+            assert_eq!(escrow.overpayment, 50);
+
+            ink::env::test::set_value_transferred::<Environment>(0);
+            assert_eq!(contract.refund_overpayment(escrow_id), Ok(()));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.overpayment, 0);
+            assert_eq!(contract.total_locked, 100);
+        }
+
+        #[ink::test]
+        fn terminate_early_by_renter_forfeits_penalty() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 10,
+                        early_termination_penalty_periods: 1,
+                        ..terms(100, 40)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            for _ in 0..3 {
+                ink::env::test::set_value_transferred::<Environment>(100);
+                contract.pay_rent(escrow_id).unwrap();
+            }
+
+            let start = TestClock::now();
+            TestClock::set(start + 15);
+            assert_eq!(contract.terminate_early(escrow_id), Ok(()));
+
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Terminated));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 0);
+            assert_eq!(contract.balance_of(renter), 0);
+            assert_eq!(
+                contract.terminate_early(escrow_id),
+                Err(EscrowError::NotLeased)
+            );
+        }
+
+        #[ink::test]
+        fn terminate_early_rejects_outsiders() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_eq!(
+                contract.terminate_early(escrow_id),
+                Err(EscrowError::NotLeased)
+            );
+            contract.rent(escrow_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([4; 32]));
+            assert_eq!(
+                contract.terminate_early(escrow_id),
+                Err(EscrowError::NotParty)
+            );
+        }
+
+        #[ink::test]
+        fn sublease_ends_with_parent_lease() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let sub_renter = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 100)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.request_sublease(escrow_id, sub_renter, 60, 0),
+                Err(EscrowError::InvalidSubleaseDuration)
+            );
+            assert_eq!(
+                contract.request_sublease(escrow_id, sub_renter, 60, 101),
+                Err(EscrowError::SubleaseOutlastsLease)
+            );
+            contract
+                .request_sublease(escrow_id, sub_renter, 60, 50)
+                .unwrap();
+
+            assert_eq!(
+                contract.approve_sublease(escrow_id),
+                Err(EscrowError::NotLandlord)
+            );
+            ink::env::test::set_caller::<Environment>(landlord);
+            let child_id = contract.approve_sublease(escrow_id).unwrap();
+            assert_eq!(contract.subleases(escrow_id), vec![child_id]);
+            assert_eq!(contract.parent_lease(child_id), Some(escrow_id));
+            assert_eq!(contract.sublease_request(escrow_id), None);
+            let child = contract.get_escrow_or_revert(child_id).unwrap();
+            assert_eq!(child.landlord, renter);
+            assert_eq!(child.renter, Some(sub_renter));
+
+            ink::env::test::set_caller::<Environment>(sub_renter);
+            contract.rent(child_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.terminate_early(escrow_id), Ok(()));
+            assert_eq!(contract.status(child_id), Ok(LeaseStatus::Terminated));
+            assert!(contract.subleases(escrow_id).is_empty());
+        }
+
+        #[ink::test]
+        fn inspector_is_drawn_once_from_both_secrets() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let pool = [AccountIdType::from([7; 32]), AccountIdType::from([8; 32])];
+            for inspector in pool {
+                contract.add_inspector(inspector).unwrap();
+            }
+            let commit = |secret: Hash| {
+                let mut output = [0u8; 32];
+                ink::env::hash_encoded::<Blake2x256, _>(&secret, &mut output);
+                Hash::from(output)
+            };
+            let (renter_secret, landlord_secret) = (Hash::from([5; 32]), Hash::from([6; 32]));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract
+                .opt_into_inspection(escrow_id, commit(renter_secret))
+                .unwrap();
+            assert_eq!(
+                contract.reveal_inspection_secret(escrow_id, renter_secret),
+                Err(EscrowError::InspectionNotAgreed)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .opt_into_inspection(escrow_id, commit(landlord_secret))
+                .unwrap();
+            assert_eq!(
+                contract.opt_into_inspection(escrow_id, commit(renter_secret)),
+                Err(EscrowError::AlreadyCommitted)
+            );
+            assert_eq!(
+                contract.reveal_inspection_secret(escrow_id, renter_secret),
+                Err(EscrowError::InvalidReveal)
+            );
+            contract
+                .reveal_inspection_secret(escrow_id, landlord_secret)
+                .unwrap();
+            assert_eq!(contract.inspector_of(escrow_id), None);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            contract
+                .reveal_inspection_secret(escrow_id, renter_secret)
+                .unwrap();
+            let inspector = contract.inspector_of(escrow_id).unwrap();
+            assert!(pool.contains(&inspector));
+            assert_eq!(
+                contract.reveal_inspection_secret(escrow_id, renter_secret),
+                Err(EscrowError::AlreadyCommitted)
+            );
+        }
+
+        #[ink::test]
+        fn owner_appoints_inspector_when_a_secret_is_withheld() {
+            let mut contract = MyPSP34::new();
+            let owner = contract.owner();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let inspector = AccountIdType::from([7; 32]);
+            contract.add_inspector(inspector).unwrap();
+            let commit = |secret: Hash| {
+                let mut output = [0u8; 32];
+                ink::env::hash_encoded::<Blake2x256, _>(&secret, &mut output);
+                Hash::from(output)
+            };
+            let (renter_secret, landlord_secret) = (Hash::from([5; 32]), Hash::from([6; 32]));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract
+                .opt_into_inspection(escrow_id, commit(renter_secret))
+                .unwrap();
+            assert_eq!(contract.inspection_reveal_deadline(escrow_id), None);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .opt_into_inspection(escrow_id, commit(landlord_secret))
+                .unwrap();
+            let deadline = TestClock::now() + INSPECTION_REVEAL_WINDOW;
+            assert_eq!(
+                contract.inspection_reveal_deadline(escrow_id),
+                Some(deadline)
+            );
+            contract
+                .reveal_inspection_secret(escrow_id, landlord_secret)
+                .unwrap();
+
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(
+                contract.appoint_inspector(escrow_id, inspector),
+                Err(EscrowError::TimelockActive)
+            );
+            TestClock::set(deadline + 1);
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.reveal_inspection_secret(escrow_id, renter_secret),
+                Err(EscrowError::RevealWindowClosed)
+            );
+            assert_eq!(
+                contract.appoint_inspector(escrow_id, inspector),
+                Err(EscrowError::NotOwner)
+            );
+            ink::env::test::set_caller::<Environment>(owner);
+            assert_eq!(
+                contract.appoint_inspector(escrow_id, landlord),
+                Err(EscrowError::NoInspectorAvailable)
+            );
+            assert_eq!(contract.appoint_inspector(escrow_id, inspector), Ok(()));
+            assert_eq!(contract.inspector_of(escrow_id), Some(inspector));
+            assert_eq!(
+                contract.appoint_inspector(escrow_id, inspector),
+                Err(EscrowError::AlreadyCommitted)
+            );
+        }
+
+        #[ink::test]
+        fn manager_claims_rent_but_cannot_terminate() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let manager = AccountIdType::from([4; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+            assert_eq!(
+                contract.set_manager(escrow_id, manager),
+                Err(EscrowError::NotLandlord)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.set_manager(escrow_id, manager).unwrap();
+            assert_eq!(contract.manager(escrow_id), Some(manager));
+
+            let start = TestClock::now();
+            TestClock::set(start + 10);
+            ink::env::test::set_caller::<Environment>(manager);
+            assert_eq!(contract.claim_rent(escrow_id), Ok(()));
+            assert_eq!(
+                contract.terminate_early(escrow_id),
+                Err(EscrowError::NotParty)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.revoke_manager(escrow_id).unwrap();
+            assert_eq!(
+                contract.revoke_manager(escrow_id),
+                Err(EscrowError::NoManager)
+            );
+            ink::env::test::set_caller::<Environment>(manager);
+            assert_eq!(
+                contract.claim_rent(escrow_id),
+                Err(EscrowError::NotLandlord)
+            );
+        }
+
+        #[ink::test]
+        fn batch_results_are_reported_per_item() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let results = contract
+                .create_escrows(vec![
+                    (landlord, terms(100, 10)),
+                    (
+                        landlord,
+                        LeaseTerms {
+                            payment_interval: 0,
+                            ..terms(100, 10)
+                        },
+                    ),
+                    (landlord, terms(50, 10)),
+                ])
+                .unwrap();
+            assert_eq!(results[1], Err(EscrowError::InvalidPaymentInterval));
+            let (first, second) = (*results[0].as_ref().unwrap(), *results[2].as_ref().unwrap());
+            contract.rent(first).unwrap();
+            contract.rent(second).unwrap();
+            assert_eq!(
+                contract.create_escrows(vec![(landlord, terms(100, 10)); MAX_BATCH_SIZE + 1]),
+                Err(EscrowError::BatchTooLarge)
+            );
+
+            ink::env::test::set_value_transferred::<Environment>(120);
+            let results = contract.pay_rent_batch(vec![first, second]).unwrap();
+            assert_eq!(results, vec![Ok(()), Err(EscrowError::InsufficientRent)]);
+            assert_eq!(contract.total_locked, 100);
+            ink::env::test::set_value_transferred::<Environment>(0);
+
+            let start = TestClock::now();
+            TestClock::set(start + 10);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.claim_rent_batch(vec![first, second]),
+                Ok(vec![Ok(()), Err(EscrowError::NothingToClaim)])
+            );
+        }
+
+        #[ink::test]
+        fn failed_batch_item_leaves_no_payment_behind() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let with_cam = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        cam_estimate: 30,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            let plain = contract.create_escrow(landlord, terms(130, 10)).unwrap();
+            contract.rent(with_cam).unwrap();
+            contract.rent(plain).unwrap();
+
+            // The contract cannot forward the CAM estimate, so the first item
+            // fails once its payment would already have been collected.
+            let contract_account = ink::env::account_id::<Environment>();
+            ink::env::test::set_account_balance::<Environment>(contract_account, 20);
+            ink::env::test::set_value_transferred::<Environment>(130);
+            let results = contract.pay_rent_batch(vec![with_cam, plain]).unwrap();
+            assert_eq!(results, vec![Err(EscrowError::TransferFailed), Ok(())]);
+            assert_eq!(contract.total_locked, 130);
+            let escrow = contract.get_escrow_or_revert(with_cam).unwrap();
+            assert_eq!((escrow.periods_paid, escrow.escrow_balance), (0, 0));
+            assert_eq!(escrow.cam_collected, 0);
+            assert_eq!(contract.contribution(with_cam, renter), 0);
+            assert_eq!(contract.contribution(plain, renter), 130);
+        }
+
+        #[ink::test]
+        fn cancel_lease_fails_if_escrow_leased() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.cancel_lease(escrow_id),
+                Err(EscrowError::AlreadyLeased)
+            );
+            // A rejected message emits nothing.
+            assert_eq!(ink::env::test::recorded_events().count(), 2);
+        }
+
+        #[ink::test]
+        fn pay_deposit_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let security_deposit = 200;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: security_deposit,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(security_deposit);
+            assert_eq!(contract.pay_deposit(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_balance, security_deposit);
+            assert_eq!(escrow.escrow_balance, 0);
+            assert_eq!(
+                contract.pay_deposit(escrow_id),
+                Err(EscrowError::DepositAlreadyPaid)
+            );
+        }
+
+        #[ink::test]
+        fn pay_deposit_fails_before_lease_start() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+
+            assert_eq!(contract.pay_deposit(escrow_id), Err(EscrowError::NotLeased));
+        }
+
+        #[ink::test]
+        fn pay_deposit_fails_if_insufficient_deposit() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(199);
+
+            assert_eq!(
+                contract.pay_deposit(escrow_id),
+                Err(EscrowError::InsufficientDeposit)
+            );
+        }
+
+        #[ink::test]
+        fn release_deposit_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let lease_duration = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        ..terms(100, lease_duration)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+            contract.pay_deposit(escrow_id).unwrap();
+
+            TestClock::advance(lease_duration + 1);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.lease_ended(escrow_id),
+                Err(EscrowError::DepositNotSettled)
+            );
+            assert_eq!(
+                contract.release_deposit(escrow_id, 50, Hash::from([7; 32])),
+                Ok(())
+            );
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_balance, 0);
+            assert_eq!(contract.lease_ended(escrow_id), Ok(()));
+        }
+
+        #[ink::test]
+        fn release_deposit_portion_works() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+            contract.pay_deposit(escrow_id).unwrap();
+
+            assert_eq!(
+                contract.release_deposit_portion(escrow_id, 50),
+                Err(EscrowError::NotLandlord)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.release_deposit_portion(escrow_id, 201),
+                Err(EscrowError::AmountExceedsDeposit)
+            );
+            assert_eq!(contract.release_deposit_portion(escrow_id, 50), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_balance, 150);
+        }
+
+        #[ink::test]
+        fn joint_owners_must_approve_landlord_actions() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let co_owner = AccountIdType::from([4; 32]);
+            let other_owner = AccountIdType::from([5; 32]);
+            let joint_terms = |co_owners: Vec<AccountIdType>, threshold| LeaseTerms {
+                security_deposit: 200,
+                joint_ownership: Some(JointOwnership {
+                    co_owners,
+                    threshold,
+                }),
+                ..terms(100, 10)
+            };
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.create_escrow(landlord, joint_terms(vec![landlord], 1)),
+                Err(EscrowError::InvalidJointOwnership)
+            );
+            assert_eq!(
+                contract.create_escrow(landlord, joint_terms(vec![co_owner], 3)),
+                Err(EscrowError::InvalidJointOwnership)
+            );
+            let escrow_id = contract
+                .create_escrow(landlord, joint_terms(vec![co_owner, other_owner], 2))
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+            contract.pay_deposit(escrow_id).unwrap();
+
+            let action = LandlordAction::ReleaseDepositPortion { amount: 50 };
+            let action_id = contract.landlord_action_id(escrow_id, action);
+            assert_eq!(
+                contract.approve_action(escrow_id, action_id),
+                Err(EscrowError::NotLandlord)
+            );
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.approve_action(escrow_id, action_id).unwrap();
+            assert_eq!(
+                contract.release_deposit_portion(escrow_id, 50),
+                Err(EscrowError::ApprovalsMissing)
+            );
+
+            ink::env::test::set_caller::<Environment>(co_owner);
+            contract.approve_action(escrow_id, action_id).unwrap();
+            assert_eq!(contract.release_deposit_portion(escrow_id, 50), Ok(()));
+            assert!(contract.action_approvals(escrow_id, action_id).is_empty());
+            assert_eq!(
+                contract
+                    .get_escrow_or_revert(escrow_id)
+                    .unwrap()
+                    .deposit_balance,
+                150
+            );
+
+            let manager = AccountIdType::from([6; 32]);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.set_manager(escrow_id, manager),
+                Err(EscrowError::ApprovalsMissing)
+            );
+            let action_id =
+                contract.landlord_action_id(escrow_id, LandlordAction::SetManager { manager });
+            contract.approve_action(escrow_id, action_id).unwrap();
+            ink::env::test::set_caller::<Environment>(other_owner);
+            contract.approve_action(escrow_id, action_id).unwrap();
+            assert_eq!(contract.set_manager(escrow_id, manager), Ok(()));
+
+            // One owner cannot agree to move the funds on everyone's behalf.
+            let successor = AccountIdType::from([7; 32]);
+            ink::env::test::set_caller::<Environment>(co_owner);
+            assert_eq!(
+                contract.approve_migration(escrow_id, successor),
+                Err(EscrowError::ApprovalsMissing)
+            );
+            assert_eq!(contract.migration_proposal(escrow_id), None);
+        }
+
+        #[ink::test]
+        fn release_deposit_fails_if_deduction_exceeds_deposit() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let lease_duration = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        ..terms(100, lease_duration)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+            contract.pay_deposit(escrow_id).unwrap();
+
+            TestClock::advance(lease_duration + 1);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.release_deposit(escrow_id, 201, Hash::from([7; 32])),
+                Err(EscrowError::DeductionExceedsDeposit)
+            );
+        }
 
-    #[derive(Debug, PartialEq, Eq, TypeInfo)]
-    #[cfg_attr(feature = "ink-as-dependency", derive(scale_info::TypeInfo))]
-    pub struct Escrow {
-        renter: AccountId,
-        landlord: AccountId,
-        rent_amount: Balance,
-        lease_duration: u64,
-        lease_start_time: u64,
-        escrow_balance: Balance,
-        is_leased: bool,
-    }
+        #[ink::test]
+        fn approve_migration_waits_for_both_parties() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let successor = AccountIdType::from([5; 32]);
+            let other_successor = AccountIdType::from([6; 32]);
 
-    #[ink(storage)]
-    pub struct MyPSP34 {
-        escrows: HashMap<Hash, Escrow>,
-    }
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(contract.approve_migration(escrow_id, successor), Ok(()));
+            assert_eq!(
+                contract.migration_proposal(escrow_id),
+                Some(MigrationProposal {
+                    successor,
+                    renter_approved: true,
+                    landlord_approved: false,
+                })
+            );
 
-    impl MyPSP34 {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                escrows: HashMap::new(),
-            }
+            // Agreeing to a different successor starts a new proposal.
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.approve_migration(escrow_id, other_successor),
+                Ok(())
+            );
+            assert_eq!(
+                contract.migration_proposal(escrow_id),
+                Some(MigrationProposal {
+                    successor: other_successor,
+                    renter_approved: false,
+                    landlord_approved: true,
+                })
+            );
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Active));
+
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([7; 32]));
+            assert_eq!(
+                contract.approve_migration(escrow_id, successor),
+                Err(EscrowError::NotParty)
+            );
         }
 
-        #[ink(message)]
-        pub fn create_escrow(
-            &mut self,
-            escrow_id: Hash,
-            landlord: AccountId,
-            rent_amount: Balance,
-            lease_duration: u64,
-        ) {
-            let caller = self.env().caller();
-            let escrow = Escrow {
-                renter: caller,
-                landlord,
-                rent_amount,
-                lease_duration,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: false,
-            };
+        #[ink::test]
+        fn approve_migration_needs_signed_co_renters() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let alice = AccountIdType::from([3; 32]);
+            let bob = AccountIdType::from([4; 32]);
+            let successor = AccountIdType::from([5; 32]);
 
-            self.escrows.insert(escrow_id, escrow);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        co_renters: vec![(alice, 6_000), (bob, 4_000)],
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(bob);
+            ink::env::test::set_value_transferred::<Environment>(80);
+            contract.accept_lease(escrow_id).unwrap();
+
+            // Bob's deposit is held, so the landlord cannot move it alone.
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.approve_migration(escrow_id, successor), Ok(()));
+            assert_eq!(
+                contract.migration_proposal(escrow_id),
+                Some(MigrationProposal {
+                    successor,
+                    renter_approved: false,
+                    landlord_approved: true,
+                })
+            );
+            ink::env::test::set_caller::<Environment>(alice);
+            assert_eq!(
+                contract.approve_migration(escrow_id, successor),
+                Err(EscrowError::NotParty)
+            );
+
+            let partial_id = Hash::from([9; 32]);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    partial_id,
+                    LeaseTerms {
+                        allow_partial: true,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                contract.approve_migration(partial_id, successor),
+                Err(EscrowError::MigrationUnsupported)
+            );
         }
 
-        #[ink(message)]
-        pub fn rent(&mut self, escrow_id: Hash) {
-            let caller = self.env().caller();
-            let mut escrow = self.get_escrow_or_revert(escrow_id);
-            self.ensure_escrow_not_leased(&escrow);
-            self.ensure_caller_is_renter(&escrow, &caller);
+        #[ink::test]
+        fn approved_reimbursement_offsets_next_rent() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
 
-            escrow.lease_start_time = self.env().block_timestamp();
-            escrow.is_leased = true;
-            self.escrows.insert(escrow_id, escrow);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 10,
+                        ..terms(100, 30)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.claim_reimbursement(escrow_id, 0, Hash::from([7; 32])),
+                Err(EscrowError::ZeroAmount)
+            );
+            assert_eq!(
+                contract.claim_reimbursement(escrow_id, 40, Hash::from([7; 32])),
+                Ok(())
+            );
+            assert_eq!(
+                contract.claim_reimbursement(escrow_id, 40, Hash::from([7; 32])),
+                Err(EscrowError::ClaimPending)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.approve_reimbursement(escrow_id), Ok(()));
+            assert_eq!(contract.reimbursement_claim(escrow_id), None);
+            let start = TestClock::now();
+            assert_eq!(contract.rent_due(escrow_id), Ok((start, 60)));
+
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(60);
+            assert_eq!(contract.pay_rent(escrow_id), Ok(()));
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.rent_credit, 0);
+            assert_eq!(escrow.periods_paid, 1);
+            assert_eq!(contract.rent_due(escrow_id), Ok((start + 10, 100)));
         }
 
-        #[ink(message, payable)]
-        pub fn pay_rent(&mut self, escrow_id: Hash) {
-            let caller = self.env().caller();
-            let value = self.env().transferred_balance();
+        #[ink::test]
+        fn cam_reconciliation_credits_overpayment() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let statement_hash = Hash::from([9; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 10,
+                        cam_estimate: 20,
+                        ..terms(100, 30)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            assert_eq!(contract.rent_due(escrow_id), Ok((start, 120)));
+            for _ in 0..3 {
+                ink::env::test::set_value_transferred::<Environment>(120);
+                contract.pay_rent(escrow_id).unwrap();
+            }
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 300);
+            assert_eq!(escrow.cam_collected, 60);
 
-            let mut escrow = self.get_escrow_or_revert(escrow_id);
-            self.ensure_escrow_leased(&escrow);
-            self.ensure_caller_is_renter(&escrow, &caller);
-            self.ensure_rent_amount_paid(&escrow, value);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.submit_cam_statement(escrow_id, 45, statement_hash),
+                Err(EscrowError::ReconciliationNotDue)
+            );
+            TestClock::set(start + 30);
+            assert_eq!(
+                contract.submit_cam_statement(escrow_id, 45, statement_hash),
+                Ok(())
+            );
 
-            escrow.escrow_balance += value;
-            self.escrows.insert(escrow_id, escrow);
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.acknowledge_cam_statement(escrow_id, Hash::from([8; 32])),
+                Err(EscrowError::StatementMismatch)
+            );
+            assert_eq!(
+                contract.acknowledge_cam_statement(escrow_id, statement_hash),
+                Ok(())
+            );
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.rent_credit, 15);
+            assert_eq!(escrow.cam_charge, 0);
+            assert_eq!(escrow.cam_collected, 0);
+            assert_eq!(contract.cam_statement(escrow_id), None);
         }
 
-        #[ink(message)]
-        pub fn lease_ended(&mut self, escrow_id: Hash) {
-            let caller = self.env().caller();
-            let mut escrow = self.get_escrow_or_revert(escrow_id);
-            self.ensure_escrow_leased(&escrow);
-            self.ensure_caller_is_landlord(&escrow, &caller);
-            self.ensure_lease_duration_passed(&escrow);
+        #[ink::test]
+        fn cam_statement_requires_commercial_lease() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
 
-            let balance = escrow.escrow_balance;
-            self.env().transfer(caller, balance).expect("failed to transfer balance");
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
 
-            self.escrows.remove(&escrow_id);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.submit_cam_statement(escrow_id, 45, Hash::from([9; 32])),
+                Err(EscrowError::NotCommercial)
+            );
         }
 
-        #[ink(message)]
-        pub fn cancel_lease(&mut self, escrow_id: Hash) {
-            let caller = self.env().caller();
-            let mut escrow = self.get_escrow_or_revert(escrow_id);
-            self.ensure_escrow_not_leased(&escrow);
-            self.ensure_caller_is_landlord(&escrow, &caller);
+        #[ink::test]
+        fn unchallenged_sales_report_adds_percentage_rent() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        percentage_rent_bps: 500,
+                        sales_breakpoint: 1_000,
+                        ..terms(100, SALES_CHALLENGE_WINDOW * 2)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            assert_eq!(
+                contract.submit_sales_report(escrow_id, 3_000, Hash::from([5; 32])),
+                Ok(())
+            );
+            assert_eq!(
+                contract.submit_sales_report(escrow_id, 3_000, Hash::from([5; 32])),
+                Err(EscrowError::ReportPending)
+            );
+            assert_eq!(contract.rent_due(escrow_id), Ok((start, 100)));
 
-            let balance = escrow.escrow_balance;
-            self.env().transfer(caller, balance).expect("failed to transfer balance");
+            TestClock::set(start + SALES_CHALLENGE_WINDOW);
+            assert_eq!(contract.rent_due(escrow_id), Ok((start, 200)));
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.challenge_sales_report(escrow_id),
+                Err(EscrowError::ChallengeWindowClosed)
+            );
 
-            self.escrows.remove(&escrow_id);
+            ink::env::test::set_caller::<Environment>(renter);
+            ink::env::test::set_value_transferred::<Environment>(100);
+            assert_eq!(
+                contract.pay_rent(escrow_id),
+                Err(EscrowError::InsufficientRent)
+            );
+            ink::env::test::set_value_transferred::<Environment>(200);
+            assert_eq!(contract.pay_rent(escrow_id), Ok(()));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.escrow_balance, 100);
+            assert_eq!(escrow.percentage_rent_due, 0);
+            assert_eq!(contract.sales_report(escrow_id), None);
         }
 
-        fn get_escrow_or_revert(&self, escrow_id: Hash) -> Escrow {
-            let escrow = self
-                .escrows
-                .get(&escrow_id)
-                .expect("escrow does not exist");
-            *escrow
+        #[ink::test]
+        fn challenged_sales_report_opens_dispute() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        percentage_rent_bps: 500,
+                        arbiter: Some(AccountIdType::from([4; 32])),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            contract
+                .submit_sales_report(escrow_id, 3_000, Hash::from([5; 32]))
+                .unwrap();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.challenge_sales_report(escrow_id), Ok(()));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.status, LeaseStatus::Disputed);
+            assert_eq!(escrow.dispute_reason, Some(Hash::from([5; 32])));
+            assert_eq!(contract.sales_report(escrow_id), None);
         }
 
-        fn ensure_escrow_not_leased(&self, escrow: &Escrow) {
-            assert!(
-                !escrow.is_leased,
-                "escrow is already leased"
+        #[ink::test]
+        fn accept_renewal_extends_lease() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_renewal_window(20).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        payment_interval: 10,
+                        ..terms(100, 100)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.propose_renewal(escrow_id, 50, 120),
+                Err(EscrowError::RenewalNotDue)
+            );
+            TestClock::set(start + 85);
+            assert_eq!(
+                contract.propose_renewal(escrow_id, 55, 120),
+                Err(EscrowError::InvalidPaymentInterval)
+            );
+            assert_eq!(contract.propose_renewal(escrow_id, 50, 120), Ok(()));
+            assert_eq!(
+                contract.renewal_proposal(escrow_id),
+                Some(RenewalProposal {
+                    new_duration: 50,
+                    new_rent_amount: 120,
+                    expires_at: start + 105,
+                })
             );
-        }
 
-        fn ensure_escrow_leased(&self, escrow: &Escrow) {
-            assert!(
-                escrow.is_leased,
-                "escrow is not leased yet"
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.accept_renewal(escrow_id), Ok(()));
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.lease_duration, 150);
+            assert_eq!(escrow.rent_amount, 120);
+            assert_eq!(
+                contract.accept_renewal(escrow_id),
+                Err(EscrowError::NoPendingRenewal)
             );
         }
 
-        fn ensure_caller_is_renter(&self, escrow: &Escrow, caller: &AccountId) {
-            assert!(
-                *caller == escrow.renter,
-                "caller is not the renter"
+        #[ink::test]
+        fn renewal_proposal_expires() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+
+            contract.set_renewal_window(20).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.propose_renewal(escrow_id, 10, 120).unwrap();
+            TestClock::set(start + 21);
+            assert_eq!(contract.renewal_proposal(escrow_id), None);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.accept_renewal(escrow_id),
+                Err(EscrowError::NoPendingRenewal)
             );
         }
 
-        fn ensure_caller_is_landlord(&self, escrow: &Escrow, caller: &AccountId) {
-            assert!(
-                *caller == escrow.landlord,
-                "caller is not the landlord"
+        #[ink::test]
+        fn lapsed_insurance_blocks_renewal() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let insurer = AccountIdType::from([6; 32]);
+            let policy = Hash::from([7; 32]);
+
+            contract.set_renewal_window(20).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        insurance: Some(InsuranceRequirement {
+                            insurer,
+                            block_renewal: true,
+                        }),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            assert!(!contract.insurance_compliant(escrow_id));
+            assert_eq!(
+                contract.attest_insurance(escrow_id, policy, start + 5),
+                Err(EscrowError::NotInsurer)
+            );
+
+            ink::env::test::set_caller::<Environment>(insurer);
+            assert_eq!(
+                contract.attest_insurance(escrow_id, policy, start),
+                Err(EscrowError::InvalidPolicyExpiry)
+            );
+            contract
+                .attest_insurance(escrow_id, policy, start + 5)
+                .unwrap();
+            assert!(contract.insurance_compliant(escrow_id));
+            assert_eq!(
+                contract.report_insurance_lapse(escrow_id),
+                Err(EscrowError::InsuranceNotLapsed)
+            );
+
+            TestClock::set(start + 5);
+            assert!(!contract.insurance_compliant(escrow_id));
+            assert_eq!(contract.report_insurance_lapse(escrow_id), Ok(()));
+            assert_eq!(
+                contract.report_insurance_lapse(escrow_id),
+                Err(EscrowError::InsuranceNotLapsed)
+            );
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract.propose_renewal(escrow_id, 10, 100).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(
+                contract.accept_renewal(escrow_id),
+                Err(EscrowError::InsuranceLapsed)
             );
+
+            ink::env::test::set_caller::<Environment>(insurer);
+            contract
+                .attest_insurance(escrow_id, policy, start + 30)
+                .unwrap();
+            assert!(!contract.insurance_status(escrow_id).unwrap().lapse_reported);
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.accept_renewal(escrow_id), Ok(()));
         }
 
-        fn ensure_rent_amount_paid(&self, escrow: &Escrow, value: Balance) {
-            assert!(
-                value >= escrow.rent_amount,
-                "insufficient rent amount"
+        #[ink::test]
+        fn rejected_reimbursement_opens_dispute() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let invoice_hash = Hash::from([7; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(AccountIdType::from([4; 32])),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            contract
+                .claim_reimbursement(escrow_id, 40, invoice_hash)
+                .unwrap();
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.reject_reimbursement(escrow_id), Ok(()));
+            assert_eq!(
+                contract.reject_reimbursement(escrow_id),
+                Err(EscrowError::NoPendingClaim)
             );
+
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.status, LeaseStatus::Disputed);
+            assert_eq!(escrow.dispute_reason, Some(invoice_hash));
+            assert_eq!(escrow.rent_credit, 0);
         }
 
-        fn ensure_lease_duration_passed(&self, escrow: &Escrow) {
-            let current_time = self.env().block_timestamp();
-            assert!(
-                escrow.lease_start_time + escrow.lease_duration <= current_time,
-                "lease duration not yet passed"
+        #[ink::test]
+        fn raise_dispute_freezes_settlement() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let arbiter = AccountIdType::from([4; 32]);
+            let lease_duration = 10;
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(arbiter),
+                        ..terms(100, lease_duration)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            assert_eq!(
+                contract.raise_dispute(escrow_id, Hash::from([9; 32])),
+                Ok(())
             );
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Disputed));
+            assert_eq!(
+                contract.raise_dispute(escrow_id, Hash::from([9; 32])),
+                Err(EscrowError::Disputed)
+            );
+
+            TestClock::advance(lease_duration + 1);
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(contract.lease_ended(escrow_id), Err(EscrowError::Disputed));
+            assert_eq!(contract.claim_rent(escrow_id), Err(EscrowError::Disputed));
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink_env::{AccountId as AccountIdType, Environment};
-        use ink_lang as ink;
-        use ink_test::utils::{DefaultEnvironment, DefaultAccounts};
+        #[ink::test]
+        fn activity_feed_merges_records_in_order() {
+            let mut contract = MyPSP34::new();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let arbiter = AccountIdType::from([4; 32]);
+            let reason = Hash::from([9; 32]);
+
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(arbiter),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(0);
+            TestClock::set(start + 3);
+            contract.raise_dispute(escrow_id, reason).unwrap();
+
+            assert_eq!(
+                contract.activity(escrow_id, 0, 10).items,
+                vec![
+                    ActivityEntry {
+                        timestamp: start,
+                        activity: Activity::Payment {
+                            payer: renter,
+                            amount: 100,
+                        },
+                    },
+                    ActivityEntry {
+                        timestamp: start + 3,
+                        activity: Activity::DisputeRaised {
+                            raised_by: renter,
+                            reason_hash: reason,
+                        },
+                    },
+                ]
+            );
+            assert_eq!(contract.activity(escrow_id, 1, 10).items.len(), 1);
+            assert!(contract.activity(escrow_id, 2, 10).items.is_empty());
+        }
 
         #[ink::test]
-        fn create_escrow_works() {
+        fn payments_are_recorded_as_receipts() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
-            let rent_amount = 100;
-            let lease_duration = 10;
+            let renter = AccountIdType::from([3; 32]);
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 50,
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            let start = TestClock::now();
+            ink::env::test::set_value_transferred::<Environment>(50);
+            contract.pay_deposit(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
 
-            let escrow = contract.get_escrow_or_revert(escrow_id);
-            assert_eq!(escrow.renter, AccountIdType::from([0x0; 32]));
-            assert_eq!(escrow.landlord, landlord);
-            assert_eq!(escrow.rent_amount, rent_amount);
-            assert_eq!(escrow.lease_duration, lease_duration);
-            assert_eq!(escrow.lease_start_time, 0);
-            assert_eq!(escrow.escrow_balance, 0);
-            assert_eq!(escrow.is_leased, false);
+            assert_eq!(contract.payment_count(escrow_id), 2);
+            assert_eq!(
+                contract.payment_at(escrow_id, 0),
+                Some(PaymentRecord {
+                    payer: renter,
+                    amount: 50,
+                    timestamp: start,
+                    period: 0,
+                    kind: PaymentKind::Deposit,
+                })
+            );
+            let rent = contract.payment_at(escrow_id, 1).unwrap();
+            assert_eq!((rent.amount, rent.kind), (100, PaymentKind::Rent));
+            assert_eq!(contract.payment_at(escrow_id, 2), None);
         }
 
         #[ink::test]
-        fn rent_works() {
+        fn raise_dispute_fails_without_arbiter() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
-            let rent_amount = 100;
-            let lease_duration = 10;
             let renter = AccountIdType::from([3; 32]);
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
-            contract.env().set_caller(renter);
-            contract.rent(escrow_id);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract.create_escrow(landlord, terms(100, 10)).unwrap();
+            assert_eq!(
+                contract.raise_dispute(escrow_id, Hash::from([9; 32])),
+                Err(EscrowError::NoArbiter)
+            );
 
-            let escrow = contract.get_escrow_or_revert(escrow_id);
-            assert_eq!(escrow.is_leased, true);
-            assert_eq!(escrow.lease_start_time > 0, true);
+            ink::env::test::set_caller::<Environment>(AccountIdType::from([5; 32]));
+            assert_eq!(
+                contract.raise_dispute(escrow_id, Hash::from([9; 32])),
+                Err(EscrowError::NotParty)
+            );
         }
 
         #[ink::test]
-        fn pay_rent_works() {
+        fn raise_dispute_fails_before_lease_start() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
-            let rent_amount = 100;
-            let lease_duration = 10;
             let renter = AccountIdType::from([3; 32]);
-            let rent_payment = 150;
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
-            contract.env().set_caller(renter);
-            contract.rent(escrow_id);
-            contract.env().set_transferred_value(rent_payment);
-            contract.pay_rent(escrow_id);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(AccountIdType::from([4; 32])),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            assert_eq!(
+                contract.raise_dispute(escrow_id, Hash::from([9; 32])),
+                Err(EscrowError::InvalidTransition {
+                    from: LeaseStatus::Open,
+                    to: LeaseStatus::Disputed,
+                })
+            );
+        }
+
+        #[ink::test]
+        fn request_arbiter_reassignment_works() {
+            let mut contract = MyPSP34::new();
+            let owner = ink::env::caller::<Environment>();
+            let landlord = AccountIdType::from([2; 32]);
+            let renter = AccountIdType::from([3; 32]);
+            let arbiter = AccountIdType::from([4; 32]);
+            let backup = AccountIdType::from([5; 32]);
+            let window = 100;
+
+            contract.add_arbiter(arbiter).unwrap();
+            contract.add_arbiter(backup).unwrap();
+            contract.set_arbiter_response_window(window).unwrap();
+            assert_eq!(contract.arbiters(), vec![arbiter, backup]);
 
-            let escrow = contract.get_escrow_or_revert(escrow_id);
-            assert_eq!(escrow.escrow_balance, rent_payment);
+            ink::env::test::set_caller::<Environment>(renter);
+            assert_eq!(contract.add_arbiter(renter), Err(EscrowError::NotOwner));
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(arbiter),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            contract
+                .raise_dispute(escrow_id, Hash::from([9; 32]))
+                .unwrap();
+            assert_eq!(
+                contract.request_arbiter_reassignment(escrow_id),
+                Err(EscrowError::ArbiterStillActive)
+            );
+
+            TestClock::advance(window + 1);
+            assert_eq!(contract.request_arbiter_reassignment(escrow_id), Ok(()));
+
+            // The inactive arbiter is skipped.
+            let escrow = contract.get_escrow_or_revert(escrow_id).unwrap();
+            assert_eq!(escrow.arbiter, Some(backup));
+            assert_eq!(escrow.dispute_reason, Some(Hash::from([9; 32])));
+            assert_eq!(escrow.status, LeaseStatus::Disputed);
+
+            ink::env::test::set_caller::<Environment>(owner);
+            contract.remove_arbiter(arbiter).unwrap();
+            ink::env::test::set_caller::<Environment>(renter);
+            TestClock::advance(window + 1);
+            assert_eq!(
+                contract.request_arbiter_reassignment(escrow_id),
+                Err(EscrowError::NoArbiterAvailable)
+            );
         }
 
         #[ink::test]
-        fn lease_ended_works() {
+        fn lease_status_transitions() {
+            use LeaseStatus::*;
+            assert!(Open.can_transition_to(Active));
+            assert!(Open.can_transition_to(Terminated));
+            assert!(Active.can_transition_to(Disputed));
+            assert!(Active.can_transition_to(Settled));
+            assert!(Disputed.can_transition_to(Settled));
+            assert!(!Open.can_transition_to(Settled));
+            assert!(!Active.can_transition_to(Open));
+            assert!(!Disputed.can_transition_to(Active));
+            assert!(!Settled.can_transition_to(Active));
+            assert!(!Terminated.can_transition_to(Active));
+        }
+
+        #[ink::test]
+        fn resolve_dispute_works() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
-            let rent_amount = 100;
-            let lease_duration = 10;
             let renter = AccountIdType::from([3; 32]);
-            let rent_payment = 150;
+            let arbiter = AccountIdType::from([4; 32]);
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
-            contract.env().set_caller(renter);
-            contract.rent(escrow_id);
-            contract.env().set_transferred_value(rent_payment);
-            contract.pay_rent(escrow_id);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        arbiter: Some(arbiter),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
 
-            // Increase block timestamp to simulate lease duration passed
-            let current_time = contract.env().block_timestamp() + lease_duration + 1;
-            contract.env().set_block_timestamp(current_time);
+            ink::env::test::set_caller::<Environment>(arbiter);
+            assert_eq!(
+                contract.resolve_dispute(escrow_id, 40, 60),
+                Err(EscrowError::NotDisputed)
+            );
 
-            contract.env().set_caller(landlord);
-            contract.lease_ended(escrow_id);
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .raise_dispute(escrow_id, Hash::from([9; 32]))
+                .unwrap();
+            assert_eq!(
+                contract.resolve_dispute(escrow_id, 40, 60),
+                Err(EscrowError::NotArbiter)
+            );
 
-            let escrow = contract.get_escrow_or_revert(escrow_id);
-            assert_eq!(escrow.escrow_balance, 0);
+            ink::env::test::set_caller::<Environment>(arbiter);
+            assert_eq!(
+                contract.resolve_dispute(escrow_id, 50, 60),
+                Err(EscrowError::InvalidShares)
+            );
+            assert_eq!(contract.resolve_dispute(escrow_id, 40, 60), Ok(()));
+            assert_eq!(contract.status(escrow_id), Ok(LeaseStatus::Settled));
         }
 
         #[ink::test]
-        fn cancel_lease_works() {
+        fn resolve_dispute_splits_renter_share_among_co_renters() {
+            let mut contract = MyPSP34::new();
+            let escrow_id = Hash::from([1; 32]);
+            let landlord = AccountIdType::from([2; 32]);
+            let alice = AccountIdType::from([3; 32]);
+            let bob = AccountIdType::from([4; 32]);
+            let arbiter = AccountIdType::from([5; 32]);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            contract
+                .list_property(
+                    escrow_id,
+                    LeaseTerms {
+                        arbiter: Some(arbiter),
+                        co_renters: vec![(alice, 6_000), (bob, 4_000)],
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            ink::env::test::set_caller::<Environment>(alice);
+            contract.accept_lease(escrow_id).unwrap();
+            ink::env::test::set_caller::<Environment>(bob);
+            contract.accept_lease(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            contract
+                .raise_dispute(escrow_id, Hash::from([9; 32]))
+                .unwrap();
+
+            ink::env::test::set_account_balance::<Environment>(
+                ink::env::account_id::<Environment>(),
+                1_000,
+            );
+            ink::env::test::set_account_balance::<Environment>(alice, 0);
+            ink::env::test::set_account_balance::<Environment>(bob, 0);
+            ink::env::test::set_caller::<Environment>(arbiter);
+            assert_eq!(contract.resolve_dispute(escrow_id, 50, 50), Ok(()));
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(alice),
+                Ok(30)
+            );
+            assert_eq!(
+                ink::env::test::get_account_balance::<Environment>(bob),
+                Ok(20)
+            );
+        }
+
+        #[ink::test]
+        fn report_health_tracks_locked_funds_and_disputes() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
-            let rent_amount = 100;
-            let lease_duration = 10;
             let renter = AccountIdType::from([3; 32]);
-            let rent_payment = 150;
+            let arbiter = AccountIdType::from([4; 32]);
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
-            contract.env().set_caller(renter);
-            contract.rent(escrow_id);
-            contract.env().set_transferred_value(rent_payment);
-            contract.pay_rent(escrow_id);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(
+                    landlord,
+                    LeaseTerms {
+                        security_deposit: 200,
+                        arbiter: Some(arbiter),
+                        ..terms(100, 10)
+                    },
+                )
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(200);
+            contract.pay_deposit(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(100);
+            contract.pay_rent(escrow_id).unwrap();
+            contract
+                .raise_dispute(escrow_id, Hash::from([9; 32]))
+                .unwrap();
+            assert_eq!(contract.total_locked, 300);
+            assert_eq!(contract.active_disputes, 1);
 
-            contract.env().set_caller(landlord);
-            contract.cancel_lease(escrow_id);
+            let events_before = ink::env::test::recorded_events().count();
+            contract.report_health();
+            assert_eq!(ink::env::test::recorded_events().count(), events_before + 1);
 
-            let escrow = contract.get_escrow_or_revert(escrow_id);
-            assert_eq!(escrow.escrow_balance, 0);
+            ink::env::test::set_caller::<Environment>(arbiter);
+            contract.resolve_dispute(escrow_id, 200, 100).unwrap();
+            assert_eq!(contract.total_locked, 0);
+            assert_eq!(contract.active_disputes, 0);
         }
 
         #[ink::test]
-        #[should_panic(expected = "escrow does not exist")]
-        fn get_escrow_or_revert_panics_if_escrow_not_found() {
+        fn get_escrow_or_revert_fails_if_escrow_not_found() {
             let contract = MyPSP34::new();
-            let escrow_id = [1; 32];
-            contract.get_escrow_or_revert(escrow_id);
+            let escrow_id = Hash::from([1; 32]);
+            assert_eq!(
+                contract.get_escrow_or_revert(escrow_id),
+                Err(EscrowError::EscrowNotFound)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "escrow is already leased")]
-        fn ensure_escrow_not_leased_panics_if_escrow_leased() {
+        fn ensure_escrow_not_leased_fails_if_escrow_leased() {
             let contract = MyPSP34::new();
             let escrow = Escrow {
-                renter: Default::default(),
-                landlord: Default::default(),
-                rent_amount: 0,
-                lease_duration: 0,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: true,
+                status: LeaseStatus::Active,
+                ..Default::default()
             };
-            contract.ensure_escrow_not_leased(&escrow);
+            assert_eq!(
+                contract.ensure_escrow_not_leased(&escrow),
+                Err(EscrowError::AlreadyLeased)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "escrow is not leased yet")]
-        fn ensure_escrow_leased_panics_if_escrow_not_leased() {
+        fn ensure_escrow_leased_fails_if_escrow_not_leased() {
             let contract = MyPSP34::new();
-            let escrow = Escrow {
-                renter: Default::default(),
-                landlord: Default::default(),
-                rent_amount: 0,
-                lease_duration: 0,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: false,
-            };
-            contract.ensure_escrow_leased(&escrow);
+            let escrow = Escrow::default();
+            assert_eq!(
+                contract.ensure_escrow_leased(&escrow),
+                Err(EscrowError::NotLeased)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "caller is not the renter")]
-        fn ensure_caller_is_renter_panics_if_caller_not_renter() {
+        fn ensure_caller_is_renter_fails_if_caller_not_renter() {
             let contract = MyPSP34::new();
             let escrow = Escrow {
-                renter: AccountIdType::from([1; 32]),
-                landlord: Default::default(),
-                rent_amount: 0,
-                lease_duration: 0,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: false,
+                renter: Some(AccountIdType::from([1; 32])),
+                ..Default::default()
             };
             let caller = AccountIdType::from([2; 32]);
-            contract.ensure_caller_is_renter(&escrow, &caller);
+            assert_eq!(
+                contract.ensure_caller_is_renter(&escrow, &caller),
+                Err(EscrowError::NotRenter)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "caller is not the landlord")]
-        fn ensure_caller_is_landlord_panics_if_caller_not_landlord() {
+        fn ensure_caller_is_landlord_fails_if_caller_not_landlord() {
             let contract = MyPSP34::new();
             let escrow = Escrow {
-                renter: Default::default(),
                 landlord: AccountIdType::from([1; 32]),
-                rent_amount: 0,
-                lease_duration: 0,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: false,
+                ..Default::default()
             };
             let caller = AccountIdType::from([2; 32]);
-            contract.ensure_caller_is_landlord(&escrow, &caller);
+            assert_eq!(
+                contract.ensure_caller_is_landlord(&escrow, &caller),
+                Err(EscrowError::NotLandlord)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "insufficient rent amount")]
-        fn ensure_rent_amount_paid_panics_if_insufficient_rent() {
+        fn ensure_rent_amount_paid_fails_if_insufficient_rent() {
             let contract = MyPSP34::new();
             let escrow = Escrow {
-                renter: Default::default(),
-                landlord: Default::default(),
                 rent_amount: 100,
-                lease_duration: 0,
-                lease_start_time: 0,
-                escrow_balance: 0,
-                is_leased: false,
+                ..Default::default()
             };
             let value = 50;
-            contract.ensure_rent_amount_paid(&escrow, value);
+            assert_eq!(
+                contract.ensure_rent_amount_paid(&escrow, value),
+                Err(EscrowError::InsufficientRent)
+            );
         }
 
         #[ink::test]
-        #[should_panic(expected = "lease duration not yet passed")]
-        fn ensure_lease_duration_passed_panics_if_lease_duration_not_passed() {
+        fn ensure_lease_duration_passed_fails_if_lease_duration_not_passed() {
             let mut contract = MyPSP34::new();
-            let escrow_id = [1; 32];
             let landlord = AccountIdType::from([2; 32]);
             let rent_amount = 100;
             let lease_duration = 10;
             let renter = AccountIdType::from([3; 32]);
             let rent_payment = 150;
 
-            contract.create_escrow(escrow_id, landlord, rent_amount, lease_duration);
-            contract.env().set_caller(renter);
-            contract.rent(escrow_id);
-            contract.env().set_transferred_value(rent_payment);
+            ink::env::test::set_caller::<Environment>(renter);
+            let escrow_id = contract
+                .create_escrow(landlord, terms(rent_amount, lease_duration))
+                .unwrap();
+            contract.rent(escrow_id).unwrap();
+            ink::env::test::set_value_transferred::<Environment>(rent_payment);
 
             // Increase block timestamp to simulate lease duration not passed
-            let current_time = contract.env().block_timestamp() + lease_duration - 1;
-            contract.env().set_block_timestamp(current_time);
+            TestClock::advance(lease_duration - 1);
+
+            ink::env::test::set_caller::<Environment>(landlord);
+            assert_eq!(
+                contract.lease_ended(escrow_id),
+                Err(EscrowError::LeaseNotExpired)
+            );
+        }
+    }
+
+    /// End-to-end tests against a running `substrate-contracts-node`, run
+    /// with `cargo test --features e2e-tests`. Unlike the unit tests every
+    /// call is signed by the dev account playing that party and moves real
+    /// balances.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        const RENT: Balance = 1_000_000_000;
+        const DEPOSIT: Balance = 2_000_000_000;
+        /// The node stamps each block with the wall-clock time, so periods
+        /// are kept short and waited out.
+        const PERIOD: u64 = 3_000;
+
+        fn wait(millis: u64) {
+            std::thread::sleep(std::time::Duration::from_millis(millis));
+        }
+
+        #[ink_e2e::test]
+        async fn lease_lifecycle_moves_real_balances(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let landlord = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+            let renter = ink_e2e::account_id(ink_e2e::AccountKeyring::Charlie);
+            let contract_id = client
+                .instantiate("my_psp34", &ink_e2e::alice(), MyPSP34Ref::new(), 0, None)
+                .await
+                .expect("instantiate failed")
+                .account_id;
+            let escrow_id = Hash::from([7; 32]);
+            let terms = LeaseTerms {
+                rent_amount: RENT,
+                lease_duration: 3 * PERIOD,
+                security_deposit: DEPOSIT,
+                payment_interval: PERIOD,
+                ..LeaseTerms::default()
+            };
+
+            let list = build_message::<MyPSP34Ref>(contract_id.clone())
+                .call(|contract| contract.list_property(escrow_id, terms.clone()));
+            let listed = client.call(&ink_e2e::bob(), list, 0, None).await;
+            assert_eq!(listed.expect("list_property failed").return_value(), Ok(()));
+
+            let held_before = client.balance(contract_id.clone()).await?;
+            let accept = build_message::<MyPSP34Ref>(contract_id.clone())
+                .call(|contract| contract.accept_lease(escrow_id));
+            let accepted = client
+                .call(&ink_e2e::charlie(), accept, DEPOSIT, None)
+                .await;
+            assert_eq!(
+                accepted.expect("accept_lease failed").return_value(),
+                Ok(())
+            );
+
+            for period in 0..3 {
+                if period > 0 {
+                    wait(PERIOD);
+                }
+                let pay = build_message::<MyPSP34Ref>(contract_id.clone())
+                    .call(|contract| contract.pay_rent(escrow_id));
+                let paid = client.call(&ink_e2e::charlie(), pay, RENT, None).await;
+                assert_eq!(paid.expect("pay_rent failed").return_value(), Ok(()));
+            }
+            assert_eq!(
+                client.balance(contract_id.clone()).await?,
+                held_before + DEPOSIT + 3 * RENT
+            );
+
+            wait(PERIOD + 1_000);
+            let release = build_message::<MyPSP34Ref>(contract_id.clone())
+                .call(|contract| contract.release_deposit(escrow_id, 0, Hash::default()));
+            let rejected = client
+                .call_dry_run(&ink_e2e::charlie(), &release, 0, None)
+                .await;
+            assert_eq!(rejected.return_value(), Err(EscrowError::NotLandlord));
+            let renter_before = client.balance(renter).await?;
+            let released = client.call(&ink_e2e::bob(), release, 0, None).await;
+            assert_eq!(
+                released.expect("release_deposit failed").return_value(),
+                Ok(())
+            );
+            assert_eq!(client.balance(renter).await?, renter_before + DEPOSIT);
+
+            // A keeper settles so the landlord's balance moves by the rent alone.
+            let landlord_before = client.balance(landlord).await?;
+            let settle = build_message::<MyPSP34Ref>(contract_id.clone())
+                .call(|contract| contract.settle_expired(escrow_id));
+            let settled = client.call(&ink_e2e::dave(), settle, 0, None).await;
+            assert_eq!(
+                settled.expect("settle_expired failed").return_value(),
+                Ok(())
+            );
+            assert_eq!(client.balance(landlord).await?, landlord_before + 3 * RENT);
+            assert_eq!(client.balance(contract_id.clone()).await?, held_before);
 
-            contract.env().set_caller(landlord);
-            contract.lease_ended(escrow_id);
+            let status = build_message::<MyPSP34Ref>(contract_id.clone())
+                .call(|contract| contract.status(escrow_id));
+            let status = client
+                .call_dry_run(&ink_e2e::dave(), &status, 0, None)
+                .await;
+            assert_eq!(status.return_value(), Ok(LeaseStatus::Settled));
+            Ok(())
         }
     }
 }